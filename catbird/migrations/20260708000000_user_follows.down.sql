@@ -0,0 +1,2 @@
+ALTER TABLE push_accounts DROP COLUMN IF EXISTS last_follow_sync_at;
+DROP TABLE IF EXISTS user_follows;
@@ -0,0 +1,16 @@
+-- Accounts each push recipient follows, for the `include: "follows"`
+-- preference filter. `follow_rkey` lets a proxied deleteRecord remove the
+-- row directly; NULL when the follow was created without a client-chosen rkey.
+CREATE TABLE IF NOT EXISTS user_follows (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_did TEXT NOT NULL,
+    followed_did TEXT NOT NULL,
+    follow_rkey TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(user_did, followed_did)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_follows_user_did ON user_follows(user_did);
+CREATE INDEX IF NOT EXISTS idx_user_follows_rkey ON user_follows(user_did, follow_rkey);
+
+ALTER TABLE push_accounts ADD COLUMN IF NOT EXISTS last_follow_sync_at TIMESTAMPTZ;
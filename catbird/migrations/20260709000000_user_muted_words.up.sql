@@ -0,0 +1,7 @@
+-- Muted words from each push recipient's `mutedWordsPref`, stored as the
+-- normalised item array so matching needs a single lookup.
+CREATE TABLE IF NOT EXISTS user_muted_words (
+    user_did TEXT PRIMARY KEY,
+    muted_words_json JSONB NOT NULL DEFAULT '[]'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
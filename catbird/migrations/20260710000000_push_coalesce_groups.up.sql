@@ -0,0 +1,21 @@
+-- Pending and recently flushed notification groups for coalescing
+-- likes/reposts/follows into one summary per (recipient, type, subject).
+-- `flush_at` is non-NULL while events are waiting for the group's window to
+-- close; `total_count` keeps running across flushes until the group idles
+-- out, so later bursts update the same on-device notification.
+CREATE TABLE IF NOT EXISTS push_coalesce_groups (
+    recipient_did TEXT NOT NULL,
+    group_key TEXT NOT NULL,
+    notification_type TEXT NOT NULL,
+    subject_uri TEXT,
+    latest_actor_did TEXT NOT NULL,
+    latest_actor_label TEXT NOT NULL,
+    total_count INTEGER NOT NULL DEFAULT 1,
+    flush_at TIMESTAMPTZ,
+    last_event_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (recipient_did, group_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_coalesce_groups_flush_at
+    ON push_coalesce_groups(flush_at)
+    WHERE flush_at IS NOT NULL;
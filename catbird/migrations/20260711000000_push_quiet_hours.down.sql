@@ -0,0 +1 @@
+ALTER TABLE push_event_queue DROP COLUMN IF EXISTS quiet_deferred;
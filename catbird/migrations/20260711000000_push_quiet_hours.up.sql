@@ -0,0 +1,3 @@
+-- Set when quiet hours pushed an event's available_at out; such events are
+-- folded into the recipient's digest once released.
+ALTER TABLE push_event_queue ADD COLUMN IF NOT EXISTS quiet_deferred BOOLEAN NOT NULL DEFAULT FALSE;
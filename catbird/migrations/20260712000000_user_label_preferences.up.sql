@@ -0,0 +1,7 @@
+-- Label settings from each push recipient's `adultContentPref`,
+-- `contentLabelPref` and `labelersPref`, used to suppress labeled content.
+CREATE TABLE IF NOT EXISTS user_label_preferences (
+    user_did TEXT PRIMARY KEY,
+    label_prefs_json JSONB NOT NULL DEFAULT '{}'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,13 @@
+-- Cached actor profiles for push notification text. Rows with found = FALSE
+-- record actors the AppView had no profile for, so they aren't re-fetched
+-- on every event.
+CREATE TABLE IF NOT EXISTS push_actor_profiles (
+    actor_did TEXT PRIMARY KEY,
+    label TEXT,
+    avatar_url TEXT,
+    found BOOLEAN NOT NULL,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_actor_profiles_fetched_at
+    ON push_actor_profiles (fetched_at);
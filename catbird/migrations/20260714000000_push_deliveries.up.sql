@@ -0,0 +1,25 @@
+-- One row per push decision (dropped, deferred, coalesced, failed to
+-- evaluate) and per transport attempt, so a missing notification can be
+-- explained after the queue row is gone. Pruned by
+-- `push.delivery_log_retention_days`.
+CREATE TABLE IF NOT EXISTS push_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    recipient_did TEXT NOT NULL,
+    queue_event_id BIGINT,
+    notification_type TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    reason TEXT,
+    device_id UUID,
+    platform TEXT,
+    apns_environment TEXT,
+    provider_id TEXT,
+    status_code INTEGER,
+    latency_ms INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_deliveries_recipient
+    ON push_deliveries (recipient_did, id DESC);
+
+CREATE INDEX IF NOT EXISTS idx_push_deliveries_created_at
+    ON push_deliveries (created_at);
@@ -0,0 +1,27 @@
+-- Queue rows that exhausted `push.max_attempts`, kept with their final error
+-- for inspection and replay from the admin port.
+CREATE TABLE IF NOT EXISTS push_event_dead_letter (
+    id BIGSERIAL PRIMARY KEY,
+    queue_event_id BIGINT NOT NULL,
+    recipient_did TEXT NOT NULL,
+    actor_did TEXT NOT NULL,
+    notification_type TEXT NOT NULL,
+    event_cid TEXT NOT NULL,
+    event_path TEXT NOT NULL,
+    subject_uri TEXT,
+    thread_root_uri TEXT,
+    event_record_json JSONB NOT NULL,
+    event_timestamp BIGINT NOT NULL,
+    dedupe_key TEXT NOT NULL,
+    quiet_deferred BOOLEAN NOT NULL DEFAULT FALSE,
+    attempts INTEGER NOT NULL,
+    final_error TEXT,
+    queued_at TIMESTAMPTZ NOT NULL,
+    dead_lettered_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_event_dead_letter_recipient
+    ON push_event_dead_letter (recipient_did, id DESC);
+
+CREATE INDEX IF NOT EXISTS idx_push_event_dead_letter_type
+    ON push_event_dead_letter (notification_type, id DESC);
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS push_event_queue_notify ON push_event_queue;
+DROP FUNCTION IF EXISTS notify_push_event_queue();
@@ -0,0 +1,15 @@
+-- Wake push workers as soon as events are queued, whoever inserts them
+-- (catbird-firehose, the chat poller, dead-letter replay). One notification
+-- per statement; Postgres folds duplicates within a transaction anyway.
+CREATE OR REPLACE FUNCTION notify_push_event_queue() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('push_event_queue', '');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS push_event_queue_notify ON push_event_queue;
+CREATE TRIGGER push_event_queue_notify
+    AFTER INSERT ON push_event_queue
+    FOR EACH STATEMENT
+    EXECUTE FUNCTION notify_push_event_queue();
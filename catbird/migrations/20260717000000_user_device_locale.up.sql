@@ -0,0 +1,3 @@
+-- Device locale sent with registerPush, used to render notification text
+-- for transports without client-side localization (Web Push, FCM).
+ALTER TABLE user_devices ADD COLUMN IF NOT EXISTS locale TEXT;
@@ -0,0 +1,4 @@
+-- Partial push preferences (the putPreferencesV2 shape) merged over the
+-- account's push_preferences document for this device only. NULL means the
+-- device follows the account preferences.
+ALTER TABLE user_devices ADD COLUMN IF NOT EXISTS preference_overrides JSONB;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS push_chat_unread;
+DROP TABLE IF EXISTS push_badge_counts;
@@ -0,0 +1,19 @@
+-- Unread counts behind the app icon badge. Notifications are seeded from
+-- app.bsky.notification.getUnreadCount (re-seeded every sync interval to
+-- correct drift) and incremented per delivered event; chats are counted per
+-- conversation by the chat poller. Both are reset when the proxy sees the
+-- account mark them read.
+CREATE TABLE IF NOT EXISTS push_badge_counts (
+    account_did TEXT PRIMARY KEY,
+    notifications_unread INTEGER NOT NULL DEFAULT 0,
+    notifications_seeded_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS push_chat_unread (
+    account_did TEXT NOT NULL,
+    convo_id TEXT NOT NULL,
+    unread INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (account_did, convo_id)
+);
@@ -0,0 +1,3 @@
+-- P-256 public key sent with registerPush. The chat poller encrypts message
+-- previews to it so queued chat events never hold plaintext.
+ALTER TABLE user_devices ADD COLUMN IF NOT EXISTS preview_key TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE push_event_dead_letter DROP COLUMN IF EXISTS badge_counted;
+ALTER TABLE push_event_queue DROP COLUMN IF EXISTS badge_counted;
@@ -0,0 +1,9 @@
+-- Set once a queued event has been counted toward the recipient's badge, so
+-- retries of the same event don't count it again.
+ALTER TABLE push_event_queue
+    ADD COLUMN IF NOT EXISTS badge_counted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Carried through the dead letter table so a replayed event isn't counted
+-- again either.
+ALTER TABLE push_event_dead_letter
+    ADD COLUMN IF NOT EXISTS badge_counted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- What each coalesce group's running total has already counted: actor DIDs
+-- for like/repost/follow groups, queue event ids for quiet-hours digests.
+-- An actor who shows up again (a retried queue row, a like after an unlike)
+-- is counted once however the events interleave. Cleared when the group
+-- idles out and its count starts over.
+CREATE TABLE IF NOT EXISTS push_coalesce_members (
+    recipient_did TEXT NOT NULL,
+    group_key TEXT NOT NULL,
+    member TEXT NOT NULL,
+    PRIMARY KEY (recipient_did, group_key, member),
+    FOREIGN KEY (recipient_did, group_key)
+        REFERENCES push_coalesce_groups(recipient_did, group_key)
+        ON DELETE CASCADE
+);
@@ -25,6 +25,160 @@ pub struct AppConfig {
     /// Clean-chat configuration (blue.catbird.chat.*)
     #[serde(default)]
     pub chat: ChatConfig,
+    /// App-password (legacy createSession) login configuration
+    #[serde(default)]
+    pub app_password: AppPasswordConfig,
+    /// Background proactive token refresher
+    #[serde(default)]
+    pub token_refresher: TokenRefresherConfig,
+    /// Device-code login for secondary screens (/auth/device/*)
+    #[serde(default)]
+    pub device_auth: DeviceAuthConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthConfig {
+    /// Serve the device-code endpoints
+    #[serde(default)]
+    pub enabled: bool,
+    /// Page where the user types the code (shown by the secondary device)
+    #[serde(default = "default_device_auth_verification_uri")]
+    pub verification_uri: String,
+    /// Lifetime of a user code / polling handle pair
+    #[serde(default = "default_device_auth_code_ttl_seconds")]
+    pub code_ttl_seconds: u64,
+    /// Minimum seconds between polls; faster polls get `slow_down`
+    #[serde(default = "default_device_auth_poll_interval_seconds")]
+    pub poll_interval_seconds: u64,
+}
+
+fn default_device_auth_verification_uri() -> String {
+    "https://catbird.blue/device".to_string()
+}
+
+fn default_device_auth_code_ttl_seconds() -> u64 {
+    600
+}
+
+fn default_device_auth_poll_interval_seconds() -> u64 {
+    5
+}
+
+impl Default for DeviceAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            verification_uri: default_device_auth_verification_uri(),
+            code_ttl_seconds: default_device_auth_code_ttl_seconds(),
+            poll_interval_seconds: default_device_auth_poll_interval_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenRefresherConfig {
+    /// Run the background refresher (refreshes then almost never run inline)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Refresh sessions whose access token expires within this many seconds
+    #[serde(default = "default_token_refresher_lead_seconds")]
+    pub lead_seconds: i64,
+    /// How often to scan the refresh schedule
+    #[serde(default = "default_token_refresher_tick_seconds")]
+    pub tick_seconds: u64,
+    /// Max sessions claimed per tick
+    #[serde(default = "default_token_refresher_batch_size")]
+    pub batch_size: usize,
+    /// Max refreshes in flight per replica
+    #[serde(default = "default_token_refresher_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Cross-replica claim lifetime; also the retry delay after a transient failure
+    #[serde(default = "default_token_refresher_claim_ttl_seconds")]
+    pub claim_ttl_seconds: u64,
+    /// Sessions unused for longer than this are left to refresh lazily
+    #[serde(default = "default_token_refresher_idle_cutoff_seconds")]
+    pub idle_cutoff_seconds: i64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_token_refresher_lead_seconds() -> i64 {
+    300
+}
+
+fn default_token_refresher_tick_seconds() -> u64 {
+    15
+}
+
+fn default_token_refresher_batch_size() -> usize {
+    200
+}
+
+fn default_token_refresher_max_concurrency() -> usize {
+    8
+}
+
+fn default_token_refresher_claim_ttl_seconds() -> u64 {
+    60
+}
+
+fn default_token_refresher_idle_cutoff_seconds() -> i64 {
+    86400 * 7
+}
+
+impl Default for TokenRefresherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            lead_seconds: default_token_refresher_lead_seconds(),
+            tick_seconds: default_token_refresher_tick_seconds(),
+            batch_size: default_token_refresher_batch_size(),
+            max_concurrency: default_token_refresher_max_concurrency(),
+            claim_ttl_seconds: default_token_refresher_claim_ttl_seconds(),
+            idle_cutoff_seconds: default_token_refresher_idle_cutoff_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppPasswordConfig {
+    /// Accept `com.atproto.server.createSession` credentials at /auth/app-password
+    #[serde(default)]
+    pub enabled: bool,
+    /// Where handles are resolved when the client doesn't name a PDS
+    #[serde(default = "default_app_password_resolver_url")]
+    pub resolver_url: String,
+    /// PLC directory used to turn a did:plc into its PDS endpoint
+    #[serde(default = "default_plc_directory_url")]
+    pub plc_directory_url: String,
+    /// Refresh the access JWT when it has less than this many seconds left
+    #[serde(default = "default_app_password_refresh_skew_seconds")]
+    pub refresh_skew_seconds: i64,
+}
+
+fn default_app_password_resolver_url() -> String {
+    "https://public.api.bsky.app".to_string()
+}
+
+fn default_plc_directory_url() -> String {
+    "https://plc.directory".to_string()
+}
+
+fn default_app_password_refresh_skew_seconds() -> i64 {
+    60
+}
+
+impl Default for AppPasswordConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolver_url: default_app_password_resolver_url(),
+            plc_directory_url: default_plc_directory_url(),
+            refresh_skew_seconds: default_app_password_refresh_skew_seconds(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -131,18 +285,49 @@ pub struct PushConfig {
     /// How often Nest should opportunistically refresh cached moderation state
     #[serde(default = "default_push_sync_interval_seconds")]
     pub sync_interval_seconds: u64,
-    /// Background queue poll interval in milliseconds
+    /// Fallback queue poll interval in milliseconds; inserts wake the worker
+    /// immediately through LISTEN/NOTIFY
     #[serde(default = "default_push_queue_poll_interval_ms")]
     pub queue_poll_interval_ms: u64,
     /// Max queue rows to lease per poll
     #[serde(default = "default_push_queue_batch_size")]
     pub queue_batch_size: u32,
+    /// Delivery attempts before a queued event is moved to the dead letter table
+    #[serde(default = "default_push_max_attempts")]
+    pub max_attempts: u32,
+    /// Recipients the queue worker processes at once; each recipient's
+    /// events still go out one at a time, in order
+    #[serde(default = "default_push_worker_concurrency")]
+    pub worker_concurrency: u32,
+    /// How long likes/reposts/follows are held per (recipient, subject, type)
+    /// before one summary notification is sent for the group
+    #[serde(default = "default_push_coalesce_window_seconds")]
+    pub coalesce_window_seconds: u64,
+    /// Days to keep `push_deliveries` (decision and delivery outcomes);
+    /// 0 disables the log
+    #[serde(default = "default_push_delivery_log_retention_days")]
+    pub delivery_log_retention_days: u32,
+    /// AppView used to hydrate actor names, avatars, and reply context for
+    /// notification text
+    #[serde(default = "default_push_appview_url")]
+    pub appview_url: String,
     /// APNs delivery configuration
     #[serde(default)]
     pub apns: ApnsConfig,
+    /// Web Push delivery configuration (browser clients, `platform: "web"`)
+    #[serde(default)]
+    pub web_push: WebPushConfig,
+    /// FCM delivery configuration (Android clients, `platform: "android"`)
+    #[serde(default)]
+    pub fcm: FcmConfig,
     /// Enable the chat poll background service
     #[serde(default)]
     pub chat_poll_enabled: bool,
+    /// Send chat message text in the clear (`messageText`) to devices that
+    /// registered without a `previewKey`, as app builds before encrypted
+    /// previews expect. Turn off once clients register preview keys
+    #[serde(default = "default_true")]
+    pub legacy_chat_previews: bool,
 }
 
 impl PushConfig {
@@ -171,18 +356,94 @@ pub struct ApnsConfig {
     pub production: bool,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebPushConfig {
+    /// VAPID signing key: the raw 32-byte P-256 private scalar, base64url
+    /// (the `privateKey` printed by `web-push generate-vapid-keys`).
+    #[serde(default)]
+    pub vapid_private_key: Option<String>,
+    /// VAPID contact (`mailto:` or `https:` URI) sent to push services.
+    #[serde(default)]
+    pub vapid_subject: Option<String>,
+    /// How long a push service may hold an undelivered message
+    #[serde(default = "default_web_push_ttl_seconds")]
+    pub ttl_seconds: u32,
+}
+
+impl Default for WebPushConfig {
+    fn default() -> Self {
+        Self {
+            vapid_private_key: None,
+            vapid_subject: None,
+            ttl_seconds: default_web_push_ttl_seconds(),
+        }
+    }
+}
+
+fn default_web_push_ttl_seconds() -> u32 {
+    86400
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FcmConfig {
+    /// Path to the Firebase service-account JSON key
+    #[serde(default)]
+    pub service_account_path: Option<String>,
+    /// Firebase project to send through; defaults to the service account's
+    /// `project_id`
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// FCM HTTP v1 API base URL
+    #[serde(default = "default_fcm_api_base_url")]
+    pub api_base_url: String,
+}
+
+impl Default for FcmConfig {
+    fn default() -> Self {
+        Self {
+            service_account_path: None,
+            project_id: None,
+            api_base_url: default_fcm_api_base_url(),
+        }
+    }
+}
+
+fn default_fcm_api_base_url() -> String {
+    "https://fcm.googleapis.com".to_string()
+}
+
 fn default_push_sync_interval_seconds() -> u64 {
     300
 }
 
 fn default_push_queue_poll_interval_ms() -> u64 {
-    500
+    5000
 }
 
 fn default_push_queue_batch_size() -> u32 {
     32
 }
 
+fn default_push_max_attempts() -> u32 {
+    8
+}
+
+fn default_push_worker_concurrency() -> u32 {
+    8
+}
+
+fn default_push_coalesce_window_seconds() -> u64 {
+    30
+}
+
+fn default_push_delivery_log_retention_days() -> u32 {
+    7
+}
+
+fn default_push_appview_url() -> String {
+    "https://public.api.bsky.app".to_string()
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     /// Host to bind to
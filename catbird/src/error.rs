@@ -7,6 +7,9 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use jacquard_oauth::error::OAuthError;
+use jacquard_oauth::request::{RequestError, RequestErrorKind};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 
@@ -29,7 +32,7 @@ pub enum AppError {
     InvalidSession,
 
     #[error("OAuth error: {0}")]
-    OAuth(String),
+    OAuth(OAuthFailure),
 
     #[error("Upstream error: {status} - {message}")]
     Upstream { status: u16, message: String },
@@ -91,7 +94,11 @@ impl IntoResponse for AppError {
                 "invalid_session",
                 "Invalid session. Please log in again.".to_string(),
             ),
-            AppError::OAuth(msg) => (StatusCode::BAD_REQUEST, "oauth_error", msg.clone()),
+            AppError::OAuth(failure) => (
+                StatusCode::BAD_REQUEST,
+                "oauth_error",
+                failure.message.clone(),
+            ),
             AppError::Upstream { status, message } => {
                 let status_code = StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY);
                 (status_code, "upstream_error", message.clone())
@@ -192,5 +199,211 @@ impl IntoResponse for AppError {
     }
 }
 
+/// Coarse category of an OAuth or token-refresh failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuthFailureKind {
+    /// The session record no longer exists in the store.
+    SessionNotFound,
+    /// The authorization server refused the grant (`invalid_grant`,
+    /// `access_denied`, a dead app-password refresh JWT, ...).
+    GrantRejected,
+    /// The session holds no refresh token to rotate.
+    NoRefreshToken,
+    /// The server answered with some other HTTP error.
+    ServerError,
+    /// Identity, metadata or endpoint resolution failed.
+    Resolution,
+    /// Reading or writing session state failed.
+    Storage,
+    /// DPoP, keyset, serialization and other local failures.
+    Other,
+}
+
+impl OAuthFailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OAuthFailureKind::SessionNotFound => "session_not_found",
+            OAuthFailureKind::GrantRejected => "grant_rejected",
+            OAuthFailureKind::NoRefreshToken => "no_refresh_token",
+            OAuthFailureKind::ServerError => "server_error",
+            OAuthFailureKind::Resolution => "resolution",
+            OAuthFailureKind::Storage => "storage",
+            OAuthFailureKind::Other => "other",
+        }
+    }
+}
+
+/// Structured OAuth / token-refresh failure.
+///
+/// Built from jacquard-oauth's `RequestErrorKind` so callers can branch on
+/// `permanent` (the session is dead; log in again) instead of matching on
+/// error strings. Also the shape stored as a session's last auth failure.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthFailure {
+    pub kind: OAuthFailureKind,
+    /// True when retrying cannot succeed and the session must be discarded.
+    pub permanent: bool,
+    /// HTTP status returned by the authorization server or PDS, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// OAuth / XRPC `error` code from the response body, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for OAuthFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl OAuthFailure {
+    pub fn from_request_error(context: &str, err: &RequestError) -> Self {
+        Self::from_request_parts(context, err, err.is_permanent())
+    }
+
+    pub fn from_session_error(context: &str, err: &jacquard_oauth::session::Error) -> Self {
+        use jacquard_oauth::session::Error;
+
+        match err {
+            Error::ServerAgent(request) | Error::RefreshFailed(request) => {
+                Self::from_request_parts(context, request, err.is_permanent())
+            }
+            Error::SessionNotFound => Self {
+                kind: OAuthFailureKind::SessionNotFound,
+                permanent: true,
+                http_status: None,
+                error_code: None,
+                message: format!("{context}: {err}"),
+            },
+            Error::Store(_) => Self {
+                kind: OAuthFailureKind::Storage,
+                permanent: false,
+                http_status: None,
+                error_code: None,
+                message: format!("{context}: {err}"),
+            },
+        }
+    }
+
+    pub fn from_oauth_error(context: &str, err: &OAuthError) -> Self {
+        let kind = match err {
+            OAuthError::Request(request) => return Self::from_request_error(context, request),
+            OAuthError::Session(session) => return Self::from_session_error(context, session),
+            OAuthError::Resolver(_) | OAuthError::Atproto(_) => OAuthFailureKind::Resolution,
+            OAuthError::Storage(_) => OAuthFailureKind::Storage,
+            _ => OAuthFailureKind::Other,
+        };
+        Self {
+            kind,
+            permanent: false,
+            http_status: None,
+            error_code: None,
+            message: format!("{context}: {err}"),
+        }
+    }
+
+    /// A non-2xx `com.atproto.server.refreshSession` reply for an
+    /// app-password session.
+    pub fn refresh_rejected(status: u16, error_code: &str, message: &str, permanent: bool) -> Self {
+        Self {
+            kind: if permanent {
+                OAuthFailureKind::GrantRejected
+            } else {
+                OAuthFailureKind::ServerError
+            },
+            permanent,
+            http_status: Some(status),
+            error_code: (!error_code.is_empty()).then(|| error_code.to_string()),
+            message: format!("refreshSession failed: {status} {error_code}: {message}"),
+        }
+    }
+
+    fn from_request_parts(context: &str, err: &RequestError, permanent: bool) -> Self {
+        let (http_status, error_code) = match err.kind() {
+            RequestErrorKind::HttpStatus(status) => (Some(status.as_u16()), None),
+            RequestErrorKind::HttpStatusWithBody { status, body } => (
+                Some(status.as_u16()),
+                body.get("error")
+                    .and_then(|code| code.as_str())
+                    .map(str::to_string),
+            ),
+            _ => (None, None),
+        };
+        // jacquard only treats invalid_grant / access_denied as permanent; a
+        // token endpoint rejecting the refresh token itself is just as final.
+        let permanent = permanent || error_code.as_deref() == Some("invalid_token");
+
+        let kind = match err.kind() {
+            RequestErrorKind::NoRefreshToken => OAuthFailureKind::NoRefreshToken,
+            RequestErrorKind::HttpStatus(_) | RequestErrorKind::HttpStatusWithBody { .. }
+                if permanent =>
+            {
+                OAuthFailureKind::GrantRejected
+            }
+            RequestErrorKind::HttpStatus(_) | RequestErrorKind::HttpStatusWithBody { .. } => {
+                OAuthFailureKind::ServerError
+            }
+            RequestErrorKind::NoEndpoint(_)
+            | RequestErrorKind::Resolver
+            | RequestErrorKind::Identity
+            | RequestErrorKind::Atproto => OAuthFailureKind::Resolution,
+            RequestErrorKind::Storage => OAuthFailureKind::Storage,
+            _ => OAuthFailureKind::Other,
+        };
+
+        Self {
+            kind,
+            permanent,
+            http_status,
+            error_code,
+            message: format!("{context}: {err}"),
+        }
+    }
+}
+
 /// Result type alias for handlers
 pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::StatusCode as HttpStatus;
+    use jacquard_oauth::session::Error as SessionError;
+
+    #[test]
+    fn invalid_grant_is_a_permanent_rejection() {
+        let err = RequestError::http_status_with_body(
+            HttpStatus::BAD_REQUEST,
+            json!({ "error": "invalid_grant", "error_description": "refresh token revoked" }),
+        );
+        let failure =
+            OAuthFailure::from_session_error("refresh", &SessionError::RefreshFailed(err));
+        assert_eq!(failure.kind, OAuthFailureKind::GrantRejected);
+        assert!(failure.permanent);
+        assert_eq!(failure.http_status, Some(400));
+        assert_eq!(failure.error_code.as_deref(), Some("invalid_grant"));
+    }
+
+    #[test]
+    fn server_errors_stay_transient() {
+        let err = RequestError::http_status(HttpStatus::BAD_GATEWAY);
+        let failure = OAuthFailure::from_session_error("refresh", &SessionError::ServerAgent(err));
+        assert_eq!(failure.kind, OAuthFailureKind::ServerError);
+        assert!(!failure.permanent);
+        assert_eq!(failure.http_status, Some(502));
+    }
+
+    #[test]
+    fn missing_session_and_refresh_token_are_permanent() {
+        let missing = OAuthFailure::from_session_error("get", &SessionError::SessionNotFound);
+        assert_eq!(missing.kind, OAuthFailureKind::SessionNotFound);
+        assert!(missing.permanent);
+
+        let no_refresh = OAuthFailure::from_request_error("get", &RequestError::no_refresh_token());
+        assert_eq!(no_refresh.kind, OAuthFailureKind::NoRefreshToken);
+        assert!(no_refresh.permanent);
+    }
+}
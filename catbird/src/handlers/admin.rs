@@ -0,0 +1,239 @@
+//! Admin handlers
+//!
+//! Served only on the internal admin port (`server.admin_port`, bound to
+//! 127.0.0.1) alongside `/metrics`, so they carry no session auth of their own.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::AppState,
+    error::{AppError, AppResult},
+    models::AuthFailureRecord,
+    services::{
+        login_attempts::{LoginAttempt, LoginAttemptStore},
+        push::{dead_letter::DeadLetterFilter, PushServices},
+    },
+};
+
+#[derive(Debug, Serialize)]
+pub struct SessionAuthStatus {
+    pub session_id: String,
+    /// DID from the session index; `None` once the session is gone.
+    pub did: Option<String>,
+    pub last_auth_failure: Option<AuthFailureRecord>,
+}
+
+/// Explain a session's auth state, including why it was last refused.
+///
+/// GET /admin/sessions/{session_id}
+pub async fn get_session_auth_status(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<SessionAuthStatus>> {
+    let auth_store = state
+        .auth_store
+        .as_ref()
+        .ok_or_else(|| AppError::Config("Auth store not configured".into()))?;
+
+    let did = auth_store.peek_did_for_session(&session_id).await?;
+    let last_auth_failure = auth_store
+        .last_auth_failure(&session_id)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read last auth failure: {e}")))?;
+
+    Ok(Json(SessionAuthStatus {
+        session_id,
+        did,
+        last_auth_failure,
+    }))
+}
+
+/// Look up one login attempt by its OAuth `state`.
+///
+/// GET /admin/login-attempts/{state}
+pub async fn get_login_attempt(
+    State(state): State<Arc<AppState>>,
+    Path(oauth_state): Path<String>,
+) -> AppResult<Json<LoginAttempt>> {
+    let attempt = LoginAttemptStore::new(&state)
+        .get(&oauth_state)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Login attempt not found".into()))?;
+    Ok(Json(attempt.redacted()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginAttemptsQuery {
+    /// DID or the identifier typed at login (usually a handle).
+    pub subject: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginAttemptList {
+    pub attempts: Vec<LoginAttempt>,
+}
+
+/// Recent login attempts for a DID or handle, newest first.
+///
+/// GET /admin/login-attempts?subject={did or handle}
+pub async fn list_login_attempts(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoginAttemptsQuery>,
+) -> AppResult<Json<LoginAttemptList>> {
+    let attempts = LoginAttemptStore::new(&state)
+        .find_by_subject(&query.subject)
+        .await?
+        .into_iter()
+        .map(LoginAttempt::redacted)
+        .collect();
+    Ok(Json(LoginAttemptList { attempts }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushDeliveriesQuery {
+    pub did: String,
+    pub limit: Option<i64>,
+    /// Only entries older than this id (the previous page's last `id`).
+    pub before: Option<i64>,
+}
+
+/// Recent push decisions and delivery attempts for an account, newest first.
+///
+/// GET /admin/push/deliveries?did={did}
+pub async fn list_push_deliveries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PushDeliveriesQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = push_services(&state)?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+    let entries = push
+        .deliveries
+        .recent(&query.did, limit, query.before)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read push delivery log: {e}")))?;
+
+    Ok(Json(serde_json::json!({
+        "did": query.did,
+        "deliveries": entries.iter().map(|entry| entry.to_json()).collect::<Vec<_>>(),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLettersQuery {
+    pub did: Option<String>,
+    #[serde(rename = "type")]
+    pub notification_type: Option<String>,
+    /// Substring of the final error.
+    pub error: Option<String>,
+    pub limit: Option<i64>,
+    /// Only entries older than this id (the previous page's last `id`).
+    pub before: Option<i64>,
+}
+
+/// Dead-lettered push events, newest first.
+///
+/// GET /admin/push/dead-letters?did={did}&type={type}&error={substring}
+pub async fn list_push_dead_letters(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DeadLettersQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = push_services(&state)?;
+    let filter = DeadLetterFilter {
+        ids: None,
+        did: query.did,
+        notification_type: query.notification_type,
+        error: query.error,
+    };
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+    let rows = push
+        .dead_letters
+        .list(&filter, query.before, limit)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read push dead letters: {e}")))?;
+    let total = push
+        .dead_letters
+        .count()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to count push dead letters: {e}")))?;
+
+    Ok(Json(serde_json::json!({
+        "total": total,
+        "deadLetters": rows.iter().map(|row| row.to_json()).collect::<Vec<_>>(),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterAction {
+    #[serde(flatten)]
+    pub filter: DeadLetterFilter,
+    /// Required to act on every dead letter when no filter is given.
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Put matching dead letters back on the push queue.
+///
+/// POST /admin/push/dead-letters/replay
+pub async fn replay_push_dead_letters(
+    State(state): State<Arc<AppState>>,
+    Json(action): Json<DeadLetterAction>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = push_services(&state)?;
+    let filter = checked_dead_letter_filter(action)?;
+    let outcome = push
+        .dead_letters
+        .replay(&filter)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to replay push dead letters: {e}")))?;
+    tracing::info!(
+        replayed = outcome.replayed,
+        skipped_stale = outcome.skipped_stale,
+        ?filter,
+        "Replayed push dead letters"
+    );
+    Ok(Json(serde_json::json!({
+        "replayed": outcome.replayed,
+        "skippedStale": outcome.skipped_stale,
+    })))
+}
+
+/// Delete matching dead letters.
+///
+/// POST /admin/push/dead-letters/purge
+pub async fn purge_push_dead_letters(
+    State(state): State<Arc<AppState>>,
+    Json(action): Json<DeadLetterAction>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = push_services(&state)?;
+    let filter = checked_dead_letter_filter(action)?;
+    let purged = push
+        .dead_letters
+        .purge(&filter)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to purge push dead letters: {e}")))?;
+    tracing::info!(purged, ?filter, "Purged push dead letters");
+    Ok(Json(serde_json::json!({ "purged": purged })))
+}
+
+/// An empty filter matches everything, so it has to be asked for explicitly.
+fn checked_dead_letter_filter(action: DeadLetterAction) -> AppResult<DeadLetterFilter> {
+    if action.filter.is_empty() && !action.all {
+        return Err(AppError::BadRequest(
+            "Give ids, did, type or error, or set all: true".into(),
+        ));
+    }
+    Ok(action.filter)
+}
+
+fn push_services(state: &AppState) -> AppResult<&Arc<PushServices>> {
+    state
+        .push
+        .as_ref()
+        .ok_or_else(|| AppError::Config("Push is not configured".into()))
+}
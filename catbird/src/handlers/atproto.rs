@@ -2,6 +2,7 @@
 //!
 //! Request handlers for ATProto proxy endpoints including:
 //! - OAuth flow (login, callback, logout)
+//! - App-password (createSession) login for PDSes without OAuth
 //! - XRPC proxy
 
 use axum::{
@@ -18,14 +19,15 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::config::AppState;
-use crate::error::{AppError, AppResult};
+use crate::error::{AppError, AppResult, OAuthFailure};
 use crate::metrics;
 use crate::middleware::JacquardDpopData;
 use crate::middleware::SESSION_COOKIE_NAME;
 use crate::models::{
-    CatbirdSession, ExchangeRequest, ExchangeResponse, LogoutResponse, OAuthCallback,
-    SessionInfo,
+    AppPasswordLoginRequest, AppPasswordLoginResponse, CatbirdSession, ExchangeRequest,
+    ExchangeResponse, LogoutResponse, OAuthCallback, SessionAuthKind, SessionInfo,
 };
+use crate::services::login_attempts::{LoginAttempt, LoginAttemptStore, LoginMode, LoginStage};
 use crate::services::{AtProtoClient, MlsAuthService, ProxyResponse};
 
 /// Handle login initiation (Redirect flow)
@@ -43,6 +45,7 @@ pub async fn login(
     let client = params.get("client").cloned();
     let redirect_to = params.get("redirect_to").cloned();
     let browser_nonce = params.get("browser_nonce").cloned();
+    let user_code = params.get("user_code").cloned();
 
     // Select the appropriate OAuth client based on the client parameter.
     // The chosen selector is persisted to Redis so the callback handler can
@@ -87,6 +90,27 @@ pub async fn login(
             return Err(AppError::BadRequest("Disallowed redirect_to URL".into()));
         }
     }
+    // Device-code approval by OAuth (handlers::device_auth): the session
+    // minted here goes to the waiting device, never to this browser.
+    let user_code = match user_code {
+        Some(raw) => {
+            if !state.config.device_auth.enabled {
+                return Err(AppError::NotFound("Device login is disabled".into()));
+            }
+            if browser_nonce.is_some() || redirect_to.is_some() {
+                return Err(AppError::BadRequest(
+                    "user_code cannot be combined with redirect_to or browser_nonce".into(),
+                ));
+            }
+            let code = crate::handlers::device_auth::normalize_user_code(&raw)
+                .ok_or_else(|| AppError::BadRequest("Invalid user_code".into()))?;
+            if !crate::handlers::device_auth::is_pending_user_code(&state, &code).await? {
+                return Err(AppError::NotFound("Unknown or expired device code".into()));
+            }
+            Some(code)
+        }
+        None => None,
+    };
     tracing::info!(
         "Login request for identifier: {}, client: {:?}, redirect_to: {:?}, selector: {}",
         identifier,
@@ -113,101 +137,62 @@ pub async fn login(
     // Generate a clean UUID for the OAuth state (= Jacquard session_id).
     let session_nonce = uuid::Uuid::new_v4().to_string();
 
-    // Persist session state to Redis.
+    // Persist the login attempt: flow state for the callback plus funnel
+    // progress (services::login_attempts).
     // Contract Rule 2: Fail closed in exchange mode on ANY persistence error.
-    // FIX 4: Persist explicit oauth_mode:"exchange" marker for admitted exchange flows.
-    // Absent browser_nonce: maintain legacy tolerant error handling.
-    if let Some(ref nonce) = browser_nonce {
-        let mut conn = state.redis.clone();
-
-        // 1. oauth_mode marker
-        let mode_key = format!("oauth_mode:{}", session_nonce);
-        redis::cmd("SET")
-            .arg(&mode_key)
-            .arg("exchange")
-            .arg("EX")
-            .arg(600)
-            .query_async::<_, ()>(&mut conn)
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to persist oauth_mode: {}", e)))?;
-
-        // 2. oauth_client
-        let client_key = format!("oauth_client:{}", session_nonce);
-        redis::cmd("SET")
-            .arg(&client_key)
-            .arg(client_selector)
-            .arg("EX")
-            .arg(600)
-            .query_async::<_, ()>(&mut conn)
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to persist oauth_client: {}", e)))?;
-
-        // 3. oauth_redirect
-        if let Some(ref r) = redirect_to {
-            let redirect_key = format!("oauth_redirect:{}", session_nonce);
-            redis::cmd("SET")
-                .arg(&redirect_key)
-                .arg(r.as_str())
-                .arg("EX")
-                .arg(600)
-                .query_async::<_, ()>(&mut conn)
-                .await
-                .map_err(|e| AppError::Internal(format!("Failed to persist oauth_redirect: {}", e)))?;
-        }
-
-        // 4. oauth_nonce
-        let nonce_key = format!("oauth_nonce:{}", session_nonce);
-        redis::cmd("SET")
-            .arg(&nonce_key)
-            .arg(nonce.as_str())
-            .arg("EX")
-            .arg(600)
-            .query_async::<_, ()>(&mut conn)
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to persist oauth_nonce: {}", e)))?;
+    // Device mode too: losing the user code would leave the device polling
+    // while the session goes nowhere.
+    // Redirect mode stays tolerant; the callback falls back to legacy inference.
+    let mode = if browser_nonce.is_some() {
+        LoginMode::Exchange
+    } else if user_code.is_some() {
+        LoginMode::Device
     } else {
-        {
-            let mut conn = state.redis.clone();
-            let key = format!("oauth_client:{}", session_nonce);
-            if let Err(e) = redis::cmd("SET")
-                .arg(&key)
-                .arg(client_selector)
-                .arg("EX")
-                .arg(600)
-                .query_async::<_, ()>(&mut conn)
-                .await
-            {
-                // Don't fail login — callback will fall back to legacy inference.
-                tracing::warn!(
-                    "Failed to persist oauth_client selector to Redis: {}",
-                    e
-                );
-            }
-        }
-
-        // Store redirect_to in Redis so the callback can look it up.
-        if let Some(ref r) = redirect_to {
-            let mut conn = state.redis.clone();
-            let key = format!("oauth_redirect:{}", session_nonce);
-            let _: Result<(), _> = redis::cmd("SET")
-                .arg(&key)
-                .arg(r.as_str())
-                .arg("EX")
-                .arg(600) // 10 minute TTL
-                .query_async(&mut conn)
-                .await;
+        LoginMode::Redirect
+    };
+    let mut attempt = LoginAttempt::start(
+        &session_nonce,
+        client_selector,
+        mode,
+        identifier,
+        chrono::Utc::now(),
+    );
+    attempt.redirect_to = redirect_to.clone();
+    attempt.browser_nonce = browser_nonce.clone();
+    attempt.user_code = user_code.clone();
+
+    let attempts = LoginAttemptStore::new(&state);
+    if let Err(e) = attempts.create(&attempt).await {
+        if mode != LoginMode::Redirect {
+            return Err(AppError::Internal(format!(
+                "Failed to persist login attempt: {}",
+                e
+            )));
         }
+        tracing::warn!("Failed to persist login attempt: {}", e);
     }
 
     let options = AuthorizeOptions {
-        state: Some(session_nonce.into()),
+        state: Some(session_nonce.clone().into()),
         ..Default::default()
     };
 
-    let auth_url = jacquard_client
-        .start_auth(identifier, options)
-        .await
-        .map_err(|e| AppError::OAuth(format!("Authorization failed: {}", e)))?;
+    let auth_url = match jacquard_client.start_auth(identifier, options).await {
+        Ok(url) => url,
+        Err(e) => {
+            let failure = OAuthFailure::from_oauth_error("Authorization failed", &e);
+            note_login_failure(
+                &attempts,
+                &session_nonce,
+                &failure.message,
+                failure.error_code.as_deref(),
+            )
+            .await;
+            return Err(AppError::OAuth(failure));
+        }
+    };
+    note_login_stage(&attempts, &session_nonce, LoginStage::ParOk).await;
+    note_login_stage(&attempts, &session_nonce, LoginStage::Redirected).await;
 
     // Redirect to the PDS authorization URL
     Ok(Response::builder()
@@ -227,55 +212,46 @@ pub async fn oauth_callback(
 ) -> AppResult<(CookieJar, Response)> {
     tracing::info!("OAuth callback received");
 
-    // FIX 4: Check if this session was admitted as an exchange flow
-    let stored_mode: Option<String> = {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_mode:{}", &callback.state);
-        redis::cmd("GET")
-            .arg(&key)
-            .query_async(&mut conn)
-            .await
-            .ok()
-    };
-    if stored_mode.is_some() {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_mode:{}", &callback.state);
-        let _: Result<(), _> = redis::cmd("DEL").arg(&key).query_async(&mut conn).await;
-    }
-
-    // Check if this session has a stored redirect_to
-    let redirect_to: Option<String> = {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_redirect:{}", &callback.state);
-        redis::cmd("GET")
-            .arg(&key)
-            .query_async(&mut conn)
-            .await
-            .ok()
+    // Flow state persisted by `login`. Logins started before login attempts
+    // were introduced still carry the old per-field keys.
+    let attempts = LoginAttemptStore::new(&state);
+    // Fail closed: an unreadable exchange-mode attempt must not be mistaken
+    // for a legacy redirect flow (that would downgrade to a session-bearing
+    // redirect).
+    let attempt = attempts.get(&callback.state).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to load login attempt");
+        e
+    })?;
+    let flow = match attempt {
+        Some(attempt) => {
+            // One-time use, like the keys it replaces. The stage check turns
+            // away late replays; the claim settles callbacks racing on the
+            // same state, and fails closed if it can't be taken.
+            if attempt.stage >= LoginStage::CallbackReceived
+                || !attempts.claim_callback(&callback.state).await?
+            {
+                tracing::warn!(stage = attempt.stage.as_str(), "Replayed OAuth callback");
+                return Err(AppError::BadRequest("Login already completed".into()));
+            }
+            note_login_stage(&attempts, &callback.state, LoginStage::CallbackReceived).await;
+            LoginFlow {
+                exchange_mode: attempt.mode == LoginMode::Exchange,
+                redirect_to: attempt.redirect_to,
+                browser_nonce: attempt.browser_nonce,
+                client_selector: Some(attempt.client),
+                user_code: attempt.user_code,
+            }
+        }
+        None => legacy_login_flow(&state, &callback.state).await,
     };
-    // Clean up the redirect key (one-time use)
-    if redirect_to.is_some() {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_redirect:{}", &callback.state);
-        let _: Result<(), _> = redis::cmd("DEL").arg(&key).query_async(&mut conn).await;
-    }
+    let LoginFlow {
+        exchange_mode,
+        redirect_to,
+        browser_nonce: stored_nonce,
+        client_selector: stored_selector,
+        user_code: device_user_code,
+    } = flow;
 
-    // Check if this session has a stored browser_nonce (ADR-014 exchange flow)
-    let stored_nonce: Option<String> = {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_nonce:{}", &callback.state);
-        redis::cmd("GET")
-            .arg(&key)
-            .query_async(&mut conn)
-            .await
-            .ok()
-    };
-    // Clean up the nonce key (one-time use)
-    if stored_nonce.is_some() {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_nonce:{}", &callback.state);
-        let _: Result<(), _> = redis::cmd("DEL").arg(&key).query_async(&mut conn).await;
-    }
     // Deny/cancel path: the provider redirects back without a code
     // (RFC 6749 §4.1.2.1 — `error` + optional `error_description` instead).
     let Some(code) = callback.code else {
@@ -286,12 +262,7 @@ pub async fn oauth_callback(
             "OAuth callback without authorization code; aborting login"
         );
         metrics::record_oauth_login(false);
-        // The selector key is one-time; the flow is dead, so drop it.
-        let mut conn = state.redis.clone();
-        let _: Result<(), _> = redis::cmd("DEL")
-            .arg(format!("oauth_client:{}", &callback.state))
-            .query_async(&mut conn)
-            .await;
+        note_login_failure(&attempts, &callback.state, err, Some(err)).await;
         let target = match redirect_to.as_deref() {
             Some(r) if is_allowed_redirect(r) => format!("{}?error={}", r, err),
             _ => format!("https://catbird.blue/oauth/callback#error={}", err),
@@ -306,28 +277,11 @@ pub async fn oauth_callback(
         ));
     };
 
-    // Read back the client selector persisted by the login handler. This is
-    // the authoritative source for which jacquard_client to use — the PDS
-    // binds the authorization code to the client_id that issued the PAR
-    // request, so login and callback MUST use the same client.
-    let stored_selector: Option<String> = {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_client:{}", &callback.state);
-        redis::cmd("GET")
-            .arg(&key)
-            .query_async(&mut conn)
-            .await
-            .ok()
-    };
-    // Clean up the selector key (one-time use)
-    if stored_selector.is_some() {
-        let mut conn = state.redis.clone();
-        let key = format!("oauth_client:{}", &callback.state);
-        let _: Result<(), _> = redis::cmd("DEL").arg(&key).query_async(&mut conn).await;
-    }
-
     // Determine which Jacquard client to use.
-    // Preferred: the selector persisted at login time.
+    // Preferred: the selector persisted at login time. This is the
+    // authoritative source — the PDS binds the authorization code to the
+    // client_id that issued the PAR request, so login and callback MUST use
+    // the same client.
     // Legacy fallback (for sessions started before this deploy OR the old
     // JSON-state format): infer from redirect_to presence or JSON-state
     // payload. Remove once in-flight legacy sessions have drained from
@@ -368,16 +322,27 @@ pub async fn oauth_callback(
 
     use jacquard_oauth::types::CallbackParams;
 
+    let callback_state = callback.state.clone();
     let params = CallbackParams {
         code: code.into(),
         state: Some(callback.state.into()),
         iss: callback.iss.map(|s| s.into()),
     };
 
-    let oauth_session = jacquard_client
-        .callback(params)
-        .await
-        .map_err(|e| AppError::OAuth(format!("Callback failed: {}", e)))?;
+    let oauth_session = match jacquard_client.callback(params).await {
+        Ok(session) => session,
+        Err(e) => {
+            let failure = OAuthFailure::from_oauth_error("Callback failed", &e);
+            note_login_failure(
+                &attempts,
+                &callback_state,
+                &failure.message,
+                failure.error_code.as_deref(),
+            )
+            .await;
+            return Err(AppError::OAuth(failure));
+        }
+    };
 
     // Jacquard stores the session in RedisAuthStore automatically.
     // Extract the session_id (now a clean UUID) and DID from the session data.
@@ -393,6 +358,43 @@ pub async fn oauth_callback(
 
     // Record successful OAuth login
     metrics::record_oauth_login(true);
+    if let Err(e) = attempts.exchanged(&callback_state, &did).await {
+        tracing::warn!(error = %e, "Failed to record login attempt progress");
+    }
+
+    // Device approval: hand the new session to the polling device and show
+    // this browser a confirmation instead of logging it in.
+    if let Some(code) = device_user_code {
+        let status = match crate::handlers::device_auth::resolve_user_code(
+            &state,
+            &code,
+            crate::handlers::device_auth::DeviceGrant::Session(&session_id),
+        )
+        .await
+        {
+            Ok(()) => {
+                metrics::record_device_auth("approved");
+                "approved"
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Device approval after OAuth failed");
+                note_login_failure(&attempts, &callback_state, &e.to_string(), None).await;
+                "expired"
+            }
+        };
+        let target = format!(
+            "{}?status={}",
+            state.config.device_auth.verification_uri, status
+        );
+        return Ok((
+            jar,
+            Response::builder()
+                .status(StatusCode::FOUND)
+                .header("Location", target)
+                .body(Body::empty())
+                .unwrap(),
+        ));
+    }
 
     // Set cookie — session_id is the Jacquard state/session identifier (clean UUID)
     let cookie = Cookie::build((SESSION_COOKIE_NAME, session_id.clone()))
@@ -404,9 +406,9 @@ pub async fn oauth_callback(
         .build();
 
     // Mode selection (Contract Rule 1 & FIX 4):
-    // If the flow was admitted in exchange mode (stored_mode == "exchange"),
+    // If the flow was admitted in exchange mode (LoginMode::Exchange),
     // it MUST complete in exchange mode or FAIL CLOSED (refusing downgrade).
-    let is_exchange_mode = stored_mode.as_deref() == Some("exchange");
+    let is_exchange_mode = exchange_mode;
 
     let app_redirect = if is_exchange_mode {
         let (Some(ref r), Some(ref nonce)) = (&redirect_to, &stored_nonce) else {
@@ -492,6 +494,58 @@ async fn resolve_handle_for_did(did: &str, pds_url: &str) -> String {
     }
 }
 
+/// Flow state the callback needs, from a `LoginAttempt` or the legacy keys.
+struct LoginFlow {
+    exchange_mode: bool,
+    redirect_to: Option<String>,
+    browser_nonce: Option<String>,
+    client_selector: Option<String>,
+    user_code: Option<String>,
+}
+
+/// Read (and consume) the per-field keys written by `login` before login
+/// attempts were persisted as one record. Remove once logins started before
+/// that deploy have drained (600s TTL + safety margin).
+async fn legacy_login_flow(state: &AppState, oauth_state: &str) -> LoginFlow {
+    let mut conn = state.redis.clone();
+    let mut values = Vec::with_capacity(5);
+    for field in ["oauth_mode", "oauth_redirect", "oauth_nonce", "oauth_client", "oauth_device"] {
+        let key = format!("{}:{}", field, oauth_state);
+        values.push(atomic_getdel(&mut conn, &key).await.ok().flatten());
+    }
+    let mut values = values.into_iter();
+    let mut next = || values.next().flatten();
+    LoginFlow {
+        exchange_mode: next().as_deref() == Some("exchange"),
+        redirect_to: next(),
+        browser_nonce: next(),
+        client_selector: next(),
+        user_code: next(),
+    }
+}
+
+/// Best-effort funnel update; losing one must never fail the login itself.
+async fn note_login_stage(attempts: &LoginAttemptStore, oauth_state: &str, stage: LoginStage) {
+    if let Err(e) = attempts.advance(oauth_state, stage).await {
+        tracing::warn!(
+            error = %e,
+            stage = stage.as_str(),
+            "Failed to record login attempt progress"
+        );
+    }
+}
+
+async fn note_login_failure(
+    attempts: &LoginAttemptStore,
+    oauth_state: &str,
+    reason: &str,
+    error_code: Option<&str>,
+) {
+    if let Err(e) = attempts.fail(oauth_state, reason, error_code).await {
+        tracing::warn!(error = %e, "Failed to record login attempt failure");
+    }
+}
+
 /// Legacy inference of the catmos client from callback signals.
 ///
 /// Used only when `oauth_client:{state}` is absent from Redis (in-flight
@@ -574,6 +628,60 @@ pub fn build_app_redirect(state_str: &str, session_id: &str) -> String {
     )
 }
 
+/// Handle app-password login (legacy createSession)
+///
+/// POST /auth/app-password
+///
+/// Nest performs `com.atproto.server.createSession` on the client's behalf,
+/// keeps the JWT pair sealed server-side, and returns the same opaque
+/// session_id an OAuth login would. The password itself is never stored.
+pub async fn app_password_login(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Json(payload): Json<AppPasswordLoginRequest>,
+) -> AppResult<(CookieJar, Json<AppPasswordLoginResponse>)> {
+    if !state.config.app_password.enabled {
+        return Err(AppError::NotFound("App-password login is disabled".into()));
+    }
+    let auth_store = state
+        .auth_store
+        .as_ref()
+        .ok_or_else(|| AppError::Internal("Auth store not configured".into()))?;
+    if state.session_encryption_key.is_none() {
+        // Refresh JWTs are only ever stored sealed (fail closed).
+        return Err(AppError::Internal(
+            "Session encryption key required for app-password sessions".into(),
+        ));
+    }
+
+    let (session_id, data) =
+        match crate::services::app_password::create_session(&state, auth_store, &payload).await {
+            Ok(created) => created,
+            Err(e) => {
+                metrics::record_app_password_login(false);
+                return Err(e);
+            }
+        };
+    metrics::record_app_password_login(true);
+
+    let cookie = Cookie::build((SESSION_COOKIE_NAME, session_id.clone()))
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .max_age(time::Duration::days(30))
+        .build();
+
+    Ok((
+        jar.add(cookie),
+        Json(AppPasswordLoginResponse {
+            session_id,
+            did: data.did,
+            handle: data.handle,
+        }),
+    ))
+}
+
 /// Handle logout
 ///
 /// POST /auth/logout
@@ -582,18 +690,28 @@ pub async fn logout(
     Extension(session): Extension<CatbirdSession>,
     jar: CookieJar,
 ) -> AppResult<(CookieJar, Json<LogoutResponse>)> {
-    let jacquard_client = state
-        .jacquard_client
-        .as_ref()
-        .ok_or_else(|| AppError::Internal("Jacquard OAuthClient not initialized".into()))?;
+    if session.auth_kind == SessionAuthKind::AppPassword {
+        let auth_store = state
+            .auth_store
+            .as_ref()
+            .ok_or_else(|| AppError::Internal("Auth store not configured".into()))?;
+        // Revokes the refresh JWT at the PDS (best effort) + store cleanup
+        crate::services::app_password::delete_session(&state, auth_store, &session.id.to_string())
+            .await?;
+    } else {
+        let jacquard_client = state
+            .jacquard_client
+            .as_ref()
+            .ok_or_else(|| AppError::Internal("Jacquard OAuthClient not initialized".into()))?;
 
-    // Revoke via Jacquard (handles token revocation at auth server + store cleanup)
-    let did = jacquard_common::types::did::Did::new(&session.did)
-        .map_err(|e| AppError::Internal(format!("Invalid DID: {e}")))?;
+        // Revoke via Jacquard (handles token revocation at auth server + store cleanup)
+        let did = jacquard_common::types::did::Did::new(&session.did)
+            .map_err(|e| AppError::Internal(format!("Invalid DID: {e}")))?;
 
-    if let Err(e) = jacquard_client.revoke(&did, &session.id.to_string()).await {
-        tracing::warn!("Failed to revoke Jacquard session: {}", e);
-        // Continue with logout even if revocation fails
+        if let Err(e) = jacquard_client.revoke(&did, &session.id.to_string()).await {
+            tracing::warn!("Failed to revoke Jacquard session: {}", e);
+            // Continue with logout even if revocation fails
+        }
     }
 
     let cookie = Cookie::build((SESSION_COOKIE_NAME, ""))
@@ -614,11 +732,27 @@ pub async fn logout(
 }
 
 /// Get current session info
-pub async fn get_session(Extension(session): Extension<CatbirdSession>) -> Json<SessionInfo> {
+pub async fn get_session(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+) -> Json<SessionInfo> {
+    let last_auth_failure = match state.auth_store.as_ref() {
+        Some(auth_store) => auth_store
+            .last_auth_failure(&session.id.to_string())
+            .await
+            .unwrap_or_else(|e| {
+                tracing::debug!(error = %e, "Failed to read last auth failure");
+                None
+            }),
+        None => None,
+    };
+
     Json(SessionInfo {
         did: session.did,
         handle: session.handle,
         created_at: session.created_at,
+        auth_kind: session.auth_kind,
+        last_auth_failure,
     })
 }
 
@@ -728,7 +862,7 @@ pub fn generate_exchange_code() -> String {
 }
 
 /// Atomically retrieve and delete a key from Redis using GETDEL (or Lua script fallback)
-async fn atomic_getdel(
+pub(crate) async fn atomic_getdel(
     conn: &mut redis::aio::ConnectionManager,
     key: &str,
 ) -> Result<Option<String>, redis::RedisError> {
@@ -1076,6 +1210,11 @@ async fn mirror_push_mutation_if_needed(
                     .await?;
             }
         }
+        "app.bsky.actor.putPreferences" => {
+            push.moderation_cache
+                .apply_put_preferences(&session.did, &body)
+                .await?;
+        }
         "app.bsky.graph.muteActorList" => {
             if let (Some(list), Some(dpop)) = (
                 body.get("list").and_then(|value| value.as_str()),
@@ -1110,6 +1249,18 @@ async fn mirror_push_mutation_if_needed(
         "com.atproto.repo.createRecord" => {
             if let Some(collection) = body.get("collection").and_then(|value| value.as_str()) {
                 match collection {
+                    "app.bsky.graph.follow" => {
+                        if let Some(subject) = body
+                            .get("record")
+                            .and_then(|value| value.get("subject"))
+                            .and_then(|value| value.as_str())
+                        {
+                            let rkey = body.get("rkey").and_then(|value| value.as_str());
+                            push.moderation_cache
+                                .upsert_follow(&session.did, subject, rkey)
+                                .await?;
+                        }
+                    }
                     "app.bsky.graph.block" => {
                         if let Some(subject) = body
                             .get("record")
@@ -1138,10 +1289,24 @@ async fn mirror_push_mutation_if_needed(
             }
         }
         "com.atproto.repo.deleteRecord" => {
-            if let (Some(collection), Some(dpop)) = (
-                body.get("collection").and_then(|value| value.as_str()),
-                jacquard_dpop,
-            ) {
+            let collection = body.get("collection").and_then(|value| value.as_str());
+            if collection == Some("app.bsky.graph.follow") {
+                // Deletes only carry the rkey; when the follow wasn't stored
+                // under it, force a re-sync before the next follows check.
+                let removed = match body.get("rkey").and_then(|value| value.as_str()) {
+                    Some(rkey) => {
+                        push.moderation_cache
+                            .remove_follow_by_rkey(&session.did, rkey)
+                            .await?
+                    }
+                    None => false,
+                };
+                if !removed {
+                    push.moderation_cache
+                        .invalidate_follows(&session.did)
+                        .await?;
+                }
+            } else if let (Some(collection), Some(dpop)) = (collection, jacquard_dpop) {
                 match collection {
                     "app.bsky.graph.block" => {
                         push.moderation_cache
@@ -1157,8 +1322,48 @@ async fn mirror_push_mutation_if_needed(
                 }
             }
         }
+        "app.bsky.notification.updateSeen" => {
+            if push.badges.reset_notifications(&session.did).await? {
+                spawn_read_update(state, &session.did, Vec::new());
+            }
+        }
+        "chat.bsky.convo.updateRead" => {
+            if let Some(convo_id) = body.get("convoId").and_then(|value| value.as_str()) {
+                let read = push.badges.reset_chat(&session.did, Some(convo_id)).await?;
+                if !read.is_empty() {
+                    spawn_read_update(state, &session.did, read);
+                }
+            }
+        }
+        "chat.bsky.convo.updateAllRead" => {
+            let read = push.badges.reset_chat(&session.did, None).await?;
+            if !read.is_empty() {
+                spawn_read_update(state, &session.did, read);
+            }
+        }
         _ => {}
     }
 
     Ok(())
 }
+
+/// Pushes the lowered badge to the account's devices, retracting the
+/// notifications of any chats in `read_convos`, without holding up the
+/// proxied response.
+fn spawn_read_update(state: &Arc<AppState>, did: &str, read_convos: Vec<String>) {
+    let Some(push) = state.push.clone() else {
+        return;
+    };
+    let state = state.clone();
+    let did = did.to_string();
+    tokio::spawn(async move {
+        if read_convos.is_empty() {
+            push.send_badge_update(&state, &did).await;
+        }
+        // Retractions carry the badge themselves.
+        for convo_id in &read_convos {
+            push.retract_chat_notifications(&state, &did, convo_id, None)
+                .await;
+        }
+    });
+}
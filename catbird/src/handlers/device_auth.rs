@@ -0,0 +1,381 @@
+//! Device-code login for secondary screens
+//!
+//! The ADR-014 exchange flow (`browser_nonce` + `/auth/exchange`) hands the
+//! session back to the app through a redirect, so the browser and the app
+//! must share an origin or a registered callback. A Mac app without a
+//! browser session, a CLI or a TV has neither. They use a
+//! device-authorization style flow instead (after RFC 8628):
+//!
+//! 1. The device calls `POST /auth/device/code` and displays the `user_code`.
+//! 2. The user approves it either on a device already logged in to Nest
+//!    (`POST /auth/device/approve`, which shares that session), or by running
+//!    OAuth on their phone via `/auth/login?user_code=...`, which mints a new
+//!    session for the device.
+//! 3. The device polls `POST /auth/device/token` with its secret
+//!    `device_code` and receives the session_id exactly once (GETDEL).
+//!
+//! Redis keys, all expiring with the code:
+//!   `device_auth:<sha256(device_code)>`  → user code (pending marker)
+//!   `device_user_code:<user_code>`       → sha256(device_code); consumed on approve/deny
+//!   `device_grant:<sha256(device_code)>` → `session:<sealed session_id>` or `denied`
+//!   `device_poll:<sha256(device_code)>`  → poll throttle (`interval` TTL)
+
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use rand::Rng;
+
+use super::atproto::{atomic_getdel, generate_exchange_code, is_valid_base64url_43};
+use crate::config::AppState;
+use crate::error::{AppError, AppResult};
+use crate::metrics;
+use crate::models::{
+    CatbirdSession, DeviceApproveRequest, DeviceApproveResponse, DeviceCodeResponse,
+    DeviceTokenRequest, DeviceTokenResponse,
+};
+
+/// RFC 8628 §6.1: consonants only, so codes never spell words and survive
+/// being read aloud. 20^8 ≈ 2.6e10 codes.
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LEN: usize = 8;
+
+const GRANT_DENIED: &str = "denied";
+const GRANT_SESSION_PREFIX: &str = "session:";
+
+/// What the user decided for a pending code.
+pub(crate) enum DeviceGrant<'a> {
+    Session(&'a str),
+    Denied,
+}
+
+fn device_key(hash: &str) -> String {
+    format!("device_auth:{}", hash)
+}
+
+fn user_code_key(user_code: &str) -> String {
+    format!("device_user_code:{}", user_code)
+}
+
+fn grant_key(hash: &str) -> String {
+    format!("device_grant:{}", hash)
+}
+
+fn poll_key(hash: &str) -> String {
+    format!("device_poll:{}", hash)
+}
+
+/// Hex SHA-256 of the polling handle; the handle itself is never stored.
+fn device_code_hash(device_code: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(device_code.as_bytes()))
+}
+
+fn generate_user_code() -> String {
+    let mut rng = rand::rngs::OsRng;
+    let raw: String = (0..USER_CODE_LEN)
+        .map(|_| USER_CODE_ALPHABET[rng.gen_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &raw[..4], &raw[4..])
+}
+
+/// Canonical `XXXX-XXXX` form of what the user typed (case, dashes and
+/// spaces are forgiven), or `None` if it cannot be a code we issued.
+pub(crate) fn normalize_user_code(input: &str) -> Option<String> {
+    let raw: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if raw.len() != USER_CODE_LEN || !raw.bytes().all(|b| USER_CODE_ALPHABET.contains(&b)) {
+        return None;
+    }
+    Some(format!("{}-{}", &raw[..4], &raw[4..]))
+}
+
+fn device_error(status: StatusCode, error: &str, message: &str) -> AppError {
+    AppError::AtprotoResponse {
+        status,
+        error: error.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn ensure_enabled(state: &AppState) -> AppResult<()> {
+    if !state.config.device_auth.enabled {
+        return Err(AppError::NotFound("Device login is disabled".into()));
+    }
+    Ok(())
+}
+
+/// Whether `user_code` is still waiting for a decision.
+pub(crate) async fn is_pending_user_code(state: &AppState, user_code: &str) -> AppResult<bool> {
+    let mut conn = state.redis.clone();
+    let exists: bool = redis::cmd("EXISTS")
+        .arg(user_code_key(user_code))
+        .query_async(&mut conn)
+        .await?;
+    Ok(exists)
+}
+
+/// Record the user's decision for a pending code. The user code is consumed,
+/// so a code can be decided once; the grant expires with the device code.
+pub(crate) async fn resolve_user_code(
+    state: &AppState,
+    user_code: &str,
+    grant: DeviceGrant<'_>,
+) -> AppResult<()> {
+    let mut conn = state.redis.clone();
+    let Some(hash) = atomic_getdel(&mut conn, &user_code_key(user_code)).await? else {
+        return Err(AppError::NotFound("Unknown or expired device code".into()));
+    };
+
+    let ttl: i64 = redis::cmd("TTL")
+        .arg(device_key(&hash))
+        .query_async(&mut conn)
+        .await?;
+    if ttl <= 0 {
+        return Err(AppError::NotFound("Unknown or expired device code".into()));
+    }
+
+    let value = match grant {
+        DeviceGrant::Session(session_id) => {
+            let enc_key = state.session_encryption_key.as_ref().ok_or_else(|| {
+                AppError::Internal("Session encryption key required for device grants".into())
+            })?;
+            let sealed = crate::services::redis_crypto::seal(enc_key, session_id.as_bytes())
+                .map_err(|e| AppError::Internal(format!("Failed to seal session_id: {}", e)))?;
+            format!("{}{}", GRANT_SESSION_PREFIX, sealed)
+        }
+        DeviceGrant::Denied => GRANT_DENIED.to_string(),
+    };
+
+    redis::cmd("SET")
+        .arg(grant_key(&hash))
+        .arg(value)
+        .arg("EX")
+        .arg(ttl)
+        .arg("NX")
+        .query_async::<_, Option<String>>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Start a device login
+///
+/// POST /auth/device/code
+pub async fn device_code(
+    State(state): State<Arc<AppState>>,
+) -> AppResult<Json<DeviceCodeResponse>> {
+    ensure_enabled(&state)?;
+    if state.session_encryption_key.is_none() {
+        // Grants carry a session_id and are only ever stored sealed (fail closed).
+        return Err(AppError::Internal(
+            "Session encryption key required for device login".into(),
+        ));
+    }
+
+    let config = &state.config.device_auth;
+    let device_code = generate_exchange_code();
+    let hash = device_code_hash(&device_code);
+    let mut conn = state.redis.clone();
+
+    // Retry on the (unlikely) user-code collision rather than overwrite
+    // someone else's pending code.
+    let mut user_code = None;
+    for _ in 0..5 {
+        let candidate = generate_user_code();
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(user_code_key(&candidate))
+            .arg(&hash)
+            .arg("EX")
+            .arg(config.code_ttl_seconds)
+            .arg("NX")
+            .query_async(&mut conn)
+            .await?;
+        if reply.is_some() {
+            user_code = Some(candidate);
+            break;
+        }
+    }
+    let user_code = user_code
+        .ok_or_else(|| AppError::Internal("Failed to allocate a device user code".into()))?;
+
+    redis::cmd("SET")
+        .arg(device_key(&hash))
+        .arg(&user_code)
+        .arg("EX")
+        .arg(config.code_ttl_seconds)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    metrics::record_device_auth("issued");
+
+    Ok(Json(DeviceCodeResponse {
+        device_code,
+        verification_uri_complete: format!("{}?user_code={}", config.verification_uri, user_code),
+        user_code,
+        verification_uri: config.verification_uri.clone(),
+        expires_in: config.code_ttl_seconds,
+        interval: config.poll_interval_seconds,
+    }))
+}
+
+/// Approve (or deny) a device from a logged-in session
+///
+/// POST /auth/device/approve
+///
+/// Approving hands the device this same session, so logging out on either
+/// ends both. Devices that need their own session are approved by running
+/// OAuth with `/auth/login?user_code=...` instead.
+pub async fn device_approve(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+    Json(payload): Json<DeviceApproveRequest>,
+) -> AppResult<Json<DeviceApproveResponse>> {
+    ensure_enabled(&state)?;
+    let user_code = normalize_user_code(&payload.user_code)
+        .ok_or_else(|| AppError::BadRequest("Invalid device code".into()))?;
+
+    let session_id = session.id.to_string();
+    let grant = if payload.approve {
+        DeviceGrant::Session(&session_id)
+    } else {
+        DeviceGrant::Denied
+    };
+    resolve_user_code(&state, &user_code, grant).await?;
+
+    tracing::info!(did = %session.did, approved = payload.approve, "Device login decided");
+    metrics::record_device_auth(if payload.approve {
+        "approved"
+    } else {
+        "denied"
+    });
+
+    Ok(Json(DeviceApproveResponse { success: true }))
+}
+
+/// Poll for the outcome of a device login
+///
+/// POST /auth/device/token
+///
+/// Errors follow RFC 8628 §3.5: `authorization_pending`, `slow_down`,
+/// `access_denied` and `expired_token`.
+pub async fn device_token(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DeviceTokenRequest>,
+) -> AppResult<Json<DeviceTokenResponse>> {
+    ensure_enabled(&state)?;
+    if !is_valid_base64url_43(&payload.device_code) {
+        return Err(device_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_grant",
+            "Invalid device code",
+        ));
+    }
+
+    let hash = device_code_hash(&payload.device_code);
+    let mut conn = state.redis.clone();
+
+    let within_interval: Option<String> = redis::cmd("SET")
+        .arg(poll_key(&hash))
+        .arg(1)
+        .arg("EX")
+        .arg(state.config.device_auth.poll_interval_seconds.max(1))
+        .arg("NX")
+        .query_async(&mut conn)
+        .await?;
+    if within_interval.is_none() {
+        return Err(device_error(
+            StatusCode::BAD_REQUEST,
+            "slow_down",
+            "Polling too fast",
+        ));
+    }
+
+    let Some(grant) = atomic_getdel(&mut conn, &grant_key(&hash)).await? else {
+        let pending: bool = redis::cmd("EXISTS")
+            .arg(device_key(&hash))
+            .query_async(&mut conn)
+            .await?;
+        return Err(if pending {
+            device_error(
+                StatusCode::BAD_REQUEST,
+                "authorization_pending",
+                "Waiting for the user to approve this device",
+            )
+        } else {
+            device_error(
+                StatusCode::BAD_REQUEST,
+                "expired_token",
+                "Device code expired; start again",
+            )
+        });
+    };
+
+    // The grant was consumed above; the pending marker goes with it.
+    let _: Result<(), _> = redis::cmd("DEL")
+        .arg(device_key(&hash))
+        .query_async(&mut conn)
+        .await;
+
+    let Some(sealed) = grant.strip_prefix(GRANT_SESSION_PREFIX) else {
+        return Err(device_error(
+            StatusCode::FORBIDDEN,
+            "access_denied",
+            "The user denied this device",
+        ));
+    };
+
+    let enc_key = state
+        .session_encryption_key
+        .as_ref()
+        .ok_or_else(|| AppError::Internal("Session encryption key not configured".into()))?;
+    let plaintext = crate::services::redis_crypto::open(enc_key, sealed)
+        .map_err(|e| AppError::Internal(format!("Failed to open device grant: {}", e)))?;
+    let session_id = String::from_utf8(plaintext)
+        .map_err(|_| AppError::Internal("Device grant is not valid UTF-8".into()))?;
+
+    metrics::record_device_auth("redeemed");
+
+    Ok(Json(DeviceTokenResponse { session_id }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_user_codes_normalize_to_themselves() {
+        for _ in 0..32 {
+            let code = generate_user_code();
+            assert_eq!(normalize_user_code(&code).as_deref(), Some(code.as_str()));
+        }
+    }
+
+    #[test]
+    fn normalization_forgives_case_and_separators() {
+        assert_eq!(
+            normalize_user_code(" bcdf ghjk ").as_deref(),
+            Some("BCDF-GHJK")
+        );
+        assert_eq!(
+            normalize_user_code("bcdf-ghjk").as_deref(),
+            Some("BCDF-GHJK")
+        );
+    }
+
+    #[test]
+    fn rejects_codes_we_never_issue() {
+        // Vowels and digits are outside the alphabet; length must be exact.
+        assert_eq!(normalize_user_code("ABCD-EFGH"), None);
+        assert_eq!(normalize_user_code("BCDF-GHJ1"), None);
+        assert_eq!(normalize_user_code("BCDF-GHJ"), None);
+    }
+
+    #[test]
+    fn device_code_hash_is_stable_hex() {
+        let hash = device_code_hash("abc");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, device_code_hash("abc"));
+    }
+}
@@ -1,5 +1,7 @@
 // This file exports handler functions for the routes defined in the application.
 
+pub mod admin;
 pub mod atproto;
 pub mod chat_poll;
+pub mod device_auth;
 pub mod push;
@@ -11,13 +11,18 @@ use serde_json::json;
 use crate::{
     config::AppState,
     error::{AppError, AppResult},
+    metrics,
     models::CatbirdSession,
     services::push::{
-        push_unavailable_error,
+        chat_preview::normalize_preview_key,
+        localization::normalize_locale,
+        notification_kind::NotificationKind,
+        push_unavailable_error, send_test,
         types::{
-            PutActivitySubscriptionInput, PutPreferencesInput, RegisterPushInput,
-            UnregisterPushInput,
+            PutActivitySubscriptionInput, PutDevicePreferencesInput, PutPreferencesInput,
+            PutQuietHoursInput, RegisterPushInput, SendTestInput, UnregisterPushInput,
         },
+        web_push::{WebPushSubscription, PLATFORM_WEB},
     },
 };
 
@@ -27,13 +32,34 @@ pub struct ListActivitySubscriptionsQuery {
     pub cursor: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GetDeliveryLogQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
 pub async fn register_push(
     State(state): State<Arc<AppState>>,
     Extension(session): Extension<CatbirdSession>,
-    Json(input): Json<RegisterPushInput>,
+    Json(mut input): Json<RegisterPushInput>,
 ) -> AppResult<StatusCode> {
     let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
     push.registry.validate_service_did(&input.service_did)?;
+    if input.platform == PLATFORM_WEB {
+        input.token = web_subscription_token(&input.token)?;
+    }
+    input.locale = input
+        .locale
+        .as_deref()
+        .map(normalize_locale)
+        .transpose()
+        .map_err(AppError::BadRequest)?;
+    input.preview_key = input
+        .preview_key
+        .as_deref()
+        .map(normalize_preview_key)
+        .transpose()
+        .map_err(AppError::BadRequest)?;
     push.registry
         .upsert_registration(&session, &input)
         .await
@@ -45,10 +71,16 @@ pub async fn register_push(
 pub async fn unregister_push(
     State(state): State<Arc<AppState>>,
     Extension(session): Extension<CatbirdSession>,
-    Json(input): Json<UnregisterPushInput>,
+    Json(mut input): Json<UnregisterPushInput>,
 ) -> AppResult<StatusCode> {
     let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
     push.registry.validate_service_did(&input.service_did)?;
+    if input.platform == PLATFORM_WEB {
+        // Match the canonical form stored by registerPush.
+        if let Ok(subscription) = WebPushSubscription::parse(&input.token) {
+            input.token = subscription.to_token();
+        }
+    }
     push.registry
         .deactivate_registration(&session, &input)
         .await
@@ -68,6 +100,19 @@ pub async fn unregister_push(
     Ok(StatusCode::OK)
 }
 
+/// VAPID public key for `PushManager.subscribe({ applicationServerKey })`.
+pub async fn get_web_push_key(
+    State(state): State<Arc<AppState>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
+    let web_push = push
+        .transports
+        .web_push
+        .as_ref()
+        .ok_or_else(|| AppError::Config("Web Push delivery is not configured".into()))?;
+    Ok(Json(json!({ "publicKey": web_push.public_key() })))
+}
+
 pub async fn get_preferences(
     State(state): State<Arc<AppState>>,
     Extension(session): Extension<CatbirdSession>,
@@ -103,6 +148,159 @@ pub async fn put_preferences_v2(
     Ok(Json(json!({ "preferences": prefs.to_lexicon_json() })))
 }
 
+/// Sets the preference overrides for the calling device, identified by its
+/// registered token. Returns the overrides and the merged preferences the
+/// device now receives.
+pub async fn put_device_preferences(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+    Json(mut input): Json<PutDevicePreferencesInput>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
+    push.registry.validate_service_did(&input.service_did)?;
+    if input.platform == PLATFORM_WEB {
+        // Match the canonical form stored by registerPush.
+        if let Ok(subscription) = WebPushSubscription::parse(&input.token) {
+            input.token = subscription.to_token();
+        }
+    }
+    if let Some(overrides) = &input.overrides {
+        PutPreferencesInput::deserialize(overrides)
+            .map_err(|e| AppError::BadRequest(format!("Invalid overrides: {e}")))?;
+    }
+
+    let updated = push
+        .registry
+        .set_preference_overrides(&session, &input)
+        .await
+        .map_err(internal_error)?;
+    if !updated {
+        return Err(AppError::NotFound(
+            "No active push registration for this device".to_string(),
+        ));
+    }
+
+    let prefs = push
+        .preferences
+        .get_or_create(&session.did)
+        .await
+        .map_err(internal_error)?;
+    let device_prefs = prefs.with_overrides(input.overrides.as_ref());
+    Ok(Json(json!({
+        "overrides": input.overrides,
+        "preferences": device_prefs.to_lexicon_json(),
+    })))
+}
+
+pub async fn get_quiet_hours(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
+    push.registry
+        .touch_account_session(&session)
+        .await
+        .map_err(internal_error)?;
+    let prefs = push
+        .preferences
+        .get_or_create(&session.did)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(json!({ "quietHours": prefs.quiet_hours })))
+}
+
+pub async fn put_quiet_hours(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+    Json(input): Json<PutQuietHoursInput>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
+    input.quiet_hours.validate().map_err(AppError::BadRequest)?;
+    push.registry
+        .touch_account_session(&session)
+        .await
+        .map_err(internal_error)?;
+    let quiet_hours = push
+        .preferences
+        .put_quiet_hours(&session.did, input.quiet_hours)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(json!({ "quietHours": quiet_hours })))
+}
+
+/// The authenticated account's recent push decisions and delivery
+/// attempts, newest first.
+pub async fn get_delivery_log(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+    Query(query): Query<GetDeliveryLogQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
+    let before_id = query
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<i64>()
+                .map_err(|_| AppError::BadRequest("Invalid cursor".to_string()))
+        })
+        .transpose()?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+
+    let entries = push
+        .deliveries
+        .recent(&session.did, limit, before_id)
+        .await
+        .map_err(internal_error)?;
+    let cursor = (entries.len() as i64 == limit)
+        .then(|| entries.last().map(|entry| entry.id.to_string()))
+        .flatten();
+
+    Ok(Json(json!({
+        "cursor": cursor,
+        "deliveries": entries.iter().map(|entry| entry.to_json()).collect::<Vec<_>>(),
+    })))
+}
+
+/// Runs a synthetic event of the requested type through the decision
+/// engine and sends a test notification to every active registration,
+/// reporting per-device outcomes. Limited per account by `SEND_TEST_LIMIT`.
+pub async fn send_test(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<CatbirdSession>,
+    Json(input): Json<SendTestInput>,
+) -> AppResult<Json<send_test::TestReport>> {
+    let push = state.push.as_ref().ok_or_else(push_unavailable_error)?;
+    let kind = match input.notification_type.as_deref() {
+        Some(name) => name
+            .parse::<NotificationKind>()
+            .map_err(|_| AppError::BadRequest(format!("Unknown notification type: {name}")))?,
+        None => NotificationKind::Mention,
+    };
+    let actor = input.actor.unwrap_or_else(|| session.did.clone());
+    if !actor.starts_with("did:") {
+        return Err(AppError::BadRequest(format!("Invalid actor DID: {actor}")));
+    }
+
+    if let Err(retry_after) = push
+        .test_limiter
+        .check(&session.did, &send_test::SEND_TEST_LIMIT)
+        .await
+    {
+        metrics::record_rate_limit_exceeded("push_send_test");
+        return Err(AppError::RateLimitExceeded { retry_after });
+    }
+
+    push.registry
+        .touch_account_session(&session)
+        .await
+        .map_err(internal_error)?;
+    let report = send_test::send_test(&state, push, &session.did, kind, &actor)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(report))
+}
+
 pub async fn list_activity_subscriptions(
     State(state): State<Arc<AppState>>,
     Extension(session): Extension<CatbirdSession>,
@@ -164,6 +362,17 @@ pub async fn put_activity_subscription(
     })))
 }
 
+/// Web registrations carry the browser's `PushSubscription` JSON as the
+/// token; store it in canonical form once its keys and endpoint check out.
+fn web_subscription_token(token: &str) -> AppResult<String> {
+    let subscription =
+        WebPushSubscription::parse(token).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    subscription
+        .validate_endpoint()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    Ok(subscription.to_token())
+}
+
 fn internal_error(err: anyhow::Error) -> AppError {
     AppError::Internal(err.to_string())
 }
@@ -70,6 +70,10 @@ async fn main() -> anyhow::Result<()> {
     metrics::register_metrics();
     tracing::info!("Prometheus metrics registered");
 
+    if state.config.token_refresher.enabled {
+        crate::services::token_refresher::TokenRefresher::new(state.clone()).spawn();
+    }
+
     if let Some(push) = state.push.clone() {
         push.spawn_worker(state.clone());
     }
@@ -122,12 +126,12 @@ async fn main() -> anyhow::Result<()> {
             ])
     };
 
-    // Start admin metrics server on internal-only port
+    // Start admin server (metrics + diagnostics) on internal-only port
     let admin_port = app_config.server.admin_port;
+    let admin_app = routes::admin::create_router().with_state(state.clone());
     tokio::spawn(async move {
-        let admin_app = Router::new().route("/metrics", get(metrics::metrics_handler));
         let admin_addr = SocketAddr::from(([127, 0, 0, 1], admin_port));
-        tracing::info!("Admin metrics listening on http://{}", admin_addr);
+        tracing::info!("Admin server listening on http://{}", admin_addr);
         let listener = tokio::net::TcpListener::bind(admin_addr)
             .await
             .expect("Failed to bind admin metrics port");
@@ -44,16 +44,75 @@ lazy_static! {
         &["status"]
     ).unwrap();
 
+    pub static ref APP_PASSWORD_LOGINS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_app_password_logins_total", "Total app-password (createSession) login attempts"),
+        &["status"]
+    ).unwrap();
+
+    pub static ref DEVICE_AUTH_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_device_auth_total", "Device-code login events (issued, approved, denied, redeemed)"),
+        &["event"]
+    ).unwrap();
+
+    pub static ref LOGIN_FUNNEL_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_login_funnel_total", "OAuth login attempts reaching each stage"),
+        &["client", "stage"]
+    ).unwrap();
+
+    pub static ref LOGIN_FAILURES_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_login_failures_total", "OAuth login attempts failed, by the stage they had reached"),
+        &["client", "stage"]
+    ).unwrap();
+
     pub static ref TOKEN_REFRESHES_TOTAL: CounterVec = CounterVec::new(
         Opts::new("catbird_token_refreshes_total", "Total token refresh attempts"),
         &["status"]
     ).unwrap();
 
+    pub static ref BACKGROUND_TOKEN_REFRESHES_TOTAL: CounterVec = CounterVec::new(
+        Opts::new(
+            "catbird_background_token_refreshes_total",
+            "Background token refresher outcomes"
+        ),
+        &["kind", "outcome"]
+    ).unwrap();
+
+    pub static ref TOKEN_REFRESH_DUE: Gauge = Gauge::new(
+        "catbird_token_refresh_due",
+        "Scheduled sessions inside the proactive refresh window"
+    ).unwrap();
+
     pub static ref ACTIVE_SESSIONS: Gauge = Gauge::new(
         "catbird_active_sessions",
         "Number of active sessions in Redis"
     ).unwrap();
 
+    // Push Metrics
+    pub static ref PUSH_DEAD_LETTERS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_push_dead_letters_total", "Push events moved to the dead letter table after exhausting their attempts"),
+        &["notification_type"]
+    ).unwrap();
+
+    pub static ref PUSH_DEAD_LETTER_DEPTH: Gauge = Gauge::new(
+        "catbird_push_dead_letter_depth",
+        "Push events currently in the dead letter table"
+    ).unwrap();
+
+    pub static ref PUSH_EVENTS_PROCESSED_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_push_events_processed_total", "Queued push events handled by the worker, by outcome"),
+        &["outcome"]
+    ).unwrap();
+
+    pub static ref PUSH_EVENTS_IN_FLIGHT: Gauge = Gauge::new(
+        "catbird_push_events_in_flight",
+        "Queued push events the worker is processing right now"
+    ).unwrap();
+
+    pub static ref PUSH_STEP_TIMEOUTS_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("catbird_push_step_timeouts_total", "Push worker steps abandoned after their timeout"),
+        &["step"]
+    ).unwrap();
+
     // Rate Limit Metrics
     pub static ref RATE_LIMIT_EXCEEDED_TOTAL: CounterVec = CounterVec::new(
         Opts::new("catbird_rate_limit_exceeded_total", "Total rate limit exceeded events"),
@@ -76,12 +135,45 @@ pub fn register_metrics() {
     REGISTRY
         .register(Box::new(OAUTH_LOGINS_TOTAL.clone()))
         .unwrap();
+    REGISTRY
+        .register(Box::new(APP_PASSWORD_LOGINS_TOTAL.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(DEVICE_AUTH_TOTAL.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(LOGIN_FUNNEL_TOTAL.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(LOGIN_FAILURES_TOTAL.clone()))
+        .unwrap();
     REGISTRY
         .register(Box::new(TOKEN_REFRESHES_TOTAL.clone()))
         .unwrap();
+    REGISTRY
+        .register(Box::new(BACKGROUND_TOKEN_REFRESHES_TOTAL.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(TOKEN_REFRESH_DUE.clone()))
+        .unwrap();
     REGISTRY
         .register(Box::new(ACTIVE_SESSIONS.clone()))
         .unwrap();
+    REGISTRY
+        .register(Box::new(PUSH_DEAD_LETTERS_TOTAL.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(PUSH_DEAD_LETTER_DEPTH.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(PUSH_EVENTS_PROCESSED_TOTAL.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(PUSH_EVENTS_IN_FLIGHT.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(PUSH_STEP_TIMEOUTS_TOTAL.clone()))
+        .unwrap();
     REGISTRY
         .register(Box::new(RATE_LIMIT_EXCEEDED_TOTAL.clone()))
         .unwrap();
@@ -122,17 +214,81 @@ pub fn record_oauth_login(success: bool) {
     OAUTH_LOGINS_TOTAL.with_label_values(&[status]).inc();
 }
 
+/// Record an app-password login attempt
+pub fn record_app_password_login(success: bool) {
+    let status = if success { "success" } else { "failure" };
+    APP_PASSWORD_LOGINS_TOTAL.with_label_values(&[status]).inc();
+}
+
+/// Record a device-code login event
+pub fn record_device_auth(event: &str) {
+    DEVICE_AUTH_TOTAL.with_label_values(&[event]).inc();
+}
+
+/// Record an OAuth login attempt reaching a funnel stage
+pub fn record_login_stage(client: &str, stage: &str) {
+    LOGIN_FUNNEL_TOTAL.with_label_values(&[client, stage]).inc();
+}
+
+/// Record an OAuth login attempt failing after reaching `stage`
+pub fn record_login_failure(client: &str, stage: &str) {
+    LOGIN_FAILURES_TOTAL
+        .with_label_values(&[client, stage])
+        .inc();
+}
+
 /// Record a token refresh attempt
 pub fn record_token_refresh(success: bool) {
     let status = if success { "success" } else { "failure" };
     TOKEN_REFRESHES_TOTAL.with_label_values(&[status]).inc();
 }
 
+/// Record a background token refresher outcome
+pub fn record_background_token_refresh(kind: &str, outcome: &str) {
+    BACKGROUND_TOKEN_REFRESHES_TOTAL
+        .with_label_values(&[kind, outcome])
+        .inc();
+}
+
+/// Update the number of sessions inside the proactive refresh window
+pub fn set_token_refresh_due(count: f64) {
+    TOKEN_REFRESH_DUE.set(count);
+}
+
 /// Update active sessions count
 pub fn set_active_sessions(count: f64) {
     ACTIVE_SESSIONS.set(count);
 }
 
+/// Record a push event moved to the dead letter table
+pub fn record_push_dead_letter(notification_type: &str) {
+    PUSH_DEAD_LETTERS_TOTAL
+        .with_label_values(&[notification_type])
+        .inc();
+}
+
+/// Update the number of push events in the dead letter table
+pub fn set_push_dead_letter_depth(count: f64) {
+    PUSH_DEAD_LETTER_DEPTH.set(count);
+}
+
+/// Record a queued push event the worker finished with
+pub fn record_push_event_processed(outcome: &str) {
+    PUSH_EVENTS_PROCESSED_TOTAL
+        .with_label_values(&[outcome])
+        .inc();
+}
+
+/// Adjust the number of push events being processed
+pub fn add_push_events_in_flight(delta: f64) {
+    PUSH_EVENTS_IN_FLIGHT.add(delta);
+}
+
+/// Record a push worker step that hit its timeout
+pub fn record_push_step_timeout(step: &str) {
+    PUSH_STEP_TIMEOUTS_TOTAL.with_label_values(&[step]).inc();
+}
+
 /// Record rate limit exceeded event
 pub fn record_rate_limit_exceeded(endpoint: &str) {
     RATE_LIMIT_EXCEEDED_TOTAL
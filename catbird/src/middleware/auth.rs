@@ -13,7 +13,7 @@ use std::sync::Arc;
 
 use crate::config::AppState;
 use crate::error::AppError;
-use crate::models::CatbirdSession;
+use crate::models::{AuthFailureSource, CatbirdSession, SessionAuthKind};
 use chrono::Utc;
 
 /// DPoP key data from Jacquard session, inserted into request extensions for the proxy.
@@ -23,6 +23,23 @@ pub struct JacquardDpopData {
     pub dpop_host_nonce: String,
 }
 
+impl JacquardDpopData {
+    /// Throwaway key material for sessions that are not DPoP-bound.
+    ///
+    /// App-password sessions authenticate with a plain Bearer token, and
+    /// `AtProtoClient` never signs a proof for them; this lets handlers and
+    /// background jobs keep threading one `(CatbirdSession, JacquardDpopData)`
+    /// pair regardless of session kind.
+    pub fn unbound() -> Self {
+        let secret_key = p256::SecretKey::random(&mut rand::thread_rng());
+        let crypto_key = jose_jwk::crypto::Key::from(secret_key);
+        Self {
+            dpop_key: jose_jwk::Key::from(&crypto_key),
+            dpop_host_nonce: String::new(),
+        }
+    }
+}
+
 /// Cookie name for the Catbird session
 pub const SESSION_COOKIE_NAME: &str = "catbird_session";
 
@@ -59,26 +76,16 @@ fn classify_auth_error(error: AppError) -> AppError {
             "TemporarilyUnavailable",
             "Authentication service is temporarily unavailable. Please retry.",
         ),
-        AppError::OAuth(message) => {
-            let lower = message.to_ascii_lowercase();
-            if lower.contains("invalid_grant")
-                || lower.contains("invalid_token")
-                || lower.contains("no refresh token")
-                || lower.contains("no per-session oauth data")
-            {
-                atproto_auth_error(
-                    StatusCode::UNAUTHORIZED,
-                    "ExpiredToken",
-                    "Session expired. Please log in again.",
-                )
-            } else {
-                atproto_auth_error(
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    "TemporarilyUnavailable",
-                    "Authentication service is temporarily unavailable. Please retry.",
-                )
-            }
-        }
+        AppError::OAuth(failure) if failure.permanent => atproto_auth_error(
+            StatusCode::UNAUTHORIZED,
+            "ExpiredToken",
+            "Session expired. Please log in again.",
+        ),
+        AppError::OAuth(_) => atproto_auth_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "TemporarilyUnavailable",
+            "Authentication service is temporarily unavailable. Please retry.",
+        ),
         AppError::AtprotoResponse { .. } => error,
         other => {
             tracing::warn!("Unexpected auth failure type: {}", other);
@@ -126,9 +133,10 @@ fn extract_session_id(req: &Request<Body>) -> Option<String> {
 ///
 /// This middleware:
 /// 1. Extracts the session ID from cookie or Authorization header
-/// 2. Validates the session via Jacquard SessionRegistry (with automatic token refresh)
-/// 3. Attempts legacy session migration if Jacquard lookup fails
-/// 4. Injects the session into request extensions
+/// 2. Resolves Nest-held app-password sessions (refreshing via `refreshSession`)
+/// 3. Otherwise validates the session via Jacquard SessionRegistry (with automatic token refresh)
+/// 4. Attempts legacy session migration if Jacquard lookup fails
+/// 5. Injects the session into request extensions
 pub async fn auth_middleware(
     State(state): State<Arc<AppState>>,
     mut req: Request<Body>,
@@ -145,13 +153,37 @@ pub async fn auth_middleware(
     let auth_store = state.auth_store.as_ref().ok_or_else(|| {
         classify_auth_error(AppError::Internal("Auth store not configured".into()))
     })?;
+
+    // App-password sessions are held by Nest itself (no Jacquard registry entry)
+    match crate::services::app_password::resolve_session(&state, auth_store, &session_id).await {
+        Ok(Some((session, dpop_data))) => {
+            note_session_activity(auth_store, &session_id).await;
+            req.extensions_mut().insert(session);
+            req.extensions_mut().insert(dpop_data);
+            return Ok(next.run(req).await);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            record_auth_failure(auth_store, &session_id, AuthFailureSource::Request, &e).await;
+            return Err(classify_auth_error(e));
+        }
+    }
+
     let jacquard_client = state.jacquard_client.as_ref().ok_or_else(|| {
         classify_auth_error(AppError::Internal("Jacquard client not configured".into()))
     })?;
 
     // Try Jacquard path (new sessions + already-migrated sessions)
-    match resolve_session_via_jacquard(auth_store, jacquard_client, &session_id).await {
+    match resolve_session_via_jacquard(
+        auth_store,
+        jacquard_client,
+        &session_id,
+        state.config.token_refresher.claim_ttl_seconds,
+    )
+    .await
+    {
         Ok((session, dpop_data)) => {
+            note_session_activity(auth_store, &session_id).await;
             req.extensions_mut().insert(session);
             req.extensions_mut().insert(dpop_data);
             return Ok(next.run(req).await);
@@ -161,6 +193,7 @@ pub async fn auth_middleware(
             tracing::debug!(session_id = %session_id, "Jacquard session not found, attempting legacy migration");
         }
         Err(e) => {
+            record_auth_failure(auth_store, &session_id, AuthFailureSource::Request, &e).await;
             return Err(classify_auth_error(e));
         }
     }
@@ -170,10 +203,15 @@ pub async fn auth_middleware(
         Ok(Some(_)) => {
             tracing::info!(session_id = %session_id, "Legacy session migrated, retrying Jacquard lookup");
             // Migration succeeded — retry Jacquard lookup
-            let (session, dpop_data) =
-                resolve_session_via_jacquard(auth_store, jacquard_client, &session_id)
-                    .await
-                    .map_err(classify_auth_error)?;
+            let (session, dpop_data) = resolve_session_via_jacquard(
+                auth_store,
+                jacquard_client,
+                &session_id,
+                state.config.token_refresher.claim_ttl_seconds,
+            )
+            .await
+            .map_err(classify_auth_error)?;
+            note_session_activity(auth_store, &session_id).await;
             req.extensions_mut().insert(session);
             req.extensions_mut().insert(dpop_data);
             Ok(next.run(req).await)
@@ -189,15 +227,44 @@ pub async fn auth_middleware(
     }
 }
 
+/// Mark a resolved session as worth keeping warm for the background token
+/// refresher. Best effort: a failure only means the next refresh may run inline.
+async fn note_session_activity(auth_store: &crate::services::RedisAuthStore, session_id: &str) {
+    if let Err(e) = auth_store.record_session_activity(session_id).await {
+        tracing::debug!(error = %e, "Failed to record session activity");
+    }
+}
+
+/// Keep the failure behind a typed `AppError::OAuth` as the session's last
+/// auth failure. Other errors carry nothing worth showing. Best effort.
+pub(crate) async fn record_auth_failure(
+    auth_store: &crate::services::RedisAuthStore,
+    session_id: &str,
+    source: AuthFailureSource,
+    error: &AppError,
+) {
+    let AppError::OAuth(failure) = error else {
+        return;
+    };
+    if let Err(e) = auth_store
+        .record_auth_failure(session_id, source, failure)
+        .await
+    {
+        tracing::debug!(error = %e, "Failed to record auth failure");
+    }
+}
+
 /// Resolve a session via Jacquard's SessionRegistry with automatic token refresh.
 ///
 /// iOS sends only session_id. We use the session_index to look up the DID,
-/// then call SessionRegistry.get() which handles token refresh atomically
-/// using in-process DashMap mutex (no Redis distributed locks needed).
+/// then load the session through `token_refresher::oauth_session`, which
+/// refreshes it under the same per-session claim as the background
+/// refresher so two replicas never spend one refresh token.
 async fn resolve_session_via_jacquard(
     auth_store: &crate::services::RedisAuthStore,
     jacquard_client: &crate::config::JacquardOAuthClient,
     session_id: &str,
+    claim_ttl_seconds: u64,
 ) -> Result<(CatbirdSession, JacquardDpopData), AppError> {
     use jacquard_common::types::did::Did;
 
@@ -211,12 +278,16 @@ async fn resolve_session_via_jacquard(
     let did = Did::new(&did_str)
         .map_err(|e| AppError::Internal(format!("Invalid DID in session index: {e}")))?;
 
-    // Step 2: Get session from registry (auto_refresh=true triggers token refresh if needed)
-    let session_data = jacquard_client
-        .registry
-        .get(&did, session_id, true)
-        .await
-        .map_err(|e| AppError::OAuth(format!("Jacquard session get failed: {e}")))?;
+    // Step 2: Get session from registry, refreshing the token if needed
+    let session_data = crate::services::token_refresher::oauth_session(
+        auth_store,
+        jacquard_client,
+        &did,
+        session_id,
+        claim_ttl_seconds,
+        "Jacquard session get failed",
+    )
+    .await?;
 
     // Step 3: Convert ClientSessionData → CatbirdSession for backward compatibility
     let expires_at = session_data
@@ -256,6 +327,7 @@ async fn resolve_session_via_jacquard(
         access_token_expires_at: expires_at,
         created_at: Utc::now(), // Not tracked in Jacquard session
         last_used_at: Utc::now(),
+        auth_kind: SessionAuthKind::OAuth,
     };
 
     Ok((session, dpop_data))
@@ -264,6 +336,7 @@ async fn resolve_session_via_jacquard(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::OAuthFailure;
     use axum::http::Request;
 
     #[test]
@@ -322,4 +395,30 @@ mod tests {
             _ => panic!("expected AtprotoResponse"),
         }
     }
+
+    #[test]
+    fn classifies_oauth_failures_by_permanence() {
+        let failure = |permanent| OAuthFailure {
+            kind: crate::error::OAuthFailureKind::ServerError,
+            permanent,
+            http_status: Some(400),
+            error_code: None,
+            message: "refresh failed".into(),
+        };
+
+        match classify_auth_error(AppError::OAuth(failure(true))) {
+            AppError::AtprotoResponse { status, error, .. } => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED);
+                assert_eq!(error, "ExpiredToken");
+            }
+            _ => panic!("expected AtprotoResponse"),
+        }
+        match classify_auth_error(AppError::OAuth(failure(false))) {
+            AppError::AtprotoResponse { status, error, .. } => {
+                assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+                assert_eq!(error, "TemporarilyUnavailable");
+            }
+            _ => panic!("expected AtprotoResponse"),
+        }
+    }
 }
@@ -7,5 +7,8 @@ mod rate_limit;
 mod request_id;
 
 pub use auth::{auth_middleware, JacquardDpopData, SESSION_COOKIE_NAME};
-pub use rate_limit::{ip_rate_limit, session_rate_limit, RateLimitConfig, RateLimitState};
+pub(crate) use auth::record_auth_failure;
+pub use rate_limit::{
+    ip_rate_limit, session_rate_limit, RateLimitConfig, RateLimitState, RateLimiter,
+};
 pub use request_id::{request_id_middleware, RequestId};
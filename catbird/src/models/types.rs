@@ -28,6 +28,22 @@ pub struct CatbirdSession {
     pub created_at: DateTime<Utc>,
     /// When this session was last used
     pub last_used_at: DateTime<Utc>,
+    /// How the upstream tokens were obtained (OAuth vs. app password)
+    #[serde(default)]
+    pub auth_kind: SessionAuthKind,
+}
+
+/// How a Nest session authenticates to the user's PDS.
+///
+/// OAuth sessions send `DPoP <token>` plus a DPoP proof; app-password
+/// sessions (`com.atproto.server.createSession`) send a plain
+/// `Bearer <accessJwt>`. Everything above `AtProtoClient` treats both alike.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionAuthKind {
+    #[default]
+    OAuth,
+    AppPassword,
 }
 
 impl CatbirdSession {
@@ -64,6 +80,31 @@ pub struct SessionInfo {
     pub did: String,
     pub handle: String,
     pub created_at: DateTime<Utc>,
+    pub auth_kind: SessionAuthKind,
+    /// Most recent token refresh / OAuth failure, if one was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_auth_failure: Option<AuthFailureRecord>,
+}
+
+/// Where an auth failure was observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthFailureSource {
+    /// `auth_middleware`, on a client request.
+    Request,
+    /// Push / chat-poll workers via `resolve_background_session`.
+    Background,
+    /// The background token refresher.
+    Refresher,
+}
+
+/// Last auth failure recorded for a session (`RedisAuthStore::record_auth_failure`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthFailureRecord {
+    pub source: AuthFailureSource,
+    #[serde(flatten)]
+    pub failure: crate::error::OAuthFailure,
+    pub occurred_at: DateTime<Utc>,
 }
 
 /// Logout response
@@ -85,3 +126,72 @@ pub struct ExchangeRequest {
 pub struct ExchangeResponse {
     pub session_id: String,
 }
+
+/// Request body for POST /auth/app-password
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppPasswordLoginRequest {
+    /// Handle, DID, or email — passed through to `createSession`
+    pub identifier: String,
+    /// App password (never stored)
+    pub password: String,
+    /// Email 2FA code, when the PDS answers `AuthFactorTokenRequired`
+    #[serde(default)]
+    pub auth_factor_token: Option<String>,
+    /// Explicit PDS to log in against; resolved from the identifier when absent
+    #[serde(default)]
+    pub pds_url: Option<String>,
+}
+
+/// Response body for POST /auth/app-password
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppPasswordLoginResponse {
+    pub session_id: String,
+    pub did: String,
+    pub handle: String,
+}
+
+/// Response body for POST /auth/device/code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCodeResponse {
+    /// Secret polling handle, kept on the requesting device
+    pub device_code: String,
+    /// Short code the user enters on a logged-in device (`XXXX-XXXX`)
+    pub user_code: String,
+    pub verification_uri: String,
+    /// `verification_uri` with the user code pre-filled
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    /// Minimum seconds between polls of /auth/device/token
+    pub interval: u64,
+}
+
+/// Request body for POST /auth/device/approve
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceApproveRequest {
+    pub user_code: String,
+    /// `false` denies the request instead
+    #[serde(default = "default_approve")]
+    pub approve: bool,
+}
+
+fn default_approve() -> bool {
+    true
+}
+
+/// Response body for POST /auth/device/approve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceApproveResponse {
+    pub success: bool,
+}
+
+/// Request body for POST /auth/device/token
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceTokenRequest {
+    pub device_code: String,
+}
+
+/// Response body for POST /auth/device/token once the device is approved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceTokenResponse {
+    pub session_id: String,
+}
@@ -0,0 +1,43 @@
+//! Admin Routes
+//!
+//! Internal-only endpoints served on the admin port:
+//! - /metrics - Prometheus metrics
+//! - /admin/* - operator diagnostics
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+
+use crate::config::AppState;
+use crate::handlers::admin;
+use crate::metrics;
+
+/// Create the admin router (bind it to loopback only).
+pub fn create_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/metrics", get(metrics::metrics_handler))
+        .route(
+            "/admin/sessions/:session_id",
+            get(admin::get_session_auth_status),
+        )
+        .route("/admin/login-attempts", get(admin::list_login_attempts))
+        .route(
+            "/admin/login-attempts/:state",
+            get(admin::get_login_attempt),
+        )
+        .route("/admin/push/deliveries", get(admin::list_push_deliveries))
+        .route(
+            "/admin/push/dead-letters",
+            get(admin::list_push_dead_letters),
+        )
+        .route(
+            "/admin/push/dead-letters/replay",
+            post(admin::replay_push_dead_letters),
+        )
+        .route(
+            "/admin/push/dead-letters/purge",
+            post(admin::purge_push_dead_letters),
+        )
+}
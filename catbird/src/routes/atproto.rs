@@ -16,7 +16,7 @@ use p256::elliptic_curve::sec1::ToEncodedPoint;
 use std::sync::Arc;
 
 use crate::config::AppState;
-use crate::handlers::{atproto, push};
+use crate::handlers::{atproto, device_auth, push};
 use crate::middleware::{auth_middleware, ip_rate_limit, session_rate_limit, RateLimitState};
 
 /// Create the ATProto router
@@ -54,7 +54,45 @@ pub fn create_router(state: Arc<AppState>) -> Router<Arc<AppState>> {
                     ip_rate_limit,
                 )),
         )
+        .route(
+            "/app-password",
+            post(atproto::app_password_login)
+                .layer(DefaultBodyLimit::max(4096))
+                .layer(middleware::from_fn_with_state(
+                    rate_limit_state.clone(),
+                    ip_rate_limit,
+                )),
+        )
+        .route(
+            "/device/code",
+            post(device_auth::device_code).layer(middleware::from_fn_with_state(
+                rate_limit_state.clone(),
+                ip_rate_limit,
+            )),
+        )
+        .route(
+            "/device/token",
+            post(device_auth::device_token)
+                .layer(DefaultBodyLimit::max(4096))
+                .layer(middleware::from_fn_with_state(
+                    rate_limit_state.clone(),
+                    ip_rate_limit,
+                )),
+        )
         // Protected auth routes
+        .route(
+            "/device/approve",
+            post(device_auth::device_approve)
+                .layer(DefaultBodyLimit::max(4096))
+                .layer(middleware::from_fn_with_state(
+                    rate_limit_state.clone(),
+                    session_rate_limit,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                )),
+        )
         .route(
             "/logout",
             post(atproto::logout).layer(middleware::from_fn_with_state(
@@ -96,6 +134,27 @@ pub fn create_router(state: Arc<AppState>) -> Router<Arc<AppState>> {
             "/app.bsky.notification.putActivitySubscription",
             post(push::put_activity_subscription),
         )
+        .route(
+            "/blue.catbird.push.getWebPushKey",
+            get(push::get_web_push_key),
+        )
+        .route(
+            "/blue.catbird.push.putDevicePreferences",
+            post(push::put_device_preferences),
+        )
+        .route(
+            "/blue.catbird.push.getQuietHours",
+            get(push::get_quiet_hours),
+        )
+        .route(
+            "/blue.catbird.push.putQuietHours",
+            post(push::put_quiet_hours),
+        )
+        .route(
+            "/blue.catbird.push.getDeliveryLog",
+            get(push::get_delivery_log),
+        )
+        .route("/blue.catbird.push.sendTest", post(push::send_test))
         .route(
             "/blue.catbird.bskychat.pushHeartbeat",
             post(crate::handlers::chat_poll::push_heartbeat),
@@ -2,5 +2,6 @@
 //!
 //! Defines all HTTP routes for the Catbird Gateway.
 
+pub mod admin;
 pub mod atproto;
 pub mod health;
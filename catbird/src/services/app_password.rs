@@ -0,0 +1,669 @@
+//! App-password (legacy) sessions held by Nest
+//!
+//! Petrel's "Legacy" mode logs in with `com.atproto.server.createSession`,
+//! and users whose PDS has no OAuth support have no other option. Instead of
+//! letting those sessions bypass Nest (and lose push, chat polling and
+//! moderation mirroring), the gateway performs `createSession` once, keeps the
+//! JWT pair sealed in Redis (AES-256-GCM via `redis_crypto`, fail closed), and
+//! hands the client the same opaque `session_id` an OAuth login produces.
+//!
+//! From there the session behaves like any other: `auth_middleware` and
+//! `resolve_background_session` resolve it to a `CatbirdSession`, the access
+//! JWT is rotated with `com.atproto.server.refreshSession` shortly before it
+//! expires, and `AtProtoClient` sends it as `Bearer` instead of DPoP.
+//!
+//! Refresh JWTs are single-use on the reference PDS, so concurrent refreshes
+//! of one session — from two requests, or two replicas — must be serialised.
+//! A short Redis lock does that; losers wait for the winner's write instead
+//! of burning the refresh token a second time.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ssrf::validate_pds_url;
+use crate::config::AppState;
+use crate::error::{AppError, AppResult, OAuthFailure};
+use crate::metrics;
+use crate::middleware::JacquardDpopData;
+use crate::models::{AppPasswordLoginRequest, CatbirdSession, SessionAuthKind};
+use crate::services::RedisAuthStore;
+
+/// Lifetime of the per-session refresh lock. Long enough to cover one
+/// `refreshSession` round trip under the shared 30s HTTP client timeout.
+pub(crate) const REFRESH_LOCK_TTL_SECONDS: u64 = 30;
+
+/// How long a request waits for another holder of the refresh lock before
+/// giving up with a retryable error.
+const REFRESH_WAIT_ATTEMPTS: u32 = 20;
+const REFRESH_WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Assumed access-JWT lifetime when the token carries no readable `exp`.
+/// The reference PDS issues two-hour access tokens.
+const DEFAULT_ACCESS_LIFETIME_SECONDS: i64 = 2 * 60 * 60;
+
+/// XRPC error codes from `createSession`/`refreshSession` that mean the
+/// credentials are dead rather than the PDS being briefly unhappy.
+const PERMANENT_REFRESH_ERRORS: &[&str] = &[
+    "ExpiredToken",
+    "InvalidToken",
+    "AccountTakedown",
+    "AccountDeactivated",
+    "AuthenticationRequired",
+];
+
+/// Sealed per-session record (`{prefix}app_password:{session_id}`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppPasswordSessionData {
+    pub did: String,
+    pub handle: String,
+    pub pds_url: String,
+    pub access_jwt: String,
+    pub refresh_jwt: String,
+    pub access_expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AppPasswordSessionData {
+    /// True when the access JWT expires within `skew_seconds`.
+    pub fn needs_refresh(&self, skew_seconds: i64) -> bool {
+        Utc::now() + chrono::Duration::seconds(skew_seconds) >= self.access_expires_at
+    }
+
+    /// Fails on a `session_id` that isn't a UUID: a fresh random id would
+    /// give the request a session that exists nowhere else.
+    pub fn to_session(&self, session_id: &str) -> AppResult<CatbirdSession> {
+        let id = uuid::Uuid::parse_str(session_id).map_err(|_| AppError::InvalidSession)?;
+        Ok(CatbirdSession {
+            id,
+            did: self.did.clone(),
+            handle: self.handle.clone(),
+            pds_url: self.pds_url.clone(),
+            access_token: self.access_jwt.clone(),
+            refresh_token: self.refresh_jwt.clone(),
+            access_token_expires_at: self.access_expires_at,
+            created_at: self.created_at,
+            last_used_at: Utc::now(),
+            auth_kind: SessionAuthKind::AppPassword,
+        })
+    }
+}
+
+/// `createSession` / `refreshSession` output (the fields Nest uses).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerSessionOutput {
+    access_jwt: String,
+    refresh_jwt: String,
+    handle: String,
+    did: String,
+    #[serde(default)]
+    did_doc: Option<Value>,
+}
+
+/// Log in with an app password and store the resulting session.
+///
+/// Returns the new Nest `session_id` and the stored record. The password is
+/// forwarded to the PDS and never persisted.
+pub async fn create_session(
+    state: &Arc<AppState>,
+    auth_store: &RedisAuthStore,
+    input: &AppPasswordLoginRequest,
+) -> AppResult<(String, AppPasswordSessionData)> {
+    let identifier = input.identifier.trim();
+    if identifier.is_empty() || input.password.is_empty() {
+        return Err(AppError::BadRequest(
+            "Missing identifier or password".into(),
+        ));
+    }
+
+    let login_pds = match input.pds_url.as_deref() {
+        Some(url) if !url.trim().is_empty() => url.trim().trim_end_matches('/').to_string(),
+        _ => resolve_pds_url(state, identifier).await?,
+    };
+    validate_pds_url(&login_pds)?;
+
+    let mut body = serde_json::json!({
+        "identifier": identifier,
+        "password": input.password,
+    });
+    if let Some(token) = input.auth_factor_token.as_deref() {
+        body["authFactorToken"] = Value::String(token.to_string());
+    }
+
+    let response = state
+        .http_client
+        .post(format!(
+            "{}/xrpc/com.atproto.server.createSession",
+            login_pds
+        ))
+        .json(&body)
+        .send()
+        .await?;
+    let status = response.status();
+    let bytes = response.bytes().await?;
+
+    if !status.is_success() {
+        let (error, message) = xrpc_error_parts(&bytes);
+        tracing::info!(status = %status, error = %error, "App-password createSession rejected");
+        // Pass the PDS's own error through (AuthFactorTokenRequired,
+        // AuthenticationRequired, RateLimitExceeded, ...) so the client can
+        // prompt for a 2FA code or show the right message.
+        let status = match status {
+            StatusCode::TOO_MANY_REQUESTS => StatusCode::TOO_MANY_REQUESTS,
+            s if s.is_client_error() => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        return Err(AppError::AtprotoResponse {
+            status,
+            error,
+            message,
+        });
+    }
+
+    let output: ServerSessionOutput = serde_json::from_slice(&bytes)?;
+
+    // The caller picked `login_pds`, so neither the DID nor the `didDoc` it
+    // returned can be taken on its word: resolve the DID ourselves. The
+    // account may live on a different PDS than the one we logged in against
+    // (e.g. an entryway); then that PDS must accept the new access token as
+    // the same account.
+    let pds_url = resolve_did_pds(state, &output.did).await?;
+    if pds_url != login_pds {
+        verify_session_at(state, &pds_url, &output.access_jwt, &output.did).await?;
+    }
+
+    let now = Utc::now();
+    let data = AppPasswordSessionData {
+        access_expires_at: access_expiry(&output.access_jwt, now),
+        did: output.did,
+        handle: output.handle,
+        pds_url,
+        access_jwt: output.access_jwt,
+        refresh_jwt: output.refresh_jwt,
+        created_at: now,
+    };
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    auth_store
+        .put_app_password_session(&session_id, &data)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to store app-password session: {e}")))?;
+
+    tracing::info!(did = %data.did, "App-password session created");
+    Ok((session_id, data))
+}
+
+/// Resolve an app-password session, refreshing the access JWT if needed.
+///
+/// Returns `Ok(None)` when `session_id` is not an app-password session, so
+/// callers fall through to the OAuth path. The returned `JacquardDpopData` is
+/// `JacquardDpopData::unbound()`: app-password sessions are Bearer-authenticated
+/// and `AtProtoClient` never signs with it.
+pub async fn resolve_session(
+    state: &Arc<AppState>,
+    auth_store: &RedisAuthStore,
+    session_id: &str,
+) -> AppResult<Option<(CatbirdSession, JacquardDpopData)>> {
+    let Some(data) = load(auth_store, session_id).await? else {
+        return Ok(None);
+    };
+
+    let skew = state.config.app_password.refresh_skew_seconds;
+    let data = if data.needs_refresh(skew) {
+        refresh_with_lock(state, auth_store, session_id, skew).await?
+    } else {
+        data
+    };
+
+    Ok(Some((data.to_session(session_id)?, JacquardDpopData::unbound())))
+}
+
+/// Refresh ahead of expiry for the background token refresher: rotates the
+/// JWT pair if the access token expires within `within_secs`.
+///
+/// Returns `Ok(None)` when `session_id` is not an app-password session.
+pub async fn refresh_if_expiring(
+    state: &Arc<AppState>,
+    auth_store: &RedisAuthStore,
+    session_id: &str,
+    within_secs: i64,
+) -> AppResult<Option<AppPasswordSessionData>> {
+    let Some(data) = load(auth_store, session_id).await? else {
+        return Ok(None);
+    };
+    if !data.needs_refresh(within_secs) {
+        return Ok(Some(data));
+    }
+    refresh_with_lock(state, auth_store, session_id, within_secs)
+        .await
+        .map(Some)
+}
+
+/// Log out: revoke the refresh JWT at the PDS (best effort) and drop the record.
+///
+/// Returns `false` when `session_id` is not an app-password session.
+pub async fn delete_session(
+    state: &Arc<AppState>,
+    auth_store: &RedisAuthStore,
+    session_id: &str,
+) -> AppResult<bool> {
+    let Some(data) = load(auth_store, session_id).await? else {
+        return Ok(false);
+    };
+
+    let url = format!(
+        "{}/xrpc/com.atproto.server.deleteSession",
+        data.pds_url.trim_end_matches('/')
+    );
+    match state
+        .http_client
+        .post(url)
+        .bearer_auth(&data.refresh_jwt)
+        .send()
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => {}
+        Ok(resp) => {
+            tracing::warn!(status = %resp.status(), "App-password deleteSession was rejected");
+        }
+        Err(e) => tracing::warn!("App-password deleteSession failed: {}", e),
+    }
+
+    auth_store
+        .delete_app_password_session(session_id)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to delete app-password session: {e}")))?;
+    Ok(true)
+}
+
+async fn load(
+    auth_store: &RedisAuthStore,
+    session_id: &str,
+) -> AppResult<Option<AppPasswordSessionData>> {
+    auth_store
+        .get_app_password_session(session_id)
+        .await
+        .map_err(|e| {
+            AppError::AuthTemporarilyUnavailable(format!("App-password session lookup failed: {e}"))
+        })
+}
+
+/// Refresh under the per-session Redis lock. Whoever loses the race waits for
+/// the winner's write and uses it, rather than spending the (single-use)
+/// refresh JWT again.
+async fn refresh_with_lock(
+    state: &Arc<AppState>,
+    auth_store: &RedisAuthStore,
+    session_id: &str,
+    skew_seconds: i64,
+) -> AppResult<AppPasswordSessionData> {
+    let lock_token = uuid::Uuid::new_v4().to_string();
+
+    for _ in 0..REFRESH_WAIT_ATTEMPTS {
+        let acquired = auth_store
+            .try_lock_app_password_refresh(session_id, &lock_token, REFRESH_LOCK_TTL_SECONDS)
+            .await?;
+
+        if acquired {
+            // Re-read under the lock: another holder may have just refreshed.
+            let result = match load(auth_store, session_id).await? {
+                None => Err(AppError::InvalidSession),
+                Some(current) if current.needs_refresh(skew_seconds) => {
+                    refresh(state, auth_store, session_id, current).await
+                }
+                Some(current) => Ok(current),
+            };
+            if let Err(e) = auth_store
+                .unlock_app_password_refresh(session_id, &lock_token)
+                .await
+            {
+                tracing::warn!("Failed to release app-password refresh lock: {}", e);
+            }
+            return result;
+        }
+
+        tokio::time::sleep(REFRESH_WAIT_INTERVAL).await;
+        match load(auth_store, session_id).await? {
+            None => return Err(AppError::InvalidSession),
+            Some(current) if !current.needs_refresh(skew_seconds) => return Ok(current),
+            Some(_) => {}
+        }
+    }
+
+    Err(AppError::AuthTemporarilyUnavailable(
+        "Timed out waiting for a concurrent session refresh".into(),
+    ))
+}
+
+async fn refresh(
+    state: &Arc<AppState>,
+    auth_store: &RedisAuthStore,
+    session_id: &str,
+    current: AppPasswordSessionData,
+) -> AppResult<AppPasswordSessionData> {
+    let url = format!(
+        "{}/xrpc/com.atproto.server.refreshSession",
+        current.pds_url.trim_end_matches('/')
+    );
+    let response = match state
+        .http_client
+        .post(url)
+        .bearer_auth(&current.refresh_jwt)
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            metrics::record_token_refresh(false);
+            return Err(AppError::AuthTemporarilyUnavailable(format!(
+                "refreshSession request failed: {e}"
+            )));
+        }
+    };
+    let status = response.status();
+    let bytes = response.bytes().await.map_err(|e| {
+        metrics::record_token_refresh(false);
+        AppError::AuthTemporarilyUnavailable(format!("refreshSession body read failed: {e}"))
+    })?;
+
+    if !status.is_success() {
+        metrics::record_token_refresh(false);
+        let (error, message) = xrpc_error_parts(&bytes);
+        let permanent = is_permanent_refresh_failure(status, &error);
+        if permanent {
+            tracing::info!(
+                did = %current.did,
+                error = %error,
+                "App-password refresh token rejected; dropping session"
+            );
+            if let Err(e) = auth_store.delete_app_password_session(session_id).await {
+                tracing::warn!("Failed to delete dead app-password session: {}", e);
+            }
+        }
+        return Err(AppError::OAuth(OAuthFailure::refresh_rejected(
+            status.as_u16(),
+            &error,
+            &message,
+            permanent,
+        )));
+    }
+
+    let output: ServerSessionOutput = serde_json::from_slice(&bytes).map_err(|e| {
+        metrics::record_token_refresh(false);
+        AppError::AuthTemporarilyUnavailable(format!("Invalid refreshSession response: {e}"))
+    })?;
+
+    // Follow a PDS migration only once the DID's own document agrees with
+    // the `didDoc` the PDS sent back.
+    let pds_url = match output.did_doc.as_ref().and_then(pds_endpoint_from_did_doc) {
+        Some(claimed) if claimed != current.pds_url => {
+            match resolve_did_pds(state, &current.did).await {
+                Ok(resolved) if resolved == claimed => resolved,
+                _ => current.pds_url,
+            }
+        }
+        _ => current.pds_url,
+    };
+
+    let refreshed = AppPasswordSessionData {
+        access_expires_at: access_expiry(&output.access_jwt, Utc::now()),
+        did: current.did,
+        handle: output.handle,
+        pds_url,
+        access_jwt: output.access_jwt,
+        refresh_jwt: output.refresh_jwt,
+        created_at: current.created_at,
+    };
+
+    auth_store
+        .put_app_password_session(session_id, &refreshed)
+        .await
+        .map_err(|e| {
+            // The old refresh JWT is already spent; surface this loudly.
+            tracing::error!(did = %refreshed.did, "Failed to persist refreshed app-password session: {}", e);
+            AppError::AuthTemporarilyUnavailable(format!("Failed to persist refreshed session: {e}"))
+        })?;
+
+    metrics::record_token_refresh(true);
+    Ok(refreshed)
+}
+
+/// Find the PDS for a handle or DID: handle → DID via the configured
+/// resolver, then DID → `#atproto_pds` service endpoint.
+async fn resolve_pds_url(state: &Arc<AppState>, identifier: &str) -> AppResult<String> {
+    let config = &state.config.app_password;
+    let identifier = identifier.trim_start_matches('@');
+
+    let did = if identifier.starts_with("did:") {
+        identifier.to_string()
+    } else if identifier.contains('@') {
+        return Err(AppError::BadRequest("Email login requires pds_url".into()));
+    } else {
+        let url = format!(
+            "{}/xrpc/com.atproto.identity.resolveHandle?handle={}",
+            config.resolver_url.trim_end_matches('/'),
+            urlencoding::encode(identifier)
+        );
+        let resp = state.http_client.get(url).send().await?;
+        if !resp.status().is_success() {
+            return Err(AppError::BadRequest(format!(
+                "Unable to resolve handle {identifier}"
+            )));
+        }
+        let json: Value = resp.json().await?;
+        json.get("did")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| AppError::BadRequest(format!("Unable to resolve handle {identifier}")))?
+    };
+
+    resolve_did_pds(state, &did).await
+}
+
+/// The `#atproto_pds` endpoint from the DID's own document (PLC directory
+/// or did:web), checked like any other PDS URL.
+async fn resolve_did_pds(state: &Arc<AppState>, did: &str) -> AppResult<String> {
+    let config = &state.config.app_password;
+    let doc_url = if let Some(rest) = did.strip_prefix("did:web:") {
+        // The host comes from the caller, so it gets the same SSRF check
+        // as a PDS URL before anything is fetched from it.
+        let doc_url = format!("https://{}/.well-known/did.json", rest.replace(':', "/"));
+        validate_pds_url(&doc_url)?;
+        doc_url
+    } else if did.starts_with("did:plc:") {
+        format!("{}/{}", config.plc_directory_url.trim_end_matches('/'), did)
+    } else {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported DID method: {did}"
+        )));
+    };
+
+    let resp = state.http_client.get(doc_url).send().await?;
+    if !resp.status().is_success() {
+        return Err(AppError::BadRequest(format!("Unable to resolve DID {did}")));
+    }
+    let doc: Value = resp.json().await?;
+    let pds_url = pds_endpoint_from_did_doc(&doc)
+        .ok_or_else(|| AppError::BadRequest(format!("DID {did} has no PDS endpoint")))?;
+    validate_pds_url(&pds_url)?;
+    Ok(pds_url)
+}
+
+/// Checks that `pds_url` accepts `access_jwt` as `did`'s session.
+async fn verify_session_at(
+    state: &Arc<AppState>,
+    pds_url: &str,
+    access_jwt: &str,
+    did: &str,
+) -> AppResult<()> {
+    let response = state
+        .http_client
+        .get(format!("{pds_url}/xrpc/com.atproto.server.getSession"))
+        .bearer_auth(access_jwt)
+        .send()
+        .await?;
+    let hosted_did = if response.status().is_success() {
+        let json: Value = response.json().await?;
+        json.get("did").and_then(Value::as_str).map(str::to_string)
+    } else {
+        None
+    };
+    if hosted_did.as_deref() != Some(did) {
+        tracing::warn!(did = %did, pds = %pds_url, "App-password login PDS does not host the DID");
+        return Err(AppError::Unauthorized(
+            "The login server is not this account's PDS".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Extract the `#atproto_pds` service endpoint from a DID document.
+fn pds_endpoint_from_did_doc(doc: &Value) -> Option<String> {
+    doc.get("service")?
+        .as_array()?
+        .iter()
+        .find(|service| {
+            service
+                .get("id")
+                .and_then(Value::as_str)
+                .is_some_and(|id| id == "#atproto_pds" || id.ends_with("#atproto_pds"))
+        })?
+        .get("serviceEndpoint")?
+        .as_str()
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Read the unverified `exp` claim from a JWT. The PDS is the only party that
+/// needs to trust the token; Nest only uses this to schedule refreshes.
+fn jwt_expiry(jwt: &str) -> Option<DateTime<Utc>> {
+    let payload = jwt.split('.').nth(1)?;
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: Value = serde_json::from_slice(&bytes).ok()?;
+    DateTime::from_timestamp(claims.get("exp")?.as_i64()?, 0)
+}
+
+fn access_expiry(jwt: &str, now: DateTime<Utc>) -> DateTime<Utc> {
+    jwt_expiry(jwt).unwrap_or(now + chrono::Duration::seconds(DEFAULT_ACCESS_LIFETIME_SECONDS))
+}
+
+fn xrpc_error_parts(body: &[u8]) -> (String, String) {
+    let json: Option<Value> = serde_json::from_slice(body).ok();
+    let field = |name: &str| {
+        json.as_ref()
+            .and_then(|v| v.get(name))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    (
+        field("error").unwrap_or_else(|| "UpstreamFailure".to_string()),
+        field("message").unwrap_or_default(),
+    )
+}
+
+fn is_permanent_refresh_failure(status: StatusCode, error: &str) -> bool {
+    (status == StatusCode::BAD_REQUEST || status == StatusCode::UNAUTHORIZED)
+        && PERMANENT_REFRESH_ERRORS.contains(&error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt_with_exp(exp: i64) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.sig",
+            engine.encode(br#"{"alg":"ES256K","typ":"at+jwt"}"#),
+            engine.encode(serde_json::json!({ "sub": "did:plc:alice", "exp": exp }).to_string())
+        )
+    }
+
+    #[test]
+    fn reads_exp_from_access_jwt() {
+        let exp = 1_900_000_000;
+        assert_eq!(
+            jwt_expiry(&jwt_with_exp(exp)).map(|dt| dt.timestamp()),
+            Some(exp)
+        );
+        assert_eq!(jwt_expiry("not-a-jwt"), None);
+    }
+
+    #[test]
+    fn unreadable_exp_falls_back_to_default_lifetime() {
+        let now = Utc::now();
+        assert_eq!(
+            access_expiry("garbage", now),
+            now + chrono::Duration::seconds(DEFAULT_ACCESS_LIFETIME_SECONDS)
+        );
+    }
+
+    #[test]
+    fn finds_pds_endpoint_in_did_doc() {
+        let doc = serde_json::json!({
+            "id": "did:plc:alice",
+            "service": [
+                { "id": "#atproto_labeler", "serviceEndpoint": "https://labeler.example" },
+                { "id": "#atproto_pds", "type": "AtprotoPersonalDataServer", "serviceEndpoint": "https://pds.example/" }
+            ]
+        });
+        assert_eq!(
+            pds_endpoint_from_did_doc(&doc),
+            Some("https://pds.example".to_string())
+        );
+        assert_eq!(pds_endpoint_from_did_doc(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn only_dead_credentials_are_permanent() {
+        assert!(is_permanent_refresh_failure(
+            StatusCode::BAD_REQUEST,
+            "ExpiredToken"
+        ));
+        assert!(is_permanent_refresh_failure(
+            StatusCode::UNAUTHORIZED,
+            "InvalidToken"
+        ));
+        assert!(!is_permanent_refresh_failure(
+            StatusCode::BAD_GATEWAY,
+            "ExpiredToken"
+        ));
+        assert!(!is_permanent_refresh_failure(
+            StatusCode::BAD_REQUEST,
+            "RateLimitExceeded"
+        ));
+    }
+
+    #[test]
+    fn needs_refresh_respects_skew() {
+        let now = Utc::now();
+        let data = AppPasswordSessionData {
+            did: "did:plc:alice".into(),
+            handle: "alice.test".into(),
+            pds_url: "https://pds.example".into(),
+            access_jwt: String::new(),
+            refresh_jwt: String::new(),
+            access_expires_at: now + chrono::Duration::seconds(120),
+            created_at: now,
+        };
+        assert!(!data.needs_refresh(60));
+        assert!(data.needs_refresh(300));
+        assert_eq!(
+            data.to_session("123e4567-e89b-12d3-a456-426614174000")
+                .unwrap()
+                .auth_kind,
+            SessionAuthKind::AppPassword
+        );
+        assert!(matches!(
+            data.to_session("not-a-uuid"),
+            Err(AppError::InvalidSession)
+        ));
+    }
+}
@@ -3,11 +3,12 @@
 //! Handles communication with ATProto PDS servers, including:
 //! - Request proxying with DPoP nonce retry
 //! - DPoP proof generation via Jacquard
+//! - Bearer auth for app-password sessions
 
 use super::ssrf::validate_pds_url;
 use crate::config::AppState;
 use crate::error::{AppError, AppResult};
-use crate::models::CatbirdSession;
+use crate::models::{CatbirdSession, SessionAuthKind};
 use futures_util::StreamExt;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde_json::Value;
@@ -593,6 +594,18 @@ impl AtProtoClient {
     ) -> AppResult<HeaderMap> {
         let mut headers = HeaderMap::new();
 
+        // App-password sessions hold a createSession access JWT, which the
+        // PDS accepts only as a plain Bearer token (no DPoP binding).
+        if session.auth_kind == SessionAuthKind::AppPassword {
+            let auth_value = format!("Bearer {}", session.access_token);
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&auth_value)
+                    .map_err(|e| AppError::Internal(e.to_string()))?,
+            );
+            return Ok(headers);
+        }
+
         let dpop_data = jacquard_dpop
             .ok_or_else(|| AppError::Internal("Missing Jacquard DPoP data for request".into()))?;
 
@@ -1,7 +1,7 @@
 //! Chat poller — polls `chat.bsky.convo.getLog` for each claimed account,
 //! processes new-message events, and enqueues push notifications.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -11,7 +11,9 @@ use serde_json::Value;
 use sqlx::{Pool, Postgres};
 
 use crate::config::AppState;
-use crate::services::push::{is_auth_revocation_error, resolve_background_session};
+use crate::services::push::{
+    chat_preview, is_auth_revocation_error, resolve_background_session, types::RegistrationRow,
+};
 
 use super::rate_budget::PdsRateBudget;
 use super::scheduler::ChatPollScheduler;
@@ -258,16 +260,40 @@ pub async fn poll_account(
     // shows was already read.
     let mut dirty: HashMap<String, String> = batch_read_maxima(&log_response.logs);
 
+    // Reads also clear those convos' badge counts, before pass 2 counts
+    // anything newer than them. A convo that had unread messages had
+    // notifications shown, which the other devices should now drop.
+    let mut retractions: Vec<(String, Option<String>)> = Vec::new();
+    if let Some(push) = state.push.as_ref() {
+        for convo_id in dirty.keys() {
+            match push
+                .badges
+                .reset_chat(&row.account_did, Some(convo_id))
+                .await
+            {
+                Ok(cleared) => {
+                    retractions.extend(cleared.into_iter().map(|convo_id| (convo_id, None)))
+                }
+                Err(err) => {
+                    tracing::warn!(did = %row.account_did, error = %err, "Failed to reset chat badge count");
+                }
+            }
+        }
+    }
+
     // Pass 2: evaluate + notify CreateMessage entries against the
     // read-seeded watermark.
+    let deleted = batch_deleted_ids(&log_response.logs);
+    let mut preview_devices: Option<Vec<RegistrationRow>> = None;
     for entry in &log_response.logs {
         if let LogEntry::CreateMessage(event) = entry {
             let wm = effective_watermark(&watermarks, &dirty, &event.convo_id);
             if !should_notify(&event.rev, wm.as_deref()) {
                 continue;
             }
-            // Own messages and muted convos advance the watermark
-            // without notifying (unmuting must not replay history).
+            // Own messages, muted convos, and messages the same batch
+            // deletes advance the watermark without notifying (unmuting
+            // must not replay history).
             let own = event.message.sender.did == row.account_did;
             let muted = !own
                 && scheduler
@@ -278,27 +304,57 @@ pub async fn poll_account(
                 had_incoming_message = true;
             }
 
-            if !own && !muted {
+            if !own && !muted && !deleted.contains(event.message.id.as_str()) {
+                let message_text: String = event
+                    .message
+                    .text
+                    .clone()
+                    .unwrap_or_default()
+                    .chars()
+                    .take(300)
+                    .collect();
+                if preview_devices.is_none() {
+                    preview_devices = Some(load_preview_devices(state, &row.account_did).await);
+                }
+                let devices = preview_devices.as_deref().unwrap_or_default();
+                let encrypted_previews =
+                    chat_preview::encrypt_for_devices(devices, &event.message.id, &message_text);
+                let legacy_previews = state.config.push.legacy_chat_previews
+                    && !message_text.is_empty()
+                    && devices.iter().any(|device| device.preview_key.is_none());
+                if legacy_previews {
+                    if let Err(err) = chat_preview::stash_legacy_preview(
+                        &state.redis,
+                        &row.account_did,
+                        &event.message.id,
+                        &message_text,
+                    )
+                    .await
+                    {
+                        tracing::debug!(did = %row.account_did, error = %err, "Failed to hold chat preview for keyless devices");
+                    }
+                }
                 let push_event = ChatPushEvent {
                     recipient_did: row.account_did.clone(),
                     sender_did: event.message.sender.did.clone(),
                     convo_id: event.convo_id.clone(),
                     message_id: event.message.id.clone(),
-                    message_text: event
-                        .message
-                        .text
-                        .clone()
-                        .unwrap_or_default()
-                        .chars()
-                        .take(300)
-                        .collect(),
+                    message_text,
                     sent_at: event.message.sent_at.clone(),
+                    encrypted_previews,
                 };
-                if let Err(err) = enqueue_push(db_pool, &push_event, 15).await {
-                    tracing::warn!(did = %row.account_did, error = %err, "Failed to enqueue chat push");
+                // Counted before enqueueing so the push carries it.
+                if let Some(push) = state.push.as_ref() {
+                    if let Err(err) = push
+                        .badges
+                        .increment_chat(&row.account_did, &event.convo_id)
+                        .await
+                    {
+                        tracing::warn!(did = %row.account_did, error = %err, "Failed to count chat message toward badge");
+                    }
                 }
-                if let Err(err) = publish_to_redis(state, &push_event).await {
-                    tracing::debug!(did = %row.account_did, error = %err, "Redis publish failed (durable path covers it)");
+                if let Err(err) = enqueue_push(db_pool, &push_event).await {
+                    tracing::warn!(did = %row.account_did, error = %err, "Failed to enqueue chat push");
                 }
             }
 
@@ -306,6 +362,36 @@ pub async fn poll_account(
         }
     }
 
+    // Pass 3: retract notifications for messages deleted while still
+    // unread. One created in this batch was never notified (pass 2 skipped
+    // it); one already read was retracted with its convo.
+    if let Some(push) = state.push.as_ref() {
+        let created = batch_created_ids(&log_response.logs);
+        for entry in &log_response.logs {
+            let LogEntry::DeleteMessage(event) = entry else {
+                continue;
+            };
+            if event.message.sender.did == row.account_did
+                || created.contains(event.message.id.as_str())
+            {
+                continue;
+            }
+            match push
+                .badges
+                .decrement_chat(&row.account_did, &event.convo_id)
+                .await
+            {
+                Ok(true) => {
+                    retractions.push((event.convo_id.clone(), Some(event.message.id.clone())))
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    tracing::warn!(did = %row.account_did, error = %err, "Failed to uncount deleted chat message");
+                }
+            }
+        }
+    }
+
     // Flush watermarks BEFORE the cursor: if we crash between the two, the
     // next poll re-reads the same logs and the watermarks suppress them.
     // (Cursor-first would re-notify on the inverse crash.)
@@ -324,6 +410,18 @@ pub async fn poll_account(
         )
         .await?;
 
+    if let Some(push) = state.push.as_ref() {
+        for (convo_id, message_id) in &retractions {
+            push.retract_chat_notifications(
+                state,
+                &row.account_did,
+                convo_id,
+                message_id.as_deref(),
+            )
+            .await;
+        }
+    }
+
     Ok(())
 }
 
@@ -562,6 +660,26 @@ fn batch_read_maxima(logs: &[LogEntry]) -> HashMap<String, String> {
     maxima
 }
 
+/// Ids of messages a single getLog page deletes.
+fn batch_deleted_ids(logs: &[LogEntry]) -> HashSet<&str> {
+    logs.iter()
+        .filter_map(|entry| match entry {
+            LogEntry::DeleteMessage(event) => Some(event.message.id.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Ids of messages a single getLog page creates.
+fn batch_created_ids(logs: &[LogEntry]) -> HashSet<&str> {
+    logs.iter()
+        .filter_map(|entry| match entry {
+            LogEntry::CreateMessage(event) => Some(event.message.id.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
 /// Look up session_id and pds_url from push_accounts for a given DID.
 async fn lookup_push_account(db_pool: &Pool<Postgres>, did: &str) -> Result<(String, String)> {
     let row = sqlx::query_as::<_, (String, String)>(
@@ -575,21 +693,31 @@ async fn lookup_push_account(db_pool: &Pool<Postgres>, did: &str) -> Result<(Str
     Ok(row)
 }
 
-/// Insert a chat push event into the push_event_queue. `delay_secs` sets how
-/// long the row is invisible to the durable worker (`available_at`): new chat
-/// enqueues pass the durable grace window (15s) so the fast-path subscriber
-/// gets first crack at claiming and delivering; requeues after a fast-path
-/// delivery failure pass 0 so the durable worker can pick it up immediately.
+/// The account's active devices, for chat previews: encrypted for those
+/// with a preview key, held in plaintext for the rest. Loaded at most once
+/// per poll and only when a message is about to be notified.
+async fn load_preview_devices(state: &Arc<AppState>, account_did: &str) -> Vec<RegistrationRow> {
+    let Some(push) = state.push.as_ref() else {
+        return Vec::new();
+    };
+    match push.registry.list_active_registrations(account_did).await {
+        Ok(registrations) => registrations,
+        Err(err) => {
+            tracing::warn!(did = %account_did, error = %err, "Failed to load devices for chat previews");
+            Vec::new()
+        }
+    }
+}
+
+/// Insert a chat push event into the push_event_queue. The insert trigger
+/// wakes the push worker, so chat goes out as quickly as any other type.
 ///
-/// `message_text` is deliberately dropped before persisting — the queue row
-/// only needs to survive long enough to build a generic notification. The
-/// fast (Redis pub/sub) path carries the real text for the one immediate
-/// delivery attempt and never writes it to disk; see `publish_to_redis`.
-pub(crate) async fn enqueue_push(
-    db_pool: &Pool<Postgres>,
-    event: &ChatPushEvent,
-    delay_secs: i64,
-) -> Result<()> {
+/// `message_text` is deliberately dropped before persisting, so message text
+/// never touches disk. Devices with a preview key still get one from
+/// `encrypted_previews`, which only they can decrypt; it survives retries
+/// like the rest of the row. Keyless devices get the short-lived copy held
+/// in Redis (`chat_preview::stash_legacy_preview`).
+pub(crate) async fn enqueue_push(db_pool: &Pool<Postgres>, event: &ChatPushEvent) -> Result<()> {
     let dedupe_key = event.dedupe_key();
     let mut persisted_event = event.clone();
     persisted_event.message_text.clear();
@@ -601,10 +729,9 @@ pub(crate) async fn enqueue_push(
         INSERT INTO push_event_queue (
             recipient_did, actor_did, notification_type,
             event_cid, event_path, event_record_json,
-            event_timestamp, dedupe_key, available_at
+            event_timestamp, dedupe_key
         )
-        VALUES ($1, $2, 'chat_message', $3, 'chat.bsky.convo.getLog', $4, $5, $6,
-                NOW() + make_interval(secs => $7))
+        VALUES ($1, $2, 'chat_message', $3, 'chat.bsky.convo.getLog', $4, $5, $6)
         ON CONFLICT (dedupe_key) DO NOTHING
         "#,
     )
@@ -614,25 +741,12 @@ pub(crate) async fn enqueue_push(
     .bind(&event_json)
     .bind(now_epoch)
     .bind(&dedupe_key)
-    .bind(delay_secs)
     .execute(db_pool)
     .await?;
 
     Ok(())
 }
 
-/// Publish a chat push event to Redis pub/sub for real-time connected clients.
-async fn publish_to_redis(state: &Arc<AppState>, event: &ChatPushEvent) -> Result<()> {
-    let payload = serde_json::to_string(event)?;
-    let mut conn = state.redis.clone();
-    let _: () = redis::cmd("PUBLISH")
-        .arg("chat_push")
-        .arg(&payload)
-        .query_async(&mut conn)
-        .await?;
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::super::types::{LogMessage, LogMessageEvent, LogSender};
@@ -708,4 +822,20 @@ mod tests {
         let wm = effective_watermark(&persisted, &dirty, "convo1");
         assert!(!should_notify("3laaa", wm.as_deref()));
     }
+
+    #[test]
+    fn batch_ids_separate_created_and_deleted_messages() {
+        let logs = vec![
+            LogEntry::CreateMessage(log_message_event("convo1", "3laaa", "did:plc:other", "m1")),
+            LogEntry::DeleteMessage(log_message_event("convo1", "3lbbb", "did:plc:other", "m1")),
+            LogEntry::DeleteMessage(log_message_event("convo1", "3lccc", "did:plc:other", "m0")),
+            LogEntry::ReadMessage(log_message_event("convo1", "3lddd", "did:plc:self", "r1")),
+        ];
+
+        // m1 is never notified; m0 predates the batch and may have been.
+        let deleted = batch_deleted_ids(&logs);
+        let created = batch_created_ids(&logs);
+        assert_eq!(deleted, HashSet::from(["m1", "m0"]));
+        assert_eq!(created, HashSet::from(["m1"]));
+    }
 }
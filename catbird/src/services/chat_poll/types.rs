@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
 use sqlx::FromRow;
 use time::OffsetDateTime;
@@ -76,7 +78,7 @@ pub struct ConvoView {
     pub muted: bool,
 }
 
-/// Push event passed from poller to queue + Redis
+/// Push event passed from poller to queue
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatPushEvent {
@@ -86,11 +88,14 @@ pub struct ChatPushEvent {
     pub message_id: String,
     pub message_text: String,
     pub sent_at: String,
+    /// `message_text` encrypted to each device's preview key, by
+    /// registration ID (see `push::chat_preview`).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub encrypted_previews: HashMap<String, String>,
 }
 
 impl ChatPushEvent {
-    /// Queue dedupe key — MUST stay identical between enqueue (poller) and
-    /// fast-path claim (push subscriber), or dedup silently breaks.
+    /// Queue dedupe key; a message polled twice is only queued once.
     pub fn dedupe_key(&self) -> String {
         format!(
             "{}:chat_message:{}:{}",
@@ -0,0 +1,441 @@
+//! Persisted OAuth login attempts
+//!
+//! One record per OAuth `state` holds everything the callback needs (client
+//! selector, flow mode, redirect target, exchange nonce, device user code)
+//! together with how far the login got. It replaces the separate
+//! `oauth_mode` / `oauth_client` / `oauth_redirect` / `oauth_nonce` /
+//! `oauth_device` keys, which each expired on their own and vanished on
+//! callback, so an abandoned or failed login left nothing to look at.
+//!
+//! Records are sealed with `SESSION_ENCRYPTION_KEY`. Without the key (only
+//! redirect logins run then; exchange and device logins require it) a record
+//! keeps just the flow fields the callback needs and the funnel progress, in
+//! the clear like the per-field keys it replaced; the identifier and DID are
+//! left out. Records are kept for
+//! `LOGIN_ATTEMPT_TTL_SECONDS` after the last update, well past the 10-minute
+//! window in which a callback can still succeed. Attempts are also indexed by
+//! subject (the identifier typed at login, and the DID once known) so an
+//! operator can answer "I can't log in" from the admin port.
+//!
+//! Redis keys:
+//!   `login_attempt:{state}`                          → LoginAttempt JSON (sealed)
+//!   `login_attempt_callback:{state}`                 → claim taken by the first callback
+//!   `login_attempts_by_subject:{sha256(subject)}`    → ZSET state scored by start time
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::redis_crypto::{open, seal};
+use crate::config::AppState;
+use crate::error::{AppError, AppResult};
+use crate::metrics;
+
+const LOGIN_ATTEMPT_TTL_SECONDS: u64 = 86400; // 24 hours
+/// Attempts kept per subject in the lookup index.
+const SUBJECT_INDEX_LIMIT: isize = 20;
+
+/// How the session is handed back once OAuth completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginMode {
+    /// Session ID in a redirect (iOS, catmos-web without a nonce).
+    Redirect,
+    /// ADR-014 exchange code bound to `browser_nonce`.
+    Exchange,
+    /// Device-code approval; the session goes to the polling device.
+    Device,
+}
+
+/// Progression of a login. `Failed` is terminal; the stage reached before
+/// failing is kept in `LoginFailure::stage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginStage {
+    Started,
+    ParOk,
+    Redirected,
+    CallbackReceived,
+    Exchanged,
+    Failed,
+}
+
+impl LoginStage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoginStage::Started => "started",
+            LoginStage::ParOk => "par_ok",
+            LoginStage::Redirected => "redirected",
+            LoginStage::CallbackReceived => "callback_received",
+            LoginStage::Exchanged => "exchanged",
+            LoginStage::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginEvent {
+    pub stage: LoginStage,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginFailure {
+    /// Last stage reached before the failure.
+    pub stage: LoginStage,
+    pub reason: String,
+    /// OAuth error code from the provider or token endpoint, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginAttempt {
+    pub state: String,
+    /// OAuth client selector: `default` or `catmos`.
+    pub client: String,
+    pub mode: LoginMode,
+    pub identifier: String,
+    #[serde(default)]
+    pub redirect_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser_nonce: Option<String>,
+    #[serde(default)]
+    pub user_code: Option<String>,
+    pub stage: LoginStage,
+    #[serde(default)]
+    pub events: Vec<LoginEvent>,
+    #[serde(default)]
+    pub did: Option<String>,
+    #[serde(default)]
+    pub failure: Option<LoginFailure>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl LoginAttempt {
+    pub fn start(
+        state: &str,
+        client: &str,
+        mode: LoginMode,
+        identifier: &str,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            state: state.to_string(),
+            client: client.to_string(),
+            mode,
+            identifier: identifier.to_string(),
+            redirect_to: None,
+            browser_nonce: None,
+            user_code: None,
+            stage: LoginStage::Started,
+            events: vec![LoginEvent {
+                stage: LoginStage::Started,
+                at: now,
+            }],
+            did: None,
+            failure: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Move to `stage`. Stages only move forward, and nothing follows
+    /// `Failed`; returns whether the attempt changed.
+    pub fn advance(&mut self, stage: LoginStage, now: DateTime<Utc>) -> bool {
+        if self.stage == LoginStage::Failed || stage <= self.stage {
+            return false;
+        }
+        self.stage = stage;
+        self.events.push(LoginEvent { stage, at: now });
+        self.updated_at = now;
+        true
+    }
+
+    /// Mark the attempt failed at its current stage. Returns whether the
+    /// attempt changed (a failed attempt keeps its first failure).
+    pub fn fail(&mut self, reason: &str, error_code: Option<&str>, now: DateTime<Utc>) -> bool {
+        if self.stage == LoginStage::Failed {
+            return false;
+        }
+        self.failure = Some(LoginFailure {
+            stage: self.stage,
+            reason: reason.to_string(),
+            error_code: error_code.map(str::to_string),
+        });
+        self.advance(LoginStage::Failed, now)
+    }
+
+    /// Copy for display on the admin port: the exchange nonce is a secret.
+    pub fn redacted(mut self) -> Self {
+        self.browser_nonce = None;
+        self
+    }
+
+    /// What is stored when there is no key to seal with: the flow fields
+    /// and progress, without secrets or anything naming the account.
+    fn unsealed(mut self) -> Self {
+        self.browser_nonce = None;
+        self.user_code = None;
+        self.identifier = String::new();
+        self.did = None;
+        self
+    }
+}
+
+fn attempt_key(state: &str) -> String {
+    format!("login_attempt:{}", state)
+}
+
+fn callback_claim_key(state: &str) -> String {
+    format!("login_attempt_callback:{}", state)
+}
+
+fn subject_key(subject: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(subject.trim().to_ascii_lowercase().as_bytes());
+    format!("login_attempts_by_subject:{:x}", digest)
+}
+
+pub struct LoginAttemptStore {
+    redis: redis::aio::ConnectionManager,
+    encryption_key: Option<[u8; 32]>,
+}
+
+impl LoginAttemptStore {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            redis: state.redis.clone(),
+            encryption_key: state.session_encryption_key,
+        }
+    }
+
+    /// Persist a new attempt and index it under its identifier.
+    pub async fn create(&self, attempt: &LoginAttempt) -> AppResult<()> {
+        self.write(attempt).await?;
+        self.index(&attempt.identifier, attempt).await?;
+        metrics::record_login_stage(&attempt.client, LoginStage::Started.as_str());
+        Ok(())
+    }
+
+    pub async fn get(&self, state: &str) -> AppResult<Option<LoginAttempt>> {
+        let mut conn = self.redis.clone();
+        let raw: Option<String> = redis::cmd("GET")
+            .arg(attempt_key(state))
+            .query_async(&mut conn)
+            .await?;
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+
+        let json = match self.encryption_key.as_ref() {
+            Some(key) => {
+                let plaintext = open(key, &raw)
+                    .map_err(|e| AppError::Crypto(format!("Failed to open login attempt: {e}")))?;
+                String::from_utf8(plaintext)
+                    .map_err(|_| AppError::Crypto("Login attempt is not valid UTF-8".into()))?
+            }
+            None => raw,
+        };
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    /// Claim the callback for `state`. Only the first caller gets `true`, so
+    /// two callbacks racing on one `state` can't both complete the login.
+    pub async fn claim_callback(&self, state: &str) -> AppResult<bool> {
+        let mut conn = self.redis.clone();
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(callback_claim_key(state))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(LOGIN_ATTEMPT_TTL_SECONDS)
+            .query_async(&mut conn)
+            .await?;
+        Ok(claimed.is_some())
+    }
+
+    pub async fn advance(&self, state: &str, stage: LoginStage) -> AppResult<Option<LoginAttempt>> {
+        self.update(state, |attempt, now| attempt.advance(stage, now))
+            .await
+    }
+
+    pub async fn fail(
+        &self,
+        state: &str,
+        reason: &str,
+        error_code: Option<&str>,
+    ) -> AppResult<Option<LoginAttempt>> {
+        self.update(state, |attempt, now| attempt.fail(reason, error_code, now))
+            .await
+    }
+
+    /// Record the account once the code exchange has produced a session,
+    /// and index the attempt under its DID.
+    pub async fn exchanged(&self, state: &str, did: &str) -> AppResult<Option<LoginAttempt>> {
+        let attempt = self
+            .update(state, |attempt, now| {
+                attempt.did = Some(did.to_string());
+                attempt.advance(LoginStage::Exchanged, now)
+            })
+            .await?;
+        if let Some(ref attempt) = attempt {
+            self.index(did, attempt).await?;
+        }
+        Ok(attempt)
+    }
+
+    /// Most recent attempts for a DID or login identifier, newest first.
+    pub async fn find_by_subject(&self, subject: &str) -> AppResult<Vec<LoginAttempt>> {
+        let mut conn = self.redis.clone();
+        let states: Vec<String> = redis::cmd("ZREVRANGE")
+            .arg(subject_key(subject))
+            .arg(0)
+            .arg(SUBJECT_INDEX_LIMIT - 1)
+            .query_async(&mut conn)
+            .await?;
+
+        let mut attempts = Vec::with_capacity(states.len());
+        for state in states {
+            // Index entries outlive records that expired on their own.
+            if let Some(attempt) = self.get(&state).await? {
+                attempts.push(attempt);
+            }
+        }
+        Ok(attempts)
+    }
+
+    /// Read-modify-write. A login's stages are driven by one browser in
+    /// sequence, so last-writer-wins is sufficient here.
+    async fn update(
+        &self,
+        state: &str,
+        apply: impl FnOnce(&mut LoginAttempt, DateTime<Utc>) -> bool,
+    ) -> AppResult<Option<LoginAttempt>> {
+        let Some(mut attempt) = self.get(state).await? else {
+            return Ok(None);
+        };
+        if apply(&mut attempt, Utc::now()) {
+            self.write(&attempt).await?;
+            match attempt.failure.as_ref() {
+                Some(failure) if attempt.stage == LoginStage::Failed => {
+                    metrics::record_login_failure(&attempt.client, failure.stage.as_str());
+                }
+                _ => metrics::record_login_stage(&attempt.client, attempt.stage.as_str()),
+            }
+        }
+        Ok(Some(attempt))
+    }
+
+    async fn write(&self, attempt: &LoginAttempt) -> AppResult<()> {
+        let value = match self.encryption_key.as_ref() {
+            Some(key) => seal(key, serde_json::to_string(attempt)?.as_bytes())
+                .map_err(|e| AppError::Crypto(format!("Failed to seal login attempt: {e}")))?,
+            None => serde_json::to_string(&attempt.clone().unsealed())?,
+        };
+        let mut conn = self.redis.clone();
+        redis::cmd("SET")
+            .arg(attempt_key(&attempt.state))
+            .arg(value)
+            .arg("EX")
+            .arg(LOGIN_ATTEMPT_TTL_SECONDS)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn index(&self, subject: &str, attempt: &LoginAttempt) -> AppResult<()> {
+        let key = subject_key(subject);
+        let mut conn = self.redis.clone();
+        redis::pipe()
+            .cmd("ZADD")
+            .arg(&key)
+            .arg(attempt.created_at.timestamp())
+            .arg(&attempt.state)
+            .ignore()
+            .cmd("ZREMRANGEBYRANK")
+            .arg(&key)
+            .arg(0)
+            .arg(-(SUBJECT_INDEX_LIMIT + 1))
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(LOGIN_ATTEMPT_TTL_SECONDS)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt() -> LoginAttempt {
+        LoginAttempt::start(
+            "state-1",
+            "default",
+            LoginMode::Redirect,
+            "alice.test",
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn stages_only_move_forward() {
+        let mut a = attempt();
+        let now = Utc::now();
+        assert!(a.advance(LoginStage::ParOk, now));
+        assert!(a.advance(LoginStage::Redirected, now));
+        assert!(!a.advance(LoginStage::ParOk, now));
+        assert_eq!(a.stage, LoginStage::Redirected);
+        assert_eq!(a.events.len(), 3);
+    }
+
+    #[test]
+    fn failure_keeps_the_stage_reached() {
+        let mut a = attempt();
+        let now = Utc::now();
+        a.advance(LoginStage::ParOk, now);
+        a.advance(LoginStage::Redirected, now);
+        assert!(a.fail("access_denied", Some("access_denied"), now));
+        assert_eq!(a.stage, LoginStage::Failed);
+        let failure = a.failure.as_ref().expect("failure");
+        assert_eq!(failure.stage, LoginStage::Redirected);
+
+        // Terminal: no further progress and the first failure wins.
+        assert!(!a.advance(LoginStage::Exchanged, now));
+        assert!(!a.fail("later", None, now));
+        assert_eq!(a.failure.unwrap().reason, "access_denied");
+    }
+
+    #[test]
+    fn subject_lookup_ignores_case() {
+        assert_eq!(subject_key("Alice.Test"), subject_key("alice.test "));
+        assert_ne!(subject_key("alice.test"), subject_key("bob.test"));
+    }
+
+    #[test]
+    fn unsealed_records_keep_only_the_flow() {
+        let mut a = attempt();
+        a.redirect_to = Some("https://catmos.example/done".into());
+        a.browser_nonce = Some("secret".into());
+        a.user_code = Some("ABCD-EFGH".into());
+        a.did = Some("did:plc:alice".into());
+        let stored = a.unsealed();
+        assert_eq!(stored.client, "default");
+        assert_eq!(stored.redirect_to.as_deref(), Some("https://catmos.example/done"));
+        assert!(stored.browser_nonce.is_none());
+        assert!(stored.user_code.is_none());
+        assert!(stored.identifier.is_empty());
+        assert!(stored.did.is_none());
+    }
+
+    #[test]
+    fn redaction_drops_the_exchange_nonce() {
+        let mut a = attempt();
+        a.browser_nonce = Some("secret".into());
+        assert!(a.redacted().browser_nonce.is_none());
+    }
+}
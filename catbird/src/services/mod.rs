@@ -2,16 +2,19 @@
 //!
 //! Business logic and external service integrations.
 
+pub mod app_password;
 mod atproto_client;
 pub mod chat_poll;
 mod crypto;
 mod dpop_nonce_cache;
+pub mod login_attempts;
 mod mls_auth;
 pub mod push;
 pub(crate) mod redis_auth_store;
 pub mod redis_crypto;
 pub mod service_auth;
 mod ssrf;
+pub mod token_refresher;
 
 pub use atproto_client::{AtProtoClient, ProxyResponse};
 pub use crypto::KeyStore;
@@ -1,24 +1,20 @@
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{borrow::Cow, path::Path, sync::Arc};
 
 use a2::{
-    Client, DefaultNotificationBuilder, Error as A2Error, ErrorReason, NotificationBuilder,
-    NotificationOptions, Priority,
+    Client, CollapseId, DefaultNotificationBuilder, Error as A2Error, ErrorReason,
+    NotificationBuilder, NotificationOptions, Priority, PushType,
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use futures_util::future::BoxFuture;
 
 use crate::config::ApnsConfig;
 
-use super::types::RegistrationRow;
-
-#[derive(Debug, Clone)]
-pub struct ApnsNotification {
-    pub title: String,
-    pub body: String,
-    pub user_did: String,
-    pub custom_data: HashMap<String, String>,
-    pub mutable_content: bool,
-    pub thread_id: Option<String>,
-}
+use super::{
+    registry::PushRegistry,
+    rich_content::MAX_APNS_PAYLOAD_BYTES,
+    transport::{DeliveryError, DeliveryReceipt, PushNotification, PushTransport},
+    types::RegistrationRow,
+};
 
 /// The two APNs environments. Sandbox tokens (Xcode debug builds) and
 /// production tokens (TestFlight/App Store builds) are not interchangeable —
@@ -63,16 +59,50 @@ fn is_bad_device_token(err: &A2Error) -> bool {
     )
 }
 
+/// A failed APNs send and the environment it was sent to.
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct ApnsError {
+    pub environment: &'static str,
+    pub error: A2Error,
+}
+
+fn is_invalid_token(err: &anyhow::Error) -> bool {
+    if let Some(ApnsError {
+        error: A2Error::ResponseError(response),
+        ..
+    }) = err.downcast_ref::<ApnsError>()
+    {
+        if response.code == 410 {
+            return true;
+        }
+        // `send` already retries BadDeviceToken once against the other
+        // environment, so a BadDeviceToken reaching here means both
+        // endpoints rejected the token — it's genuinely invalid, not just
+        // aimed at the wrong environment.
+        if let Some(body) = response.error.as_ref() {
+            if body.reason == ErrorReason::BadDeviceToken {
+                return true;
+            }
+        }
+    }
+
+    let message = err.to_string().to_ascii_lowercase();
+    message.contains("unregistered")
+}
+
 #[derive(Clone)]
 pub struct ApnsDelivery {
     production_client: Arc<Client>,
     sandbox_client: Arc<Client>,
     topic: String,
     default_production: bool,
+    /// Persists the environment learned by try-and-learn.
+    registry: PushRegistry,
 }
 
 impl ApnsDelivery {
-    pub fn new(config: &ApnsConfig) -> Result<Option<Self>> {
+    pub fn new(config: &ApnsConfig, registry: PushRegistry) -> Result<Option<Self>> {
         let (Some(key_path), Some(key_id), Some(team_id), Some(topic)) = (
             config.key_path.as_deref(),
             config.key_id.as_deref(),
@@ -109,9 +139,18 @@ impl ApnsDelivery {
             sandbox_client: Arc::new(sandbox_client),
             topic: topic.to_string(),
             default_production: config.production,
+            registry,
         }))
     }
 
+    /// The environment `send` tries first for a registration.
+    pub fn first_env(&self, registration: &RegistrationRow) -> &'static str {
+        first_try_env(
+            registration.apns_environment.as_deref(),
+            self.default_production,
+        )
+    }
+
     fn client_for(&self, env: &str) -> &Client {
         if env == ENV_PRODUCTION {
             &self.production_client
@@ -129,23 +168,26 @@ impl ApnsDelivery {
     /// also fails with `BadDeviceToken`, the original error is returned so
     /// callers can deactivate the token as invalid.
     ///
-    /// Returns the APNs environment ("production" or "sandbox") that
-    /// successfully delivered the notification, so callers can persist a
-    /// learned environment when it differs from what's on file.
+    /// The receipt names the APNs environment ("production" or "sandbox")
+    /// that accepted the notification, so callers can persist a learned
+    /// environment when it differs from what's on file.
     pub async fn send(
         &self,
         registration: &RegistrationRow,
-        notification: &ApnsNotification,
-    ) -> Result<&'static str> {
-        let payload = self.build_payload(registration, notification)?;
+        notification: &PushNotification,
+    ) -> Result<DeliveryReceipt> {
+        let notification = fit_payload(notification, |candidate| {
+            Ok(self
+                .build_payload(registration, candidate)?
+                .to_json_string()?
+                .len())
+        })?;
+        let payload = self.build_payload(registration, &notification)?;
 
-        let first_env = first_try_env(
-            registration.apns_environment.as_deref(),
-            self.default_production,
-        );
+        let first_env = self.first_env(registration);
 
         match self.client_for(first_env).send(payload.clone()).await {
-            Ok(_) => Ok(first_env),
+            Ok(response) => Ok(receipt(response, first_env)),
             Err(err) if is_bad_device_token(&err) => {
                 let second_env = other_env(first_env);
                 tracing::info!(
@@ -155,43 +197,85 @@ impl ApnsDelivery {
                     "APNs BadDeviceToken on first attempt; retrying on other environment"
                 );
                 match self.client_for(second_env).send(payload).await {
-                    Ok(_) => Ok(second_env),
+                    Ok(response) => Ok(receipt(response, second_env)),
                     Err(second_err) => {
                         // BadDeviceToken on both endpoints means the token is
                         // genuinely invalid, not just aimed at the wrong
                         // environment. Surface the original error so
                         // `is_invalid_token` (which also checks for
                         // BadDeviceToken) can deactivate it.
-                        Err(anyhow::Error::new(second_err))
+                        Err(ApnsError {
+                            environment: second_env,
+                            error: second_err,
+                        }
+                        .into())
                     }
                 }
             }
-            Err(err) => Err(anyhow::Error::new(err)),
+            Err(err) => Err(ApnsError {
+                environment: first_env,
+                error: err,
+            }
+            .into()),
         }
     }
 
     fn build_payload<'a>(
         &'a self,
         registration: &'a RegistrationRow,
-        notification: &'a ApnsNotification,
+        notification: &'a PushNotification,
     ) -> Result<a2::request::payload::Payload<'a>> {
-        let mut builder = DefaultNotificationBuilder::new()
-            .set_title(&notification.title)
-            .set_body(&notification.body)
-            .set_sound("default");
+        let has_alert = notification.has_alert();
+        let mut builder = DefaultNotificationBuilder::new();
+        if has_alert {
+            builder = builder
+                .set_title(&notification.title)
+                .set_body(&notification.body);
+        }
 
+        if let Some(text) = &notification.localization {
+            // iOS renders these from the app's Localizable.strings in the
+            // device language; title/body above remain the fallback.
+            builder = builder
+                .set_title_loc_key(text.title_key)
+                .set_loc_key(text.body_key)
+                .set_loc_args(&text.body_args);
+        }
+        if !notification.silent {
+            builder = builder.set_sound("default");
+        }
         if notification.mutable_content {
             builder = builder.set_mutable_content();
         }
+        if notification.content_available {
+            builder = builder.set_content_available();
+        }
+        if let Some(badge) = notification.badge {
+            builder = builder.set_badge(badge);
+        }
+
+        let collapse_id = notification
+            .collapse_id
+            .as_deref()
+            .map(CollapseId::new)
+            .transpose()?;
 
         let mut payload = builder.build(
             &registration.device_token,
             NotificationOptions {
                 apns_topic: Some(&self.topic),
-                apns_priority: Some(Priority::High),
-                apns_collapse_id: None,
+                // Nothing to show without an alert, so let APNs batch it.
+                apns_priority: Some(if has_alert {
+                    Priority::High
+                } else {
+                    Priority::Normal
+                }),
+                apns_collapse_id: collapse_id,
                 apns_expiration: None,
-                apns_push_type: None,
+                // APNs requires the background type for content-available
+                // pushes with no alert.
+                apns_push_type: (notification.content_available && !has_alert)
+                    .then_some(PushType::Background),
                 apns_id: None,
             },
         );
@@ -204,13 +288,112 @@ impl ApnsDelivery {
             payload.add_custom_data("thread-id", thread_id)?;
         }
 
+        if let Some(ref rich) = notification.rich {
+            payload.add_custom_data("rich", rich)?;
+        }
+
         Ok(payload)
     }
 }
 
+impl PushTransport for ApnsDelivery {
+    fn send<'a>(
+        &'a self,
+        registration: &'a RegistrationRow,
+        notification: &'a PushNotification,
+    ) -> BoxFuture<'a, Result<DeliveryReceipt, DeliveryError>> {
+        Box::pin(async move {
+            let receipt = match ApnsDelivery::send(self, registration, notification).await {
+                Ok(receipt) => receipt,
+                Err(err) if is_invalid_token(&err) => {
+                    return Err(DeliveryError::InvalidToken {
+                        reason: "apns_unregistered",
+                        detail: err.to_string(),
+                        response: response(&err),
+                    });
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            if let Some(delivered_env) = receipt
+                .environment
+                .filter(|env| registration.apns_environment.as_deref() != Some(*env))
+            {
+                tracing::info!(
+                    did = %registration.did,
+                    token = %registration.device_token,
+                    env = delivered_env,
+                    "Learned APNs environment"
+                );
+                if let Err(err) = self
+                    .registry
+                    .set_apns_environment(
+                        &registration.did,
+                        &registration.device_token,
+                        delivered_env,
+                    )
+                    .await
+                {
+                    tracing::warn!(error = %err, "Failed to persist learned APNs environment");
+                }
+            }
+            Ok(receipt)
+        })
+    }
+}
+
+/// Status, `apns-id`, and environment of the response APNs rejected a
+/// send with.
+pub(super) fn response(err: &anyhow::Error) -> Option<DeliveryReceipt> {
+    match err.downcast_ref::<ApnsError>() {
+        Some(ApnsError {
+            environment,
+            error: A2Error::ResponseError(response),
+        }) => Some(DeliveryReceipt {
+            status: response.code,
+            provider_id: response.apns_id.clone(),
+            environment: Some(environment),
+        }),
+        _ => None,
+    }
+}
+
+fn receipt(response: a2::Response, environment: &'static str) -> DeliveryReceipt {
+    DeliveryReceipt {
+        status: response.code,
+        provider_id: response.apns_id,
+        environment: Some(environment),
+    }
+}
+
+/// `notification` with its rich content trimmed until `payload_size`
+/// reports it fits in an APNs payload.
+fn fit_payload(
+    notification: &PushNotification,
+    payload_size: impl Fn(&PushNotification) -> Result<usize>,
+) -> Result<Cow<'_, PushNotification>> {
+    let mut fitted = Cow::Borrowed(notification);
+    loop {
+        let size = payload_size(&fitted)?;
+        if size <= MAX_APNS_PAYLOAD_BYTES {
+            return Ok(fitted);
+        }
+        let trimmed = match fitted.to_mut().rich.as_mut() {
+            Some(rich) => rich.trim(),
+            None => false,
+        };
+        if !trimmed {
+            return Err(anyhow!(
+                "APNs payload is {size} bytes, limit is {MAX_APNS_PAYLOAD_BYTES}"
+            ));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::services::push::rich_content::{MediaAttachment, MediaKind, RichContent};
 
     #[test]
     fn first_try_env_prefers_known_registration_env() {
@@ -258,4 +441,106 @@ mod tests {
         });
         assert!(!is_bad_device_token(&unregistered_err));
     }
+
+    #[test]
+    fn unregistered_and_rejected_tokens_are_invalid() {
+        let response = |reason, code| {
+            anyhow::Error::new(ApnsError {
+                environment: ENV_SANDBOX,
+                error: A2Error::ResponseError(a2::Response {
+                    error: Some(a2::ErrorBody {
+                        reason,
+                        timestamp: None,
+                    }),
+                    apns_id: Some("apns-id".to_string()),
+                    code,
+                }),
+            })
+        };
+        assert!(is_invalid_token(&response(ErrorReason::Unregistered, 410)));
+        assert!(is_invalid_token(&response(
+            ErrorReason::BadDeviceToken,
+            400
+        )));
+        assert!(!is_invalid_token(&response(
+            ErrorReason::TooManyRequests,
+            429
+        )));
+        assert!(!is_invalid_token(&anyhow::anyhow!("connection reset")));
+
+        assert_eq!(
+            super::response(&response(ErrorReason::TooManyRequests, 429)),
+            Some(DeliveryReceipt {
+                status: 429,
+                provider_id: Some("apns-id".to_string()),
+                environment: Some(ENV_SANDBOX),
+            })
+        );
+        assert_eq!(super::response(&anyhow::anyhow!("connection reset")), None);
+    }
+
+    fn rich_notification(parent_text: &str) -> PushNotification {
+        PushNotification {
+            title: "New reply".into(),
+            body: "Alice: hello".into(),
+            localization: None,
+            user_did: "did:plc:bob".into(),
+            custom_data: Default::default(),
+            mutable_content: true,
+            rich: Some(RichContent {
+                avatar_url: Some("https://cdn.bsky.app/avatar".into()),
+                media: vec![MediaAttachment {
+                    kind: MediaKind::Image,
+                    url: "https://cdn.bsky.app/image".into(),
+                    alt: Some("a".repeat(100)),
+                }],
+                parent_text: Some(parent_text.to_string()),
+            }),
+            thread_id: None,
+            collapse_id: None,
+            silent: false,
+            content_available: false,
+            badge: None,
+        }
+    }
+
+    #[test]
+    fn badge_updates_and_retractions_carry_no_alert() {
+        let update = PushNotification::badge_only("did:plc:bob", 3);
+        assert!(update.is_badge_only());
+        assert!(update.silent);
+        assert_eq!(update.badge, Some(3));
+        assert!(!rich_notification("hi").is_badge_only());
+
+        let retraction =
+            PushNotification::chat_retraction("did:plc:bob", "convo1", Some("msg1"), Some(2));
+        assert!(!retraction.has_alert());
+        assert!(!retraction.is_badge_only());
+        assert_eq!(retraction.thread_id.as_deref(), Some("chat:convo1"));
+        assert_eq!(retraction.custom_data["messageId"], "msg1");
+    }
+
+    fn json_size(notification: &PushNotification) -> Result<usize> {
+        Ok(serde_json::to_string(&notification.rich)?.len() + notification.body.len())
+    }
+
+    #[test]
+    fn oversized_payloads_drop_rich_fields_in_priority_order() {
+        let small = rich_notification("short");
+        assert!(matches!(
+            fit_payload(&small, json_size).unwrap(),
+            Cow::Borrowed(_)
+        ));
+
+        let large = rich_notification(&"x".repeat(MAX_APNS_PAYLOAD_BYTES));
+        let fitted = fit_payload(&large, json_size).unwrap();
+        let rich = fitted.rich.as_ref().unwrap();
+        assert_eq!(rich.parent_text, None);
+        assert_eq!(rich.media.len(), 1);
+        assert!(rich.media[0].alt.is_some());
+        assert!(rich.avatar_url.is_some());
+
+        let hopeless = |_: &PushNotification| Ok(MAX_APNS_PAYLOAD_BYTES + 1);
+        assert!(fit_payload(&large, hopeless).is_err());
+    }
 }
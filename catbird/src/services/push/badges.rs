@@ -0,0 +1,311 @@
+//! Unread counts behind the app icon badge.
+//!
+//! Notifications are seeded from `app.bsky.notification.getUnreadCount`
+//! and counted up as events are delivered or coalesced; the seed is
+//! refreshed every sync interval, which corrects whatever the local count
+//! missed (events dropped by push preferences still count as unread).
+//! Chats are counted per conversation by the chat poller, which is also
+//! how it knows a read or deleted message had been notified. The proxy
+//! resets both when the account marks them read, and every APNs alert
+//! carries the total.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use reqwest::Method;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    config::AppState,
+    services::{AtProtoClient, ProxyResponse},
+};
+
+use super::{with_step_timeout, BADGE_SEED_TIMEOUT};
+
+#[derive(Clone)]
+pub struct BadgeCounter {
+    db_pool: Pool<Postgres>,
+    reseed_interval_seconds: i64,
+}
+
+impl BadgeCounter {
+    pub fn new(db_pool: Pool<Postgres>, reseed_interval_seconds: u64) -> Self {
+        Self {
+            db_pool,
+            reseed_interval_seconds: reseed_interval_seconds as i64,
+        }
+    }
+
+    /// Counts a notification event and returns the badge to send with it.
+    /// When the seed is stale the count is re-read from the AppView
+    /// instead, which already includes this event.
+    pub async fn count_notification(
+        &self,
+        state: &Arc<AppState>,
+        account_did: &str,
+    ) -> Result<u32> {
+        if self.needs_seed(account_did).await? {
+            let fetched = with_step_timeout(
+                "badge_seed",
+                BADGE_SEED_TIMEOUT,
+                fetch_unread_count(state, &self.db_pool, account_did),
+            )
+            .await
+            .and_then(|result| result);
+            match fetched {
+                Ok(unread) => {
+                    self.seed_notifications(account_did, unread).await?;
+                    return self.total(account_did).await;
+                }
+                Err(err) => {
+                    // Count locally until the next interval rather than
+                    // retrying the fetch on every event.
+                    tracing::debug!(
+                        did = %account_did,
+                        error = %err,
+                        "Failed to seed unread notification count"
+                    );
+                    self.mark_seed_attempted(account_did).await?;
+                }
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO push_badge_counts (account_did, notifications_unread)
+            VALUES ($1, 1)
+            ON CONFLICT (account_did) DO UPDATE
+            SET notifications_unread = push_badge_counts.notifications_unread + 1,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(account_did)
+        .execute(&self.db_pool)
+        .await?;
+
+        self.total(account_did).await
+    }
+
+    /// Clears the notification count (`app.bsky.notification.updateSeen`).
+    /// Returns whether it was non-zero.
+    pub async fn reset_notifications(&self, account_did: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE push_badge_counts
+            SET notifications_unread = 0, updated_at = NOW()
+            WHERE account_did = $1 AND notifications_unread > 0
+            "#,
+        )
+        .bind(account_did)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn increment_chat(&self, account_did: &str, convo_id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO push_chat_unread (account_did, convo_id, unread)
+            VALUES ($1, $2, 1)
+            ON CONFLICT (account_did, convo_id) DO UPDATE
+            SET unread = push_chat_unread.unread + 1, updated_at = NOW()
+            "#,
+        )
+        .bind(account_did)
+        .bind(convo_id)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Uncounts one message deleted before it was read. Returns whether the
+    /// conversation had anything unread.
+    pub async fn decrement_chat(&self, account_did: &str, convo_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE push_chat_unread
+            SET unread = unread - 1, updated_at = NOW()
+            WHERE account_did = $1 AND convo_id = $2 AND unread > 0
+            "#,
+        )
+        .bind(account_did)
+        .bind(convo_id)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Clears one conversation's unread count, or all of them when
+    /// `convo_id` is `None`. Returns the conversations that had unread
+    /// messages, whose notifications can now be retracted.
+    pub async fn reset_chat(
+        &self,
+        account_did: &str,
+        convo_id: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let cleared = sqlx::query_as::<_, (String, i32)>(
+            r#"
+            DELETE FROM push_chat_unread
+            WHERE account_did = $1 AND ($2::TEXT IS NULL OR convo_id = $2)
+            RETURNING convo_id, unread
+            "#,
+        )
+        .bind(account_did)
+        .bind(convo_id)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(cleared
+            .into_iter()
+            .filter(|(_, unread)| *unread > 0)
+            .map(|(convo_id, _)| convo_id)
+            .collect())
+    }
+
+    /// Unread notifications plus unread chat messages.
+    pub async fn total(&self, account_did: &str) -> Result<u32> {
+        let total = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COALESCE((SELECT notifications_unread::BIGINT
+                             FROM push_badge_counts WHERE account_did = $1), 0)
+                 + COALESCE((SELECT SUM(unread)::BIGINT
+                             FROM push_chat_unread WHERE account_did = $1), 0)
+            "#,
+        )
+        .bind(account_did)
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(badge_value(total))
+    }
+
+    async fn needs_seed(&self, account_did: &str) -> Result<bool> {
+        let fresh = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM push_badge_counts
+                WHERE account_did = $1
+                  AND notifications_seeded_at > NOW() - make_interval(secs => $2)
+            )
+            "#,
+        )
+        .bind(account_did)
+        .bind(self.reseed_interval_seconds)
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(!fresh)
+    }
+
+    async fn seed_notifications(&self, account_did: &str, unread: i32) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO push_badge_counts (account_did, notifications_unread, notifications_seeded_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (account_did) DO UPDATE
+            SET notifications_unread = EXCLUDED.notifications_unread,
+                notifications_seeded_at = NOW(),
+                updated_at = NOW()
+            "#,
+        )
+        .bind(account_did)
+        .bind(unread)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_seed_attempted(&self, account_did: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO push_badge_counts (account_did, notifications_seeded_at)
+            VALUES ($1, NOW())
+            ON CONFLICT (account_did) DO UPDATE
+            SET notifications_seeded_at = NOW(), updated_at = NOW()
+            "#,
+        )
+        .bind(account_did)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// `app.bsky.notification.getUnreadCount` through the account's PDS, using
+/// its background push session.
+async fn fetch_unread_count(
+    state: &Arc<AppState>,
+    db_pool: &Pool<Postgres>,
+    account_did: &str,
+) -> Result<i32> {
+    let (session_id, pds_url) = sqlx::query_as::<_, (String, String)>(
+        "SELECT session_id, pds_url FROM push_accounts WHERE account_did = $1 AND auth_revoked_at IS NULL",
+    )
+    .bind(account_did)
+    .fetch_optional(db_pool)
+    .await?
+    .ok_or_else(|| anyhow!("No active push account for DID {}", account_did))?;
+
+    let (session, dpop) =
+        super::resolve_background_session(state, account_did, &session_id, &pds_url).await?;
+    let response = AtProtoClient::new(state.clone())
+        .proxy_request(
+            &session,
+            Method::GET,
+            "/xrpc/app.bsky.notification.getUnreadCount",
+            None,
+            None,
+            None,
+            None,
+            "push-sync",
+            Some(&dpop),
+        )
+        .await?;
+
+    let body = match response {
+        ProxyResponse::Buffered { status, body, .. } => {
+            if !(200..300).contains(&status) {
+                return Err(anyhow!("getUnreadCount returned HTTP {}", status));
+            }
+            body
+        }
+        ProxyResponse::Streaming { .. } => {
+            return Err(anyhow!("Unexpected streaming response for getUnreadCount"))
+        }
+    };
+
+    unread_count_from_json(&serde_json::from_slice(&body)?)
+        .ok_or_else(|| anyhow!("getUnreadCount response has no count"))
+}
+
+fn unread_count_from_json(payload: &Value) -> Option<i32> {
+    let count = payload.get("count").and_then(Value::as_i64)?;
+    Some(count.clamp(0, i32::MAX as i64) as i32)
+}
+
+fn badge_value(total: i64) -> u32 {
+    u32::try_from(total.max(0)).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_unread_count_and_clamps_badges() {
+        assert_eq!(unread_count_from_json(&json!({ "count": 7 })), Some(7));
+        assert_eq!(unread_count_from_json(&json!({ "count": -3 })), Some(0));
+        assert_eq!(unread_count_from_json(&json!({})), None);
+
+        assert_eq!(badge_value(12), 12);
+        assert_eq!(badge_value(-1), 0);
+        assert_eq!(badge_value(i64::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,301 @@
+//! End-to-end encrypted chat message previews.
+//!
+//! Queued chat events never hold message text. Instead, the chat poller
+//! encrypts the preview to each device that sent a `previewKey` with
+//! `registerPush`, stores only the ciphertexts in the event, and the decision
+//! engine hands each device its own as the `encryptedPreview` custom data
+//! key. The Notification Service Extension decrypts it with the private key
+//! that never leaves the device; a device without a key gets the generic
+//! chat body.
+//!
+//! Until every client registers a key, devices without one keep the
+//! plaintext preview app builds before this expect (`messageText`, see
+//! `LEGACY_PREVIEW_KEY`) when `push.legacy_chat_previews` is on. The text
+//! never reaches Postgres: the poller holds it in Redis for
+//! `LEGACY_PREVIEW_TTL_SECONDS`, and a push sent after that shows the generic
+//! body.
+//!
+//! Format (ECIES, version 1): `base64url(E || nonce || ciphertext || tag)`,
+//! where `E` is a fresh ephemeral P-256 public key (65-byte uncompressed
+//! SEC1) and the ciphertext is the UTF-8 preview under AES-128-GCM. The key
+//! is `HKDF-SHA256(salt = "", ikm = ECDH(e, D), info = PREVIEW_INFO || E || D)`
+//! with `D` the device key in uncompressed form, and the message ID is the
+//! additional authenticated data, so a preview can't be replayed onto
+//! another message.
+
+use std::collections::HashMap;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes128Gcm, Nonce,
+};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use p256::{elliptic_curve::sec1::ToEncodedPoint, PublicKey, SecretKey};
+use serde_json::Value;
+
+use super::{
+    types::RegistrationRow,
+    web_push::{decode_base64url, hkdf_expand, random_bytes},
+};
+
+const PREVIEW_INFO: &[u8] = b"Catbird chat preview v1\0";
+
+/// Custom data key of the plaintext preview for devices without a key.
+pub const LEGACY_PREVIEW_KEY: &str = "messageText";
+/// Characters of plaintext preview sent.
+const LEGACY_PREVIEW_CHARS: usize = 200;
+/// How long the plaintext is held for the worker to pick up.
+const LEGACY_PREVIEW_TTL_SECONDS: u64 = 300;
+
+/// Checks a `previewKey` sent by a client and returns it as unpadded
+/// base64url of the uncompressed point, whichever SEC1 form it came in.
+pub fn normalize_preview_key(value: &str) -> Result<String, String> {
+    let key = parse_preview_key(value)
+        .ok_or_else(|| "Invalid previewKey: expected a base64url P-256 public key".to_string())?;
+    Ok(URL_SAFE_NO_PAD.encode(key.to_encoded_point(false).as_bytes()))
+}
+
+fn parse_preview_key(value: &str) -> Option<PublicKey> {
+    let bytes = decode_base64url(value.trim())?;
+    PublicKey::from_sec1_bytes(&bytes).ok()
+}
+
+/// Encrypts `text` to one device's preview key.
+pub fn encrypt_preview(preview_key: &str, message_id: &str, text: &str) -> Result<String> {
+    let device_public = parse_preview_key(preview_key)
+        .ok_or_else(|| anyhow!("preview key is not a P-256 point"))?;
+    let ephemeral = SecretKey::random(&mut rand::rngs::OsRng);
+    encrypt_with(&ephemeral, &device_public, random_bytes(), message_id, text)
+}
+
+fn encrypt_with(
+    ephemeral: &SecretKey,
+    device_public: &PublicKey,
+    nonce: [u8; 12],
+    message_id: &str,
+    text: &str,
+) -> Result<String> {
+    let ephemeral_bytes = ephemeral.public_key().to_encoded_point(false);
+    let device_bytes = device_public.to_encoded_point(false);
+    let shared =
+        p256::ecdh::diffie_hellman(ephemeral.to_nonzero_scalar(), device_public.as_affine());
+
+    let mut info = Vec::with_capacity(PREVIEW_INFO.len() + 65 + 65);
+    info.extend_from_slice(PREVIEW_INFO);
+    info.extend_from_slice(ephemeral_bytes.as_bytes());
+    info.extend_from_slice(device_bytes.as_bytes());
+    let key = hkdf_expand::<16>(&[], shared.raw_secret_bytes(), &info)?;
+
+    let ciphertext = Aes128Gcm::new_from_slice(&key)
+        .map_err(|_| anyhow!("invalid chat preview key length"))?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: text.as_bytes(),
+                aad: message_id.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("chat preview encryption failed"))?;
+
+    let mut sealed = Vec::with_capacity(65 + nonce.len() + ciphertext.len());
+    sealed.extend_from_slice(ephemeral_bytes.as_bytes());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(URL_SAFE_NO_PAD.encode(sealed))
+}
+
+/// Previews for every registration with a key, by registration ID. A key
+/// that no longer parses is skipped; that device shows the generic body.
+pub fn encrypt_for_devices(
+    registrations: &[RegistrationRow],
+    message_id: &str,
+    text: &str,
+) -> HashMap<String, String> {
+    if text.is_empty() {
+        return HashMap::new();
+    }
+    registrations
+        .iter()
+        .filter_map(|registration| {
+            let preview_key = registration.preview_key.as_deref()?;
+            match encrypt_preview(preview_key, message_id, text) {
+                Ok(preview) => Some((registration.id.to_string(), preview)),
+                Err(err) => {
+                    tracing::debug!(
+                        registration = %registration.id,
+                        error = %err,
+                        "Skipping chat preview for device"
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn legacy_preview_key(recipient_did: &str, message_id: &str) -> String {
+    format!("chat_preview:{recipient_did}:{message_id}")
+}
+
+/// Holds `text` for the keyless devices of `recipient_did`.
+pub async fn stash_legacy_preview(
+    redis: &redis::aio::ConnectionManager,
+    recipient_did: &str,
+    message_id: &str,
+    text: &str,
+) -> Result<()> {
+    let text: String = text.chars().take(LEGACY_PREVIEW_CHARS).collect();
+    let mut conn = redis.clone();
+    redis::cmd("SET")
+        .arg(legacy_preview_key(recipient_did, message_id))
+        .arg(text)
+        .arg("EX")
+        .arg(LEGACY_PREVIEW_TTL_SECONDS)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// The plaintext preview held for a queued chat event, if it hasn't expired.
+pub async fn legacy_preview(
+    redis: &redis::aio::ConnectionManager,
+    record: &Value,
+) -> Result<Option<String>> {
+    let (Some(recipient_did), Some(message_id)) = (
+        record.get("recipientDid").and_then(Value::as_str),
+        record.get("messageId").and_then(Value::as_str),
+    ) else {
+        return Ok(None);
+    };
+    let mut conn = redis.clone();
+    let text: Option<String> = redis::cmd("GET")
+        .arg(legacy_preview_key(recipient_did, message_id))
+        .query_async(&mut conn)
+        .await?;
+    Ok(text.filter(|text| !text.is_empty()))
+}
+
+/// The preview a queued chat event holds for `registration`.
+pub fn preview_for<'a>(record: &'a Value, registration: &RegistrationRow) -> Option<&'a str> {
+    record
+        .get("encryptedPreviews")
+        .and_then(|previews| previews.get(registration.id.to_string()))
+        .and_then(Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// What the Notification Service Extension does.
+    fn decrypt(device: &SecretKey, message_id: &str, preview: &str) -> Option<String> {
+        let sealed = URL_SAFE_NO_PAD.decode(preview).ok()?;
+        let (ephemeral_bytes, rest) = sealed.split_at(65);
+        let (nonce, ciphertext) = rest.split_at(12);
+        let ephemeral = PublicKey::from_sec1_bytes(ephemeral_bytes).ok()?;
+        let shared = p256::ecdh::diffie_hellman(device.to_nonzero_scalar(), ephemeral.as_affine());
+
+        let mut info = PREVIEW_INFO.to_vec();
+        info.extend_from_slice(ephemeral_bytes);
+        info.extend_from_slice(device.public_key().to_encoded_point(false).as_bytes());
+        let key = hkdf_expand::<16>(&[], shared.raw_secret_bytes(), &info).ok()?;
+
+        let plaintext = Aes128Gcm::new_from_slice(&key)
+            .ok()?
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: message_id.as_bytes(),
+                },
+            )
+            .ok()?;
+        String::from_utf8(plaintext).ok()
+    }
+
+    fn device_key() -> (SecretKey, String) {
+        let secret = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let public = URL_SAFE_NO_PAD.encode(secret.public_key().to_encoded_point(false).as_bytes());
+        (secret, public)
+    }
+
+    fn registration(preview_key: Option<&str>) -> RegistrationRow {
+        RegistrationRow {
+            id: sqlx::types::Uuid::from_u128(1),
+            did: "did:plc:bob".to_string(),
+            device_token: "token".to_string(),
+            platform: "ios".to_string(),
+            app_id: "blue.catbird".to_string(),
+            service_did: None,
+            age_restricted: false,
+            is_active: true,
+            apns_environment: None,
+            locale: None,
+            preference_overrides: None,
+            preview_key: preview_key.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn device_decrypts_its_preview() {
+        let (secret, public) = device_key();
+        let preview = encrypt_preview(&public, "msg1", "see you at 8 🐈").unwrap();
+
+        assert_eq!(
+            decrypt(&secret, "msg1", &preview).as_deref(),
+            Some("see you at 8 🐈")
+        );
+        assert_eq!(decrypt(&secret, "msg2", &preview), None);
+        let other = SecretKey::from_slice(&[9u8; 32]).unwrap();
+        assert_eq!(decrypt(&other, "msg1", &preview), None);
+    }
+
+    #[test]
+    fn previews_use_fresh_keys_and_nonces() {
+        let (_, public) = device_key();
+        let first = encrypt_preview(&public, "msg1", "hi").unwrap();
+        let second = encrypt_preview(&public, "msg1", "hi").unwrap();
+        assert_ne!(first, second);
+
+        let ephemeral = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let device = parse_preview_key(&public).unwrap();
+        assert_eq!(
+            encrypt_with(&ephemeral, &device, [0u8; 12], "msg1", "hi").unwrap(),
+            encrypt_with(&ephemeral, &device, [0u8; 12], "msg1", "hi").unwrap()
+        );
+    }
+
+    #[test]
+    fn normalizes_preview_keys() {
+        let (secret, public) = device_key();
+        let compressed =
+            URL_SAFE_NO_PAD.encode(secret.public_key().to_encoded_point(true).as_bytes());
+        assert_eq!(normalize_preview_key(&compressed).unwrap(), public);
+        assert_eq!(
+            normalize_preview_key(&format!("{public}=")).unwrap(),
+            public
+        );
+        assert!(normalize_preview_key("not a key").is_err());
+        assert!(normalize_preview_key(&URL_SAFE_NO_PAD.encode([4u8; 65])).is_err());
+    }
+
+    #[test]
+    fn only_devices_with_keys_get_previews() {
+        let (secret, public) = device_key();
+        let keyed = registration(Some(&public));
+        let mut keyless = registration(None);
+        keyless.id = sqlx::types::Uuid::from_u128(2);
+
+        let previews = encrypt_for_devices(&[keyed.clone(), keyless.clone()], "msg1", "hello");
+        assert_eq!(previews.len(), 1);
+        assert!(encrypt_for_devices(&[keyed.clone()], "msg1", "").is_empty());
+
+        let record = json!({ "convoId": "convo1", "encryptedPreviews": previews });
+        let preview = preview_for(&record, &keyed).unwrap();
+        assert_eq!(decrypt(&secret, "msg1", preview).as_deref(), Some("hello"));
+        assert_eq!(preview_for(&record, &keyless), None);
+        assert_eq!(preview_for(&json!({}), &keyed), None);
+    }
+}
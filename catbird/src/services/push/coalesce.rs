@@ -0,0 +1,457 @@
+//! Coalescing for high-volume social notifications.
+//!
+//! A viral post would otherwise produce one banner per like. Likes, reposts,
+//! and follows that pass the decision engine are instead folded into a
+//! `push_coalesce_groups` row keyed by (recipient, type, subject). The worker
+//! flushes a group once its window closes, sending a single summary. Every
+//! flush for a group reuses its collapse id, so a later burst replaces the
+//! banner already on screen ("Alice and 40 others…") instead of stacking a
+//! new one. The count is of distinct actors (of events, for a digest), tracked
+//! per group in `push_coalesce_members`. Groups idle past `GROUP_RETENTION_SECONDS` are
+//! purged and start over.
+//!
+//! Events held back by quiet hours land in a per-recipient digest group
+//! instead, whatever their type.
+
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, Pool, Postgres};
+use std::collections::HashMap;
+
+use super::{
+    localization::{LocalizedText, DEFAULT_LOCALE},
+    notification_kind::NotificationKind,
+    transport::PushNotification,
+    types::{PushPreferencesDocument, QueueRow},
+};
+
+/// Group type for events released from quiet hours.
+const DIGEST_TYPE: &str = "digest";
+
+/// How long an idle group keeps its running count and collapse id.
+const GROUP_RETENTION_SECONDS: i64 = 6 * 60 * 60;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CoalescedGroup {
+    pub recipient_did: String,
+    pub group_key: String,
+    pub notification_type: String,
+    pub subject_uri: Option<String>,
+    pub latest_actor_did: String,
+    pub latest_actor_label: String,
+    pub total_count: i32,
+}
+
+#[derive(Clone)]
+pub struct PushCoalescer {
+    db_pool: Pool<Postgres>,
+    window_seconds: i64,
+}
+
+impl PushCoalescer {
+    pub fn new(db_pool: Pool<Postgres>, window_seconds: u64) -> Self {
+        Self {
+            db_pool,
+            window_seconds: window_seconds as i64,
+        }
+    }
+
+    /// Whether `row` should be held for a summary rather than sent now.
+    pub fn applies_to(&self, row: &QueueRow, prefs: &PushPreferencesDocument) -> bool {
+        row.quiet_deferred
+            || (self.window_seconds > 0
+                && prefs.grouping.enabled
+                && row.notification_type.coalesces())
+    }
+
+    /// Folds an approved event into its group, opening the flush window if
+    /// the group has nothing pending. The running count is of distinct
+    /// `member`s in `push_coalesce_members`, so a retried queue row, or a
+    /// second event from someone already counted, leaves it alone.
+    pub async fn absorb(&self, row: &QueueRow, actor_label: &str) -> Result<()> {
+        let (notification_type, subject_uri) = if row.quiet_deferred {
+            (DIGEST_TYPE, None)
+        } else {
+            (row.notification_type.as_str(), row.subject_uri.as_deref())
+        };
+        let group_key = group_key(notification_type, subject_uri);
+        let member = member(row);
+        let mut tx = self.db_pool.begin().await?;
+
+        // A group idle past retention starts counting over.
+        sqlx::query(
+            r#"
+            DELETE FROM push_coalesce_members m
+            USING push_coalesce_groups g
+            WHERE g.recipient_did = $1
+              AND g.group_key = $2
+              AND g.last_event_at < NOW() - make_interval(secs => $3)
+              AND m.recipient_did = g.recipient_did
+              AND m.group_key = g.group_key
+            "#,
+        )
+        .bind(&row.recipient_did)
+        .bind(&group_key)
+        .bind(GROUP_RETENTION_SECONDS)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO push_coalesce_groups (
+                recipient_did,
+                group_key,
+                notification_type,
+                subject_uri,
+                latest_actor_did,
+                latest_actor_label,
+                total_count,
+                flush_at,
+                last_event_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, 0, NOW() + make_interval(secs => $7), NOW())
+            ON CONFLICT (recipient_did, group_key)
+            DO UPDATE
+            SET total_count = CASE
+                    WHEN push_coalesce_groups.last_event_at
+                         < NOW() - make_interval(secs => $8) THEN 0
+                    ELSE push_coalesce_groups.total_count
+                END,
+                latest_actor_did = EXCLUDED.latest_actor_did,
+                latest_actor_label = EXCLUDED.latest_actor_label,
+                flush_at = COALESCE(push_coalesce_groups.flush_at, EXCLUDED.flush_at),
+                last_event_at = NOW()
+            "#,
+        )
+        .bind(&row.recipient_did)
+        .bind(&group_key)
+        .bind(notification_type)
+        .bind(subject_uri)
+        .bind(&row.actor_did)
+        .bind(actor_label)
+        .bind(self.window_seconds)
+        .bind(GROUP_RETENTION_SECONDS)
+        .execute(&mut *tx)
+        .await?;
+
+        let first_seen = sqlx::query(
+            r#"
+            INSERT INTO push_coalesce_members (recipient_did, group_key, member)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(&row.recipient_did)
+        .bind(&group_key)
+        .bind(&member)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if first_seen {
+            sqlx::query(
+                r#"
+                UPDATE push_coalesce_groups
+                SET total_count = total_count + 1
+                WHERE recipient_did = $1 AND group_key = $2
+                "#,
+            )
+            .bind(&row.recipient_did)
+            .bind(&group_key)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Claims groups whose window has closed, clearing their pending state.
+    /// Events absorbed after this call open a fresh window.
+    pub async fn claim_due(&self, limit: i64) -> Result<Vec<CoalescedGroup>> {
+        let groups = sqlx::query_as::<_, CoalescedGroup>(
+            r#"
+            WITH due AS (
+                SELECT recipient_did, group_key
+                FROM push_coalesce_groups
+                WHERE flush_at <= NOW()
+                ORDER BY flush_at ASC
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE push_coalesce_groups g
+            SET flush_at = NULL
+            FROM due
+            WHERE g.recipient_did = due.recipient_did
+              AND g.group_key = due.group_key
+            RETURNING
+                g.recipient_did,
+                g.group_key,
+                g.notification_type,
+                g.subject_uri,
+                g.latest_actor_did,
+                g.latest_actor_label,
+                g.total_count
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    /// Re-arms a group whose summary failed transiently.
+    pub async fn retry_later(&self, group: &CoalescedGroup) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE push_coalesce_groups
+            SET flush_at = COALESCE(flush_at, NOW() + make_interval(secs => $3))
+            WHERE recipient_did = $1 AND group_key = $2
+            "#,
+        )
+        .bind(&group.recipient_did)
+        .bind(&group.group_key)
+        .bind(self.window_seconds)
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Holds a due group back, e.g. until the recipient's quiet hours end.
+    pub async fn defer(&self, group: &CoalescedGroup, delay_seconds: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE push_coalesce_groups
+            SET flush_at = GREATEST(
+                    COALESCE(flush_at, NOW()),
+                    NOW() + make_interval(secs => $3)
+                )
+            WHERE recipient_did = $1 AND group_key = $2
+            "#,
+        )
+        .bind(&group.recipient_did)
+        .bind(&group.group_key)
+        .bind(delay_seconds.max(0))
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Drops idle groups with nothing pending. Returns the number removed.
+    pub async fn purge_idle(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM push_coalesce_groups
+            WHERE flush_at IS NULL
+              AND last_event_at < NOW() - make_interval(secs => $1)
+            "#,
+        )
+        .bind(GROUP_RETENTION_SECONDS)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// What `row` counts as in its group: the actor, or for a digest (which
+/// counts notifications, not people) the queue event.
+fn member(row: &QueueRow) -> String {
+    if row.quiet_deferred {
+        format!("event:{}", row.id)
+    } else {
+        row.actor_did.clone()
+    }
+}
+
+fn group_key(notification_type: &str, subject_uri: Option<&str>) -> String {
+    format!("{}:{}", notification_type, subject_uri.unwrap_or_default())
+}
+
+/// 32 URL-safe base64 characters derived from the group, valid as an APNs
+/// collapse id, FCM collapse key, and Web Push topic alike.
+fn collapse_id(recipient_did: &str, group_key: &str) -> String {
+    let digest = Sha256::digest(format!("{recipient_did}|{group_key}").as_bytes());
+    URL_SAFE_NO_PAD.encode(&digest[..24])
+}
+
+pub fn build_summary(group: &CoalescedGroup) -> PushNotification {
+    let actor_label = group.latest_actor_label.clone();
+    let others = group.total_count.max(1) - 1;
+    if group.notification_type == DIGEST_TYPE {
+        let text = match others {
+            0 => LocalizedText::new("PUSH_DIGEST_TITLE", "PUSH_DIGEST_ONE", vec![actor_label]),
+            n => LocalizedText::new(
+                "PUSH_DIGEST_TITLE",
+                "PUSH_DIGEST_OTHER",
+                vec![(n + 1).to_string(), actor_label],
+            ),
+        };
+        return summary_notification(group, text);
+    }
+
+    let kind = group.notification_type.parse::<NotificationKind>().ok();
+    let (plural_title_key, one_key, other_key) = match kind {
+        Some(NotificationKind::Like) => (
+            "PUSH_LIKES_TITLE",
+            "PUSH_LIKE_SUMMARY_ONE",
+            "PUSH_LIKE_SUMMARY_OTHER",
+        ),
+        Some(NotificationKind::Repost) => (
+            "PUSH_REPOSTS_TITLE",
+            "PUSH_REPOST_SUMMARY_ONE",
+            "PUSH_REPOST_SUMMARY_OTHER",
+        ),
+        Some(NotificationKind::Follow) => (
+            "PUSH_FOLLOWERS_TITLE",
+            "PUSH_FOLLOW_SUMMARY_ONE",
+            "PUSH_FOLLOW_SUMMARY_OTHER",
+        ),
+        _ => (
+            "PUSH_SUMMARY_TITLE",
+            "PUSH_SUMMARY_ONE",
+            "PUSH_SUMMARY_OTHER",
+        ),
+    };
+    let text = match others {
+        0 => LocalizedText::new(
+            kind.map_or("PUSH_SUMMARY_TITLE", NotificationKind::title_key),
+            kind.and_then(NotificationKind::action_key)
+                .unwrap_or("PUSH_SUMMARY_BODY"),
+            vec![actor_label],
+        ),
+        1 => LocalizedText::new(plural_title_key, one_key, vec![actor_label]),
+        n => LocalizedText::new(
+            plural_title_key,
+            other_key,
+            vec![actor_label, n.to_string()],
+        ),
+    };
+
+    summary_notification(group, text)
+}
+
+fn summary_notification(group: &CoalescedGroup, text: LocalizedText) -> PushNotification {
+    let mut custom_data = HashMap::new();
+    custom_data.insert("reason".to_string(), group.notification_type.clone());
+    custom_data.insert("actorDid".to_string(), group.latest_actor_did.clone());
+    custom_data.insert("actorLabel".to_string(), group.latest_actor_label.clone());
+    custom_data.insert("groupCount".to_string(), group.total_count.to_string());
+    if let Some(subject_uri) = &group.subject_uri {
+        custom_data.insert("subjectUri".to_string(), subject_uri.clone());
+    }
+
+    PushNotification {
+        title: text.title(DEFAULT_LOCALE),
+        body: text.body(DEFAULT_LOCALE),
+        localization: Some(text),
+        user_did: group.recipient_did.clone(),
+        custom_data,
+        mutable_content: false,
+        rich: None,
+        thread_id: Some(group.group_key.clone()),
+        collapse_id: Some(collapse_id(&group.recipient_did, &group.group_key)),
+        silent: false,
+        content_available: false,
+        badge: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(notification_type: &str, total_count: i32) -> CoalescedGroup {
+        CoalescedGroup {
+            recipient_did: "did:plc:recipient".to_string(),
+            group_key: group_key(notification_type, Some("at://did:plc:recipient/post/1")),
+            notification_type: notification_type.to_string(),
+            subject_uri: Some("at://did:plc:recipient/post/1".to_string()),
+            latest_actor_did: "did:plc:alice".to_string(),
+            latest_actor_label: "Alice".to_string(),
+            total_count,
+        }
+    }
+
+    #[test]
+    fn groups_count_actors_and_digests_count_events() {
+        let row = |id: i64, actor_did: &str, quiet_deferred: bool| QueueRow {
+            id,
+            recipient_did: "did:plc:recipient".to_string(),
+            actor_did: actor_did.to_string(),
+            notification_type: NotificationKind::Like,
+            event_cid: format!("bafy{id}"),
+            event_path: format!("app.bsky.feed.like/{id}"),
+            subject_uri: Some("at://did:plc:recipient/post/1".to_string()),
+            thread_root_uri: None,
+            event_record_json: serde_json::json!({}),
+            event_timestamp: id,
+            created_at: time::OffsetDateTime::now_utc(),
+            attempts: 1,
+            quiet_deferred,
+            badge_counted: false,
+        };
+
+        // A, B, A: the second A is the same member as the first.
+        assert_eq!(member(&row(1, "did:plc:a", false)), "did:plc:a");
+        assert_eq!(
+            member(&row(1, "did:plc:a", false)),
+            member(&row(3, "did:plc:a", false))
+        );
+        assert_ne!(
+            member(&row(1, "did:plc:a", true)),
+            member(&row(3, "did:plc:a", true))
+        );
+        assert_eq!(member(&row(1, "did:plc:a", true)), "event:1");
+    }
+
+    #[test]
+    fn summary_counts_the_other_actors() {
+        assert_eq!(
+            build_summary(&group("like", 1)).body,
+            "Alice liked your post"
+        );
+        assert_eq!(
+            build_summary(&group("like", 2)).body,
+            "Alice and 1 other liked your post"
+        );
+        let summary = build_summary(&group("follow", 24));
+        assert_eq!(summary.body, "Alice and 23 others followed you");
+        assert_eq!(summary.title, "New followers");
+        assert_eq!(summary.custom_data["groupCount"], "24");
+    }
+
+    #[test]
+    fn quiet_hours_digest_summarises_every_type() {
+        let mut digest = group(DIGEST_TYPE, 5);
+        digest.group_key = group_key(DIGEST_TYPE, None);
+        digest.subject_uri = None;
+
+        let summary = build_summary(&digest);
+        assert_eq!(summary.title, "While you were away");
+        assert_eq!(
+            summary.body,
+            "5 notifications during quiet hours, most recently from Alice"
+        );
+        assert_eq!(
+            summary.for_locale(Some("ja")).body,
+            "おやすみ時間中に5件の通知(最新はAliceさんから)"
+        );
+    }
+
+    #[test]
+    fn collapse_id_is_stable_per_group_and_fits_every_transport() {
+        let first = build_summary(&group("like", 1)).collapse_id.unwrap();
+        let later = build_summary(&group("like", 40)).collapse_id.unwrap();
+        let other = build_summary(&group("repost", 1)).collapse_id.unwrap();
+
+        assert_eq!(first, later);
+        assert_ne!(first, other);
+        assert_eq!(first.len(), 32);
+        assert!(first
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+}
@@ -0,0 +1,263 @@
+//! Operator access to `push_event_dead_letter`.
+//!
+//! `PushQueue` moves an event here once it has used up `push.max_attempts`.
+//! From the admin port, dead letters can be listed, put back on the queue
+//! once the cause is fixed, or purged. Every operation takes the same
+//! filter; an empty filter matches everything.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{FromRow, Pool, Postgres};
+use time::OffsetDateTime;
+
+use super::{deliveries::rfc3339, queue::MAX_EVENT_AGE_HOURS};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DeadLetterFilter {
+    /// Specific dead letter ids.
+    #[serde(default)]
+    pub ids: Option<Vec<i64>>,
+    #[serde(default)]
+    pub did: Option<String>,
+    #[serde(rename = "type", default)]
+    pub notification_type: Option<String>,
+    /// Substring of the final error.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl DeadLetterFilter {
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_none()
+            && self.did.is_none()
+            && self.notification_type.is_none()
+            && self.error.is_none()
+    }
+}
+
+/// `WHERE` clause over `$1..$4` matching `DeadLetterFilter`.
+const FILTER_CLAUSE: &str = r#"
+    ($1::bigint[] IS NULL OR id = ANY($1))
+    AND ($2::text IS NULL OR recipient_did = $2)
+    AND ($3::text IS NULL OR notification_type = $3)
+    AND ($4::text IS NULL OR strpos(COALESCE(final_error, ''), $4) > 0)
+"#;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct DeadLetterRow {
+    pub id: i64,
+    pub queue_event_id: i64,
+    pub recipient_did: String,
+    pub actor_did: String,
+    pub notification_type: String,
+    pub event_path: String,
+    pub subject_uri: Option<String>,
+    pub event_record_json: Value,
+    pub attempts: i32,
+    pub final_error: Option<String>,
+    pub queued_at: OffsetDateTime,
+    pub dead_lettered_at: OffsetDateTime,
+}
+
+impl DeadLetterRow {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "queueEventId": self.queue_event_id,
+            "recipientDid": self.recipient_did,
+            "actorDid": self.actor_did,
+            "notificationType": self.notification_type,
+            "eventPath": self.event_path,
+            "subjectUri": self.subject_uri,
+            "eventRecord": self.event_record_json,
+            "attempts": self.attempts,
+            "finalError": self.final_error,
+            "queuedAt": rfc3339(self.queued_at),
+            "deadLetteredAt": rfc3339(self.dead_lettered_at),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayOutcome {
+    pub replayed: u64,
+    /// Matching letters too old to deliver, left where they are.
+    pub skipped_stale: u64,
+}
+
+#[derive(Clone)]
+pub struct DeadLetterQueue {
+    db_pool: Pool<Postgres>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(db_pool: Pool<Postgres>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Matching dead letters, newest first, below the `before_id` cursor.
+    pub async fn list(
+        &self,
+        filter: &DeadLetterFilter,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<DeadLetterRow>> {
+        let sql = format!(
+            r#"
+            SELECT
+                id,
+                queue_event_id,
+                recipient_did,
+                actor_did,
+                notification_type,
+                event_path,
+                subject_uri,
+                event_record_json,
+                attempts,
+                final_error,
+                queued_at,
+                dead_lettered_at
+            FROM push_event_dead_letter
+            WHERE {FILTER_CLAUSE}
+              AND ($5::bigint IS NULL OR id < $5)
+            ORDER BY id DESC
+            LIMIT $6
+            "#
+        );
+        let rows = sqlx::query_as::<_, DeadLetterRow>(&sql)
+            .bind(filter.ids.as_deref())
+            .bind(filter.did.as_deref())
+            .bind(filter.notification_type.as_deref())
+            .bind(filter.error.as_deref())
+            .bind(before_id)
+            .bind(limit)
+            .fetch_all(&self.db_pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    /// Puts matching dead letters back on the queue with fresh attempts,
+    /// keeping the time they were first queued. Events whose dedupe key is
+    /// already queued are dropped rather than duplicated; ones older than
+    /// the worker would deliver stay dead-lettered and are counted as
+    /// skipped.
+    pub async fn replay(&self, filter: &DeadLetterFilter) -> Result<ReplayOutcome> {
+        let sql = format!(
+            r#"
+            WITH replayed AS (
+                DELETE FROM push_event_dead_letter
+                WHERE {FILTER_CLAUSE}
+                  AND queued_at > NOW() - make_interval(hours => $5)
+                RETURNING *
+            )
+            INSERT INTO push_event_queue (
+                recipient_did,
+                actor_did,
+                notification_type,
+                event_cid,
+                event_path,
+                subject_uri,
+                thread_root_uri,
+                event_record_json,
+                event_timestamp,
+                dedupe_key,
+                quiet_deferred,
+                badge_counted,
+                created_at
+            )
+            SELECT
+                recipient_did,
+                actor_did,
+                notification_type,
+                event_cid,
+                event_path,
+                subject_uri,
+                thread_root_uri,
+                event_record_json,
+                event_timestamp,
+                dedupe_key,
+                quiet_deferred,
+                badge_counted,
+                queued_at
+            FROM replayed
+            ON CONFLICT (dedupe_key) DO NOTHING
+            "#
+        );
+        let result = sqlx::query(&sql)
+            .bind(filter.ids.as_deref())
+            .bind(filter.did.as_deref())
+            .bind(filter.notification_type.as_deref())
+            .bind(filter.error.as_deref())
+            .bind(MAX_EVENT_AGE_HOURS)
+            .execute(&self.db_pool)
+            .await?;
+
+        let stale_sql = format!(
+            r#"
+            SELECT COUNT(*)
+            FROM push_event_dead_letter
+            WHERE {FILTER_CLAUSE}
+              AND queued_at <= NOW() - make_interval(hours => $5)
+            "#
+        );
+        let skipped_stale = sqlx::query_scalar::<_, i64>(&stale_sql)
+            .bind(filter.ids.as_deref())
+            .bind(filter.did.as_deref())
+            .bind(filter.notification_type.as_deref())
+            .bind(filter.error.as_deref())
+            .bind(MAX_EVENT_AGE_HOURS)
+            .fetch_one(&self.db_pool)
+            .await?;
+
+        Ok(ReplayOutcome {
+            replayed: result.rows_affected(),
+            skipped_stale: skipped_stale.max(0) as u64,
+        })
+    }
+
+    /// Deletes matching dead letters. Returns the number removed.
+    pub async fn purge(&self, filter: &DeadLetterFilter) -> Result<u64> {
+        let sql = format!("DELETE FROM push_event_dead_letter WHERE {FILTER_CLAUSE}");
+        let result = sqlx::query(&sql)
+            .bind(filter.ids.as_deref())
+            .bind(filter.did.as_deref())
+            .bind(filter.notification_type.as_deref())
+            .bind(filter.error.as_deref())
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn count(&self) -> Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM push_event_dead_letter")
+            .fetch_one(&self.db_pool)
+            .await?;
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_reads_admin_request_fields() {
+        let filter: DeadLetterFilter = serde_json::from_value(json!({
+            "ids": [3, 4],
+            "type": "mention",
+            "error": "timed out"
+        }))
+        .unwrap();
+
+        assert_eq!(filter.ids, Some(vec![3, 4]));
+        assert_eq!(filter.notification_type.as_deref(), Some("mention"));
+        assert_eq!(filter.error.as_deref(), Some("timed out"));
+        assert!(filter.did.is_none());
+        assert!(!filter.is_empty());
+        assert!(DeadLetterFilter::default().is_empty());
+    }
+}
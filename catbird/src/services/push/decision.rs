@@ -1,15 +1,22 @@
 use anyhow::Result;
-use reqwest::Url;
-use serde_json::Value;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::sync::Arc;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::config::AppState;
 
 use super::{
-    apns::ApnsNotification,
+    chat_preview,
+    labels::{verdict, LabelVerdict},
+    localization::{self, LocalizedText, DEFAULT_LOCALE},
+    muted_words::{has_muted_word, MutedWord},
+    notification_kind::NotificationKind,
+    quiet_hours::QuietHoursMode,
+    rich_content::{self, RichContent},
+    transport::PushNotification,
     types::{PushPreferencesDocument, QueueRow, RegistrationRow},
-    PushServices,
+    with_step_timeout, PushServices, HYDRATION_TIMEOUT, MODERATION_SYNC_TIMEOUT,
 };
 
 #[derive(Clone, Default)]
@@ -17,7 +24,11 @@ pub struct PushDecisionEngine;
 
 pub enum QueueDisposition {
     Drop(&'static str),
-    Deliver(Vec<(RegistrationRow, ApnsNotification)>),
+    Deliver(Vec<(RegistrationRow, PushNotification)>),
+    /// Hold for a grouped summary; carries the resolved actor label.
+    Coalesce(String),
+    /// Recipient is in quiet hours; retry after this many seconds.
+    Defer(i64),
 }
 
 impl PushDecisionEngine {
@@ -39,18 +50,39 @@ impl PushDecisionEngine {
             return Ok(QueueDisposition::Drop("no_active_registrations"));
         }
 
-        services
-            .moderation_cache
-            .ensure_fresh(state, &row.recipient_did)
-            .await?;
+        let registration_count = registrations.len();
 
         let prefs = services
             .preferences
             .get_or_create(&row.recipient_did)
             .await?;
-        if !prefs.is_push_enabled_for(&row.notification_type) {
+        // Each device gets the account preferences with its own overrides.
+        let mut devices: Vec<(RegistrationRow, PushPreferencesDocument)> = registrations
+            .into_iter()
+            .map(|registration| {
+                let device_prefs = prefs.with_overrides(registration.preference_overrides.as_ref());
+                (registration, device_prefs)
+            })
+            .filter(|(_, device_prefs)| device_prefs.is_push_enabled_for(row.notification_type))
+            .collect();
+        if devices.is_empty() {
             return Ok(QueueDisposition::Drop("preferences_disabled"));
         }
+        let follows_only = devices
+            .iter()
+            .any(|(_, device_prefs)| device_prefs.is_follows_only_for(row.notification_type));
+
+        // Mutes, blocks and lists must be current, so a sync that can't
+        // finish in time retries the event. A stale follow graph is refreshed
+        // in the background instead and `is_following` reads the cached one.
+        with_step_timeout(
+            "moderation_sync",
+            MODERATION_SYNC_TIMEOUT,
+            services
+                .moderation_cache
+                .ensure_fresh(state, &row.recipient_did, follows_only),
+        )
+        .await??;
 
         if services
             .moderation_cache
@@ -68,6 +100,20 @@ impl PushDecisionEngine {
             return Ok(QueueDisposition::Drop("list_filtered"));
         }
 
+        if follows_only
+            && !services
+                .moderation_cache
+                .is_following(&row.recipient_did, &row.actor_did)
+                .await?
+        {
+            devices.retain(|(_, device_prefs)| {
+                !device_prefs.is_follows_only_for(row.notification_type)
+            });
+            if devices.is_empty() {
+                return Ok(QueueDisposition::Drop("actor_not_followed"));
+            }
+        }
+
         if let Some(thread_root_uri) = row.thread_root_uri.as_deref() {
             if services
                 .moderation_cache
@@ -78,88 +124,221 @@ impl PushDecisionEngine {
             }
         }
 
-        let actor_label = if row.notification_type == "chat_message" {
+        let now = Utc::now();
+        let muted_words = services
+            .moderation_cache
+            .muted_words(&row.recipient_did)
+            .await?;
+        if !muted_words.is_empty() {
+            let actor_followed = if muted_words.iter().any(MutedWord::excludes_following) {
+                services
+                    .moderation_cache
+                    .is_following(&row.recipient_did, &row.actor_did)
+                    .await?
+            } else {
+                false
+            };
+            if has_muted_word(&muted_words, &row.event_record_json, actor_followed, now) {
+                return Ok(QueueDisposition::Drop("muted_word"));
+            }
+        }
+
+        let (label_verdict, restricted_verdict) =
+            if row.notification_type == NotificationKind::ChatMessage {
+                (LabelVerdict::Deliver, LabelVerdict::Deliver)
+            } else {
+                self.label_verdicts(state, services, row, now).await?
+            };
+        let devices: Vec<(RegistrationRow, PushPreferencesDocument, LabelVerdict)> = devices
+            .into_iter()
+            .map(|(registration, device_prefs)| {
+                let verdict = if registration.age_restricted {
+                    restricted_verdict
+                } else {
+                    label_verdict
+                };
+                (registration, device_prefs, verdict)
+            })
+            .filter(|(_, _, verdict)| *verdict != LabelVerdict::Drop)
+            .collect();
+        if devices.is_empty() {
+            return Ok(QueueDisposition::Drop("labeled_content"));
+        }
+
+        let quiet_until = prefs
+            .quiet_hours
+            .active_until(now)
+            .filter(|_| !prefs.quiet_hours.bypasses(row.notification_type));
+        if let Some(until) = quiet_until {
+            if prefs.quiet_hours.mode == QuietHoursMode::Defer {
+                return Ok(QueueDisposition::Defer((until - now).num_seconds()));
+            }
+        }
+
+        let profile = if row.notification_type == NotificationKind::ChatMessage {
             None
         } else {
-            Some(resolve_actor_label(&state.http_client, &row.actor_did).await)
+            with_step_timeout(
+                "hydration",
+                HYDRATION_TIMEOUT,
+                services.profiles.get(&state.http_client, &row.actor_did),
+            )
+            .await
+            .unwrap_or_else(|err| {
+                tracing::debug!(actor = %row.actor_did, error = %err, "Actor profile lookup abandoned");
+                None
+            })
         };
+        let actor_label = profile.as_ref().and_then(|profile| profile.label.clone());
+
+        // A group's summary goes to every device, so only coalesce when the
+        // labels and each device's preferences treat them all alike.
+        let every_device_groups = devices.len() == registration_count
+            && devices
+                .iter()
+                .all(|(_, device_prefs, _)| services.coalescer.applies_to(row, device_prefs));
+        if label_verdict == restricted_verdict && every_device_groups {
+            let actor_label = actor_label.unwrap_or_else(|| fallback_actor_label(&row.actor_did));
+            return Ok(QueueDisposition::Coalesce(actor_label));
+        }
 
-        let notification = build_notification(row, &prefs, actor_label.as_deref());
-        let deliveries = registrations
+        let mut notification = build_notification(row, &prefs, actor_label.as_deref());
+        let parent_text = match rich_content::reply_parent_uri(&row.event_record_json) {
+            Some(parent_uri) if is_reply(row.notification_type) => with_step_timeout(
+                "hydration",
+                HYDRATION_TIMEOUT,
+                services.parent_posts.text(&state.http_client, parent_uri),
+            )
+            .await
+            .and_then(|text| text)
+            .unwrap_or_else(|err| {
+                tracing::debug!(parent = %parent_uri, error = %err, "Reply parent lookup failed");
+                None
+            }),
+            _ => None,
+        };
+        let rich = rich_content_for(
+            row,
+            profile.and_then(|profile| profile.avatar_url),
+            parent_text,
+        );
+        if !rich.is_empty() {
+            // Lets the iOS service extension attach media and context.
+            notification.rich = Some(rich);
+            notification.mutable_content = true;
+        }
+        notification.silent = quiet_until.is_some();
+        let legacy_preview = if row.notification_type == NotificationKind::ChatMessage
+            && state.config.push.legacy_chat_previews
+            && devices
+                .iter()
+                .any(|(registration, _, _)| registration.preview_key.is_none())
+        {
+            with_step_timeout(
+                "hydration",
+                HYDRATION_TIMEOUT,
+                chat_preview::legacy_preview(&state.redis, &row.event_record_json),
+            )
+            .await
+            .and_then(|preview| preview)
+            .unwrap_or_else(|err| {
+                tracing::debug!(error = %err, "Chat preview lookup failed");
+                None
+            })
+        } else {
+            None
+        };
+        let redacted = redact_notification(&notification);
+        let deliveries = devices
             .into_iter()
-            .map(|registration| (registration, notification.clone()))
+            .map(|(registration, _, verdict)| match verdict {
+                LabelVerdict::Redact => (registration, redacted.clone()),
+                _ => {
+                    let notification = with_chat_preview(
+                        row,
+                        &registration,
+                        &notification,
+                        legacy_preview.as_deref(),
+                    );
+                    (registration, notification)
+                }
+            })
             .collect();
 
         Ok(QueueDisposition::Deliver(deliveries))
     }
-}
 
-async fn resolve_actor_label(http_client: &reqwest::Client, actor_did: &str) -> String {
-    match fetch_actor_label(http_client, actor_did).await {
-        Ok(Some(label)) => label,
-        Ok(None) => fallback_actor_label(actor_did),
-        Err(err) => {
-            tracing::debug!(
-                actor = %actor_did,
-                error = %err,
-                "Failed to hydrate push notification actor profile"
-            );
-            fallback_actor_label(actor_did)
+    /// Label verdicts for the actor and the records the event touches, for
+    /// regular and `age_restricted` registrations respectively. A labeler
+    /// that can't be reached redacts rather than risk showing hidden text,
+    /// and drops for `age_restricted` registrations, which fail closed.
+    async fn label_verdicts(
+        &self,
+        state: &Arc<AppState>,
+        services: &PushServices,
+        row: &QueueRow,
+        now: DateTime<Utc>,
+    ) -> Result<(LabelVerdict, LabelVerdict)> {
+        let label_prefs = services
+            .moderation_cache
+            .label_preferences(&row.recipient_did)
+            .await?;
+        let mut subjects = vec![
+            row.actor_did.clone(),
+            format!("at://{}/{}", row.actor_did, row.event_path),
+        ];
+        subjects.extend(row.subject_uri.clone());
+
+        let labels = with_step_timeout(
+            "labels",
+            HYDRATION_TIMEOUT,
+            services
+                .labels
+                .labels_for(&state.http_client, &label_prefs.sources(), &subjects),
+        )
+        .await
+        .and_then(|labels| labels);
+        match labels {
+            Ok(labels) => Ok((
+                verdict(&labels, &label_prefs, false, now),
+                verdict(&labels, &label_prefs, true, now),
+            )),
+            Err(err) => {
+                tracing::debug!(
+                    recipient = %row.recipient_did,
+                    actor = %row.actor_did,
+                    error = %err,
+                    "Label lookup failed; redacting push notification"
+                );
+                Ok((LabelVerdict::Redact, LabelVerdict::Drop))
+            }
         }
     }
 }
 
-async fn fetch_actor_label(
-    http_client: &reqwest::Client,
-    actor_did: &str,
-) -> Result<Option<String>> {
-    let mut url = Url::parse("https://public.api.bsky.app/xrpc/app.bsky.actor.getProfile")?;
-    url.query_pairs_mut().append_pair("actor", actor_did);
-
-    let response = http_client.get(url).send().await?;
-    if !response.status().is_success() {
-        tracing::debug!(
-            actor = %actor_did,
-            status = %response.status(),
-            "Public AppView profile lookup failed for push notification actor"
-        );
-        return Ok(None);
-    }
-
-    let payload: Value = response.json().await?;
-    Ok(actor_label_from_profile_json(&payload))
-}
-
-fn actor_label_from_profile_json(profile: &Value) -> Option<String> {
-    profile
-        .get("displayName")
-        .and_then(Value::as_str)
-        .and_then(clean_profile_label)
-        .or_else(|| {
-            profile
-                .get("handle")
-                .and_then(Value::as_str)
-                .and_then(clean_profile_label)
-                .map(|handle| {
-                    if handle.starts_with('@') {
-                        handle
-                    } else {
-                        format!("@{handle}")
-                    }
-                })
-        })
-}
-
-fn clean_profile_label(value: &str) -> Option<String> {
-    let collapsed = value.split_whitespace().collect::<Vec<_>>().join(" ");
-    if collapsed.is_empty() {
-        None
-    } else {
-        Some(truncate(&collapsed, 80))
+/// Strips record text from a notification whose content the recipient's
+/// labelers put behind a warning.
+fn redact_notification(notification: &PushNotification) -> PushNotification {
+    let mut redacted = notification.clone();
+    let actor_label = redacted
+        .custom_data
+        .get("actorLabel")
+        .cloned()
+        .unwrap_or_else(|| "Someone".to_string());
+    let body_args = vec![actor_label];
+    redacted.body = localization::render(DEFAULT_LOCALE, "PUSH_CONTENT_WARNING_BODY", &body_args);
+    if let Some(text) = redacted.localization.as_mut() {
+        text.body_key = "PUSH_CONTENT_WARNING_BODY";
+        text.body_args = body_args;
     }
+    redacted.rich = None;
+    redacted
+        .custom_data
+        .insert("contentWarning".to_string(), "true".to_string());
+    redacted
 }
 
-fn build_chat_notification(row: &QueueRow) -> ApnsNotification {
+fn build_chat_notification(row: &QueueRow) -> PushNotification {
     let convo_id = row
         .event_record_json
         .get("convoId")
@@ -172,60 +351,84 @@ fn build_chat_notification(row: &QueueRow) -> ApnsNotification {
         .unwrap_or_default();
 
     // messageText is stripped before the event is persisted (see
-    // `enqueue_push`), so this durable/fallback path never has a preview to
-    // show — only the fast Redis pub/sub path (mod.rs) carries the real text,
-    // and only for its single immediate delivery attempt.
+    // `enqueue_push`); `with_chat_preview` adds an encrypted preview for
+    // devices with a preview key, and the short-lived plaintext one for the
+    // rest while it lasts.
     let mut custom_data = HashMap::new();
-    custom_data.insert("type".to_string(), "chat_message".to_string());
+    custom_data.insert(
+        "type".to_string(),
+        NotificationKind::ChatMessage.as_str().to_string(),
+    );
     custom_data.insert("recipientDid".to_string(), row.recipient_did.clone());
     custom_data.insert("convoId".to_string(), convo_id.to_string());
     custom_data.insert("messageId".to_string(), message_id.to_string());
     custom_data.insert("senderDid".to_string(), row.actor_did.clone());
 
-    ApnsNotification {
-        title: "New Message".to_string(),
-        body: "You have a new message".to_string(),
+    let text = LocalizedText::new(
+        NotificationKind::ChatMessage.title_key(),
+        "PUSH_CHAT_BODY",
+        Vec::new(),
+    );
+    PushNotification {
+        title: text.title(DEFAULT_LOCALE),
+        body: text.body(DEFAULT_LOCALE),
+        localization: Some(text),
         user_did: row.recipient_did.clone(),
         custom_data,
         mutable_content: true,
+        rich: None,
         thread_id: Some(format!("chat:{}", convo_id)),
+        collapse_id: None,
+        silent: false,
+        content_available: false,
+        badge: None,
     }
 }
 
+/// Adds the chat preview encrypted for `registration`, which its
+/// Notification Service Extension swaps in for the generic body, or for a
+/// device without a preview key the plaintext `legacy_preview`.
+fn with_chat_preview(
+    row: &QueueRow,
+    registration: &RegistrationRow,
+    notification: &PushNotification,
+    legacy_preview: Option<&str>,
+) -> PushNotification {
+    let mut notification = notification.clone();
+    if row.notification_type == NotificationKind::ChatMessage {
+        if let Some(preview) = chat_preview::preview_for(&row.event_record_json, registration) {
+            notification
+                .custom_data
+                .insert("encryptedPreview".to_string(), preview.to_string());
+        } else if let Some(text) = legacy_preview.filter(|_| registration.preview_key.is_none()) {
+            notification.custom_data.insert(
+                chat_preview::LEGACY_PREVIEW_KEY.to_string(),
+                text.to_string(),
+            );
+        }
+    }
+    notification
+}
+
 fn build_notification(
     row: &QueueRow,
     _prefs: &PushPreferencesDocument,
     actor_label: Option<&str>,
-) -> ApnsNotification {
-    if row.notification_type == "chat_message" {
+) -> PushNotification {
+    if row.notification_type == NotificationKind::ChatMessage {
         return build_chat_notification(row);
     }
     let actor_label = actor_label
         .map(str::to_string)
         .unwrap_or_else(|| fallback_actor_label(&row.actor_did));
 
-    let title = match row.notification_type.as_str() {
-        "mention" => "New mention",
-        "reply" => "New reply",
-        "like" => "New like",
-        "follow" => "New follower",
-        "repost" => "New repost",
-        "quote" => "New quote",
-        "via_like" => "New like via repost",
-        "via_repost" => "New repost via repost",
-        "activity_post" => "New post",
-        "activity_reply" => "New reply",
-        _ => "New notification",
-    }
-    .to_string();
-
-    let body = match row.notification_type.as_str() {
-        "follow" => format!("{} followed you", actor_label),
-        "like" => format!("{} liked your post", actor_label),
-        "repost" => format!("{} reposted your post", actor_label),
-        "via_like" => format!("{} liked a reposted post", actor_label),
-        "via_repost" => format!("{} reposted a reposted post", actor_label),
-        _ => {
+    let text = match row.notification_type.action_key() {
+        Some(action_key) => LocalizedText::new(
+            row.notification_type.title_key(),
+            action_key,
+            vec![actor_label.clone()],
+        ),
+        None => {
             let text = row
                 .event_record_json
                 .get("text")
@@ -234,16 +437,23 @@ fn build_notification(
                 .trim()
                 .to_string();
 
-            if text.is_empty() {
-                format!("From {}", actor_label)
+            let (body_key, body_args) = if text.is_empty() {
+                ("PUSH_FROM_BODY", vec![actor_label.clone()])
             } else {
-                format!("{}: {}", actor_label, truncate(&text, 160))
-            }
+                (
+                    "PUSH_TEXT_BODY",
+                    vec![actor_label.clone(), truncate(&text, 160)],
+                )
+            };
+            LocalizedText::new(row.notification_type.title_key(), body_key, body_args)
         }
     };
 
     let mut custom_data = HashMap::new();
-    custom_data.insert("reason".to_string(), row.notification_type.clone());
+    custom_data.insert(
+        "reason".to_string(),
+        row.notification_type.as_str().to_string(),
+    );
     custom_data.insert("actorDid".to_string(), row.actor_did.clone());
     custom_data.insert("actorLabel".to_string(), actor_label);
     custom_data.insert("eventCid".to_string(), row.event_cid.clone());
@@ -259,13 +469,46 @@ fn build_notification(
         custom_data.insert("threadRootUri".to_string(), thread_root_uri.clone());
     }
 
-    ApnsNotification {
-        title,
-        body,
+    PushNotification {
+        title: text.title(DEFAULT_LOCALE),
+        body: text.body(DEFAULT_LOCALE),
+        localization: Some(text),
         user_did: row.recipient_did.clone(),
         custom_data,
         mutable_content: false,
+        rich: None,
         thread_id: None,
+        collapse_id: None,
+        silent: false,
+        content_available: false,
+        badge: None,
+    }
+}
+
+fn is_reply(kind: NotificationKind) -> bool {
+    matches!(
+        kind,
+        NotificationKind::Reply | NotificationKind::ActivityReply
+    )
+}
+
+/// Rich content for a notification. Embed media only comes from post
+/// records; likes, reposts and follows carry just the avatar.
+fn rich_content_for(
+    row: &QueueRow,
+    avatar_url: Option<String>,
+    parent_text: Option<String>,
+) -> RichContent {
+    let is_post = row.notification_type.action_key().is_none()
+        && row.notification_type != NotificationKind::ChatMessage;
+    RichContent {
+        avatar_url,
+        media: if is_post {
+            rich_content::media_from_record(&row.actor_did, &row.event_record_json)
+        } else {
+            Vec::new()
+        },
+        parent_text: parent_text.map(|text| truncate(&text, 160)),
     }
 }
 
@@ -277,11 +520,10 @@ fn fallback_actor_label(actor_did: &str) -> String {
     }
 }
 
+/// Cuts `value` to at most `max_len` user-perceived characters, so emoji
+/// sequences and combining marks are never split.
 fn truncate(value: &str, max_len: usize) -> String {
-    if value.chars().count() <= max_len {
-        return value.to_string();
-    }
-    value.chars().take(max_len).collect::<String>()
+    value.graphemes(true).take(max_len).collect()
 }
 
 #[cfg(test)]
@@ -290,12 +532,12 @@ mod tests {
     use serde_json::json;
     use time::OffsetDateTime;
 
-    fn queue_row(notification_type: &str) -> QueueRow {
+    fn queue_row(notification_type: NotificationKind) -> QueueRow {
         QueueRow {
             id: 1,
             recipient_did: "did:plc:recipient".to_string(),
             actor_did: "did:plc:alice123".to_string(),
-            notification_type: notification_type.to_string(),
+            notification_type,
             event_cid: "bafy-cid".to_string(),
             event_path: "app.bsky.feed.like/3abc".to_string(),
             subject_uri: None,
@@ -304,58 +546,248 @@ mod tests {
             event_timestamp: 1_771_234_567,
             created_at: OffsetDateTime::now_utc(),
             attempts: 0,
+            quiet_deferred: false,
+            badge_counted: false,
         }
     }
 
     #[test]
-    fn profile_label_prefers_display_name_over_handle() {
-        let profile = json!({
-            "did": "did:plc:alice123",
-            "handle": "alice.example",
-            "displayName": "Alice Example"
-        });
+    fn social_notification_body_uses_hydrated_actor_label() {
+        let row = queue_row(NotificationKind::Like);
 
-        assert_eq!(
-            actor_label_from_profile_json(&profile).as_deref(),
-            Some("Alice Example")
+        let notification = build_notification(
+            &row,
+            &PushPreferencesDocument::default(),
+            Some("Alice Example"),
         );
+
+        assert_eq!(notification.body, "Alice Example liked your post");
+        assert!(!notification.body.contains("did:plc"));
     }
 
     #[test]
-    fn profile_label_falls_back_to_handle_when_display_name_is_blank() {
-        let profile = json!({
-            "did": "did:plc:alice123",
-            "handle": "alice.example",
-            "displayName": "   "
+    fn redacted_notification_drops_record_text() {
+        let row = queue_row(NotificationKind::Mention);
+        let notification = build_notification(
+            &row,
+            &PushPreferencesDocument::default(),
+            Some("Alice Example"),
+        );
+
+        let redacted = redact_notification(&notification);
+
+        assert_eq!(redacted.body, "From Alice Example (content warning)");
+        assert_eq!(redacted.custom_data["contentWarning"], "true");
+        assert!(notification.body.contains("hello from the post"));
+        let translated = redacted.for_locale(Some("de-DE"));
+        assert_eq!(translated.body, "Von Alice Example (Inhaltswarnung)");
+        assert!(!translated.body.contains("hello from the post"));
+    }
+
+    #[test]
+    fn rich_content_carries_post_media_and_reply_context() {
+        let mut row = queue_row(NotificationKind::Reply);
+        row.event_record_json = json!({
+            "text": "look",
+            "embed": {
+                "$type": "app.bsky.embed.images",
+                "images": [{ "image": { "ref": { "$link": "bafyimage" } }, "alt": "" }],
+            },
         });
 
-        assert_eq!(
-            actor_label_from_profile_json(&profile).as_deref(),
-            Some("@alice.example")
+        let rich = rich_content_for(
+            &row,
+            Some("https://cdn.bsky.app/avatar".to_string()),
+            Some("p".repeat(500)),
+        );
+        assert_eq!(rich.media.len(), 1);
+        assert_eq!(rich.parent_text.as_deref().map(str::len), Some(160));
+
+        row.notification_type = NotificationKind::Like;
+        let rich = rich_content_for(&row, None, None);
+        assert!(rich.is_empty());
+
+        let mut notification = build_notification(
+            &queue_row(NotificationKind::Reply),
+            &PushPreferencesDocument::default(),
+            Some("Alice"),
         );
+        notification.rich = Some(RichContent {
+            parent_text: Some("secret".to_string()),
+            ..RichContent::default()
+        });
+        assert_eq!(redact_notification(&notification).rich, None);
     }
 
     #[test]
-    fn social_notification_body_uses_hydrated_actor_label() {
-        let row = queue_row("like");
-
+    fn notifications_render_in_the_device_locale() {
         let notification = build_notification(
-            &row,
+            &queue_row(NotificationKind::Reply),
             &PushPreferencesDocument::default(),
-            Some("Alice Example"),
+            Some("Alice"),
         );
 
-        assert_eq!(notification.body, "Alice Example liked your post");
-        assert!(!notification.body.contains("did:plc"));
+        let english = notification.for_locale(None);
+        assert_eq!(english.body, "Alice: hello from the post");
+        let spanish = notification.for_locale(Some("es-MX"));
+        assert_eq!(spanish.title, "Nueva respuesta");
+        assert_eq!(spanish.body, "Alice: hello from the post");
+        let french = notification.for_locale(Some("fr"));
+        assert_eq!(french.body, "Alice : hello from the post");
+        let unknown = notification.for_locale(Some("ko"));
+        assert_eq!(unknown.title, "New reply");
+    }
+
+    #[test]
+    fn truncate_keeps_grapheme_clusters_whole() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello", 3), "hel");
+        // Family emoji: four code points joined by ZWJs, one grapheme.
+        assert_eq!(
+            truncate("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}ab", 1),
+            "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"
+        );
+        assert_eq!(truncate("e\u{301}te\u{301}", 2), "e\u{301}t");
     }
 
     #[test]
     fn social_notification_body_avoids_raw_did_when_label_missing() {
-        let row = queue_row("repost");
+        let row = queue_row(NotificationKind::Repost);
 
         let notification = build_notification(&row, &PushPreferencesDocument::default(), None);
 
         assert_eq!(notification.body, "Someone reposted your post");
         assert!(!notification.body.contains("did:plc"));
     }
+
+    #[test]
+    fn every_kind_maps_to_its_title_body_and_reason() {
+        let cases = [
+            (
+                NotificationKind::Mention,
+                "New mention",
+                "Alice: hello from the post",
+            ),
+            (
+                NotificationKind::Reply,
+                "New reply",
+                "Alice: hello from the post",
+            ),
+            (NotificationKind::Like, "New like", "Alice liked your post"),
+            (
+                NotificationKind::Follow,
+                "New follower",
+                "Alice followed you",
+            ),
+            (
+                NotificationKind::Repost,
+                "New repost",
+                "Alice reposted your post",
+            ),
+            (
+                NotificationKind::Quote,
+                "New quote",
+                "Alice: hello from the post",
+            ),
+            (
+                NotificationKind::LikeViaRepost,
+                "New like via repost",
+                "Alice liked a reposted post",
+            ),
+            (
+                NotificationKind::RepostViaRepost,
+                "New repost via repost",
+                "Alice reposted a reposted post",
+            ),
+            (
+                NotificationKind::ActivityPost,
+                "New post",
+                "Alice: hello from the post",
+            ),
+            (
+                NotificationKind::ActivityReply,
+                "New reply",
+                "Alice: hello from the post",
+            ),
+            (
+                NotificationKind::StarterpackJoined,
+                "Starter pack joined",
+                "Alice joined Bluesky with your starter pack",
+            ),
+            (
+                NotificationKind::Verified,
+                "You're verified",
+                "Alice verified your account",
+            ),
+            (
+                NotificationKind::Unverified,
+                "Verification removed",
+                "Alice removed your verification",
+            ),
+            (
+                NotificationKind::ChatMessage,
+                "New Message",
+                "You have a new message",
+            ),
+        ];
+        assert_eq!(cases.len(), NotificationKind::ALL.len());
+
+        for (kind, title, body) in cases {
+            let notification = build_notification(
+                &queue_row(kind),
+                &PushPreferencesDocument::default(),
+                Some("Alice"),
+            );
+            assert_eq!(notification.title, title, "{kind}");
+            assert_eq!(notification.body, body, "{kind}");
+            let (key, value) = if kind == NotificationKind::ChatMessage {
+                ("type", "chat_message")
+            } else {
+                ("reason", kind.as_str())
+            };
+            assert_eq!(notification.custom_data[key], value, "{kind}");
+        }
+    }
+
+    #[test]
+    fn chat_notifications_carry_each_devices_encrypted_preview() {
+        let registration = |id: u128| RegistrationRow {
+            id: sqlx::types::Uuid::from_u128(id),
+            did: "did:plc:recipient".to_string(),
+            device_token: format!("token-{id}"),
+            platform: "ios".to_string(),
+            app_id: "blue.catbird".to_string(),
+            service_did: None,
+            age_restricted: false,
+            is_active: true,
+            apns_environment: None,
+            locale: None,
+            preference_overrides: None,
+            preview_key: None,
+        };
+        let (mut keyed, keyless) = (registration(1), registration(2));
+        keyed.preview_key = Some("device-key".to_string());
+        let mut row = queue_row(NotificationKind::ChatMessage);
+        row.event_record_json = json!({
+            "convoId": "convo1",
+            "messageId": "msg1",
+            "encryptedPreviews": { keyed.id.to_string(): "sealed" },
+        });
+        let notification = build_notification(&row, &PushPreferencesDocument::default(), None);
+
+        let with_preview = with_chat_preview(&row, &keyed, &notification, Some("hi"));
+        assert_eq!(with_preview.custom_data["encryptedPreview"], "sealed");
+        assert!(!with_preview.custom_data.contains_key("messageText"));
+        assert_eq!(with_preview.body, "You have a new message");
+        let without = with_chat_preview(&row, &keyless, &notification, None);
+        assert!(!without.custom_data.contains_key("encryptedPreview"));
+        assert!(!without.custom_data.contains_key("messageText"));
+        let legacy = with_chat_preview(&row, &keyless, &notification, Some("hi"));
+        assert_eq!(legacy.custom_data["messageText"], "hi");
+
+        row.notification_type = NotificationKind::Mention;
+        let mention = with_chat_preview(&row, &keyed, &notification, Some("hi"));
+        assert!(!mention.custom_data.contains_key("encryptedPreview"));
+        assert!(!mention.custom_data.contains_key("messageText"));
+    }
 }
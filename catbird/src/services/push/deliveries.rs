@@ -0,0 +1,354 @@
+//! Push delivery log (`push_deliveries`).
+//!
+//! Queue rows are deleted once handled, so this is the only record of what
+//! happened to an event: every decision that didn't end in a send, and every
+//! transport attempt with the device, APNs environment, provider message id,
+//! status, and latency. Users read their own history through
+//! `blue.catbird.push.getDeliveryLog`; operators query by DID on the admin
+//! port. Writes are best-effort and never hold up delivery.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use sqlx::{FromRow, Pool, Postgres};
+use time::OffsetDateTime;
+
+use super::{
+    apns::ApnsError,
+    transport::{provider_response, DeliveryError, DeliveryReceipt},
+    types::{QueueRow, RegistrationRow},
+    StepTimeout,
+};
+
+/// Longest decision reason kept per row.
+const MAX_REASON_LEN: usize = 500;
+
+/// The event a log row is about.
+#[derive(Debug, Clone, Copy)]
+pub struct LoggedEvent<'a> {
+    pub recipient_did: &'a str,
+    pub notification_type: &'a str,
+    /// `None` for pushes that never had a queue row (coalesced summaries).
+    pub queue_event_id: Option<i64>,
+}
+
+impl<'a> From<&'a QueueRow> for LoggedEvent<'a> {
+    fn from(row: &'a QueueRow) -> Self {
+        Self {
+            recipient_did: &row.recipient_did,
+            notification_type: row.notification_type.as_str(),
+            queue_event_id: Some(row.id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct DeliveryLogEntry {
+    pub id: i64,
+    pub recipient_did: String,
+    pub queue_event_id: Option<i64>,
+    pub notification_type: String,
+    /// `dropped`, `deferred`, `coalesced`, `error`, `delivered`,
+    /// `invalid_token`, or `failed`.
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub device_id: Option<sqlx::types::Uuid>,
+    pub platform: Option<String>,
+    pub apns_environment: Option<String>,
+    pub provider_id: Option<String>,
+    pub status_code: Option<i32>,
+    pub latency_ms: Option<i32>,
+    pub created_at: OffsetDateTime,
+}
+
+impl DeliveryLogEntry {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id.to_string(),
+            "notificationType": self.notification_type,
+            "outcome": self.outcome,
+            "reason": self.reason,
+            "deviceId": self.device_id.map(|id| id.to_string()),
+            "platform": self.platform,
+            "apnsEnvironment": self.apns_environment,
+            "providerId": self.provider_id,
+            "statusCode": self.status_code,
+            "latencyMs": self.latency_ms,
+            "createdAt": rfc3339(self.created_at),
+        })
+    }
+}
+
+/// `2026-02-16T09:36:07.000Z`, as lexicon `datetime` fields expect.
+pub(super) fn rfc3339(value: OffsetDateTime) -> Option<String> {
+    chrono::DateTime::from_timestamp(value.unix_timestamp(), value.nanosecond())
+        .map(|value| value.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+}
+
+/// The `reason` logged for a failed send: the transport's code for a dead
+/// token, else one of `timeout`, `network`, `throttled`, `payload_too_large`,
+/// `auth_rejected`, `provider_unavailable`, `rejected`, or `internal`.
+/// Raw errors can carry tokens and internal detail, so they only go to the
+/// server log; the status code is logged alongside.
+pub fn failure_reason(err: &DeliveryError) -> &'static str {
+    let err = match err {
+        DeliveryError::InvalidToken { reason, .. } => return *reason,
+        DeliveryError::Other(err) => err,
+    };
+    if let Some(response) = provider_response(err) {
+        return match response.status {
+            429 => "throttled",
+            413 => "payload_too_large",
+            401 | 403 => "auth_rejected",
+            status if status >= 500 => "provider_unavailable",
+            _ => "rejected",
+        };
+    }
+    if err.downcast_ref::<StepTimeout>().is_some() {
+        "timeout"
+    } else if err.downcast_ref::<reqwest::Error>().is_some()
+        || err.downcast_ref::<ApnsError>().is_some()
+    {
+        "network"
+    } else {
+        "internal"
+    }
+}
+
+#[derive(Clone)]
+pub struct DeliveryLog {
+    db_pool: Pool<Postgres>,
+    retention_days: u32,
+}
+
+impl DeliveryLog {
+    /// A `retention_days` of 0 turns the log off.
+    pub fn new(db_pool: Pool<Postgres>, retention_days: u32) -> Self {
+        Self {
+            db_pool,
+            retention_days,
+        }
+    }
+
+    /// Records a decision that didn't send anything.
+    pub async fn record_decision(&self, event: LoggedEvent<'_>, outcome: &str, reason: &str) {
+        let reason: String = reason.chars().take(MAX_REASON_LEN).collect();
+        self.insert(event, outcome, Some(&reason), None, None, None)
+            .await;
+    }
+
+    /// Records one transport attempt and its result. Failures keep the
+    /// push service's status, message id, and environment when it answered,
+    /// and a `failure_reason` code rather than the raw error.
+    pub async fn record_attempt(
+        &self,
+        event: LoggedEvent<'_>,
+        registration: &RegistrationRow,
+        result: &Result<DeliveryReceipt, DeliveryError>,
+        latency: Duration,
+    ) {
+        let (outcome, reason, response) = match result {
+            Ok(receipt) => ("delivered", None, Some(receipt.clone())),
+            Err(err @ DeliveryError::InvalidToken { .. }) => {
+                ("invalid_token", Some(failure_reason(err)), err.response())
+            }
+            Err(err @ DeliveryError::Other(_)) => {
+                ("failed", Some(failure_reason(err)), err.response())
+            }
+        };
+        self.insert(
+            event,
+            outcome,
+            reason,
+            Some(registration),
+            response.as_ref(),
+            Some(latency),
+        )
+        .await;
+    }
+
+    /// Most recent entries for `recipient_did`, newest first, starting below
+    /// the `before_id` cursor when given.
+    pub async fn recent(
+        &self,
+        recipient_did: &str,
+        limit: i64,
+        before_id: Option<i64>,
+    ) -> Result<Vec<DeliveryLogEntry>> {
+        let entries = sqlx::query_as::<_, DeliveryLogEntry>(
+            r#"
+            SELECT
+                id,
+                recipient_did,
+                queue_event_id,
+                notification_type,
+                outcome,
+                reason,
+                device_id,
+                platform,
+                apns_environment,
+                provider_id,
+                status_code,
+                latency_ms,
+                created_at
+            FROM push_deliveries
+            WHERE recipient_did = $1
+              AND ($2::bigint IS NULL OR id < $2)
+            ORDER BY id DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(recipient_did)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Deletes entries past the retention window. Returns the number removed.
+    pub async fn purge_expired(&self) -> Result<u64> {
+        if self.retention_days == 0 {
+            return Ok(0);
+        }
+        let result = sqlx::query(
+            "DELETE FROM push_deliveries WHERE created_at < NOW() - make_interval(days => $1)",
+        )
+        .bind(self.retention_days as i32)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn insert(
+        &self,
+        event: LoggedEvent<'_>,
+        outcome: &str,
+        reason: Option<&str>,
+        registration: Option<&RegistrationRow>,
+        receipt: Option<&DeliveryReceipt>,
+        latency: Option<Duration>,
+    ) {
+        if self.retention_days == 0 {
+            return;
+        }
+        let apns_environment = receipt
+            .and_then(|receipt| receipt.environment)
+            .or_else(|| registration.and_then(|r| r.apns_environment.as_deref()));
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO push_deliveries (
+                recipient_did,
+                queue_event_id,
+                notification_type,
+                outcome,
+                reason,
+                device_id,
+                platform,
+                apns_environment,
+                provider_id,
+                status_code,
+                latency_ms
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(event.recipient_did)
+        .bind(event.queue_event_id)
+        .bind(event.notification_type)
+        .bind(outcome)
+        .bind(reason)
+        .bind(registration.map(|r| r.id))
+        .bind(registration.map(|r| r.platform.as_str()))
+        .bind(apns_environment)
+        .bind(receipt.and_then(|receipt| receipt.provider_id.as_deref()))
+        .bind(receipt.map(|receipt| i32::from(receipt.status)))
+        .bind(latency.map(|latency| latency.as_millis().min(i32::MAX as u128) as i32))
+        .execute(&self.db_pool)
+        .await;
+
+        if let Err(err) = result {
+            tracing::warn!(
+                recipient = %event.recipient_did,
+                outcome,
+                error = %err,
+                "Failed to record push delivery log entry"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::push::web_push::WebPushError;
+
+    #[test]
+    fn entry_json_uses_lexicon_field_names() {
+        let entry = DeliveryLogEntry {
+            id: 42,
+            recipient_did: "did:plc:recipient".to_string(),
+            queue_event_id: Some(7),
+            notification_type: "like".to_string(),
+            outcome: "delivered".to_string(),
+            reason: None,
+            device_id: None,
+            platform: Some("ios".to_string()),
+            apns_environment: Some("production".to_string()),
+            provider_id: Some("6F8B0B52-1C1B-4B0C-9C7B-1D2E3F405060".to_string()),
+            status_code: Some(200),
+            latency_ms: Some(85),
+            created_at: OffsetDateTime::from_unix_timestamp(1_771_234_567).unwrap(),
+        };
+
+        let json = entry.to_json();
+        assert_eq!(json["id"], "42");
+        assert_eq!(json["apnsEnvironment"], "production");
+        assert_eq!(json["statusCode"], 200);
+        assert_eq!(json["createdAt"], "2026-02-16T09:36:07.000Z");
+        assert!(json.get("recipientDid").is_none());
+    }
+
+    #[test]
+    fn failures_are_logged_with_fixed_reasons() {
+        let rejected = |status| {
+            DeliveryError::Other(
+                WebPushError::Rejected {
+                    status,
+                    reason: "secret endpoint detail".to_string(),
+                }
+                .into(),
+            )
+        };
+        assert_eq!(failure_reason(&rejected(429)), "throttled");
+        assert_eq!(failure_reason(&rejected(413)), "payload_too_large");
+        assert_eq!(failure_reason(&rejected(503)), "provider_unavailable");
+        assert_eq!(failure_reason(&rejected(400)), "rejected");
+        assert_eq!(rejected(503).response().map(|r| r.status), Some(503));
+
+        let timeout = DeliveryError::Other(
+            StepTimeout {
+                step: "send",
+                seconds: 10,
+            }
+            .into(),
+        );
+        assert_eq!(failure_reason(&timeout), "timeout");
+        assert_eq!(
+            failure_reason(&DeliveryError::Other(anyhow::anyhow!("token abc123"))),
+            "internal"
+        );
+        assert_eq!(
+            failure_reason(&DeliveryError::InvalidToken {
+                reason: "fcm_unregistered",
+                detail: "token abc123".to_string(),
+                response: None,
+            }),
+            "fcm_unregistered"
+        );
+    }
+}
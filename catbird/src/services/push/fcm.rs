@@ -0,0 +1,619 @@
+//! Firebase Cloud Messaging (HTTP v1) delivery for Android clients.
+//!
+//! Android clients register with `platform: "android"` and their FCM
+//! registration token. Sends are authorised with an OAuth2 access token
+//! minted from the configured service account (a self-signed RS256 JWT
+//! exchanged at the account's `token_uri`) and cached until shortly before it
+//! expires. A `mutable_content` notification goes out as a data-only message
+//! so the app can rewrite it before display; anything else carries a
+//! `notification` block that Android renders directly. `UNREGISTERED` (and
+//! `SENDER_ID_MISMATCH`, just as final for this sender) is reported as an
+//! invalid token.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::future::BoxFuture;
+use rsa::{
+    pkcs1v15::SigningKey,
+    pkcs8::DecodePrivateKey,
+    signature::{SignatureEncoding, Signer},
+    RsaPrivateKey,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::Mutex;
+
+use crate::config::FcmConfig;
+
+use super::{
+    transport::{DeliveryError, DeliveryReceipt, PushNotification, PushTransport},
+    types::RegistrationRow,
+};
+
+/// `user_devices.platform` for FCM registrations.
+pub const PLATFORM_ANDROID: &str = "android";
+
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+/// Google accepts service-account assertions valid for at most an hour.
+const ASSERTION_LIFETIME_SECS: i64 = 3600;
+/// Mint a new access token this long before the cached one expires.
+const ACCESS_TOKEN_REFRESH_MARGIN_SECS: i64 = 300;
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// FCM's limit on a message's `notification` and `data` combined.
+const MAX_PAYLOAD_BYTES: usize = 4096;
+
+/// The fields of a Firebase service-account key file that matter here.
+#[derive(Debug, Clone, Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    #[serde(default)]
+    private_key_id: Option<String>,
+    private_key: String,
+    client_email: String,
+    token_uri: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("FCM rejected message with status {status} ({error_code}): {message}")]
+pub struct FcmError {
+    pub status: u16,
+    /// `FcmError.errorCode` from the response details, else the RPC status.
+    pub error_code: String,
+    pub message: String,
+}
+
+impl FcmError {
+    fn from_response(status: u16, body: &Value) -> Self {
+        let error = &body["error"];
+        let detail_code = error["details"].as_array().and_then(|details| {
+            details
+                .iter()
+                .find_map(|detail| detail["errorCode"].as_str())
+        });
+        Self {
+            status,
+            error_code: detail_code
+                .or_else(|| error["status"].as_str())
+                .unwrap_or("UNKNOWN")
+                .to_string(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// The registration token will never be accepted again.
+    pub fn is_unregistered(&self) -> bool {
+        match self.error_code.as_str() {
+            "UNREGISTERED" | "SENDER_ID_MISMATCH" => true,
+            // INVALID_ARGUMENT also covers malformed payloads; only a
+            // rejected token is final.
+            "INVALID_ARGUMENT" => self.message.contains("registration token"),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+struct CachedToken {
+    value: String,
+    expires_at: i64,
+}
+
+#[derive(Clone)]
+pub struct FcmDelivery {
+    http_client: reqwest::Client,
+    account: Arc<ServiceAccount>,
+    signing_key: Arc<SigningKey<Sha256>>,
+    send_url: String,
+    access_token: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl FcmDelivery {
+    pub fn new(config: &FcmConfig) -> Result<Option<Self>> {
+        let Some(path) = config.service_account_path.as_deref() else {
+            tracing::info!("FCM delivery is not configured; android registrations will be skipped");
+            return Ok(None);
+        };
+
+        let raw = std::fs::read_to_string(path)
+            .context(format!("Failed to read FCM service account file: {}", path))?;
+        let account: ServiceAccount =
+            serde_json::from_str(&raw).context("FCM service account file is not valid")?;
+
+        Self::from_service_account(account, config.project_id.as_deref(), &config.api_base_url)
+            .map(Some)
+    }
+
+    fn from_service_account(
+        account: ServiceAccount,
+        project_id: Option<&str>,
+        api_base_url: &str,
+    ) -> Result<Self> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(&account.private_key)
+            .context("FCM service account private_key is not a PKCS#8 RSA key")?;
+        let send_url = format!(
+            "{}/v1/projects/{}/messages:send",
+            api_base_url.trim_end_matches('/'),
+            project_id.unwrap_or(&account.project_id)
+        );
+        let http_client = reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?;
+
+        Ok(Self {
+            http_client,
+            account: Arc::new(account),
+            signing_key: Arc::new(SigningKey::<Sha256>::new(private_key)),
+            send_url,
+            access_token: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    pub async fn send(
+        &self,
+        registration: &RegistrationRow,
+        notification: &PushNotification,
+    ) -> Result<DeliveryReceipt> {
+        let access_token = self.access_token().await?;
+        let response = self
+            .http_client
+            .post(&self.send_url)
+            .bearer_auth(&access_token)
+            .json(&build_message(&registration.device_token, notification)?)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            return Ok(DeliveryReceipt {
+                status: status.as_u16(),
+                provider_id: body.get("name").and_then(Value::as_str).map(str::to_string),
+                environment: None,
+            });
+        }
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            // Revoked or rotated key: mint a fresh token on the retry.
+            self.access_token.lock().await.take();
+        }
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        Err(FcmError::from_response(status.as_u16(), &body).into())
+    }
+
+    /// A cached access token, or a new one from the token endpoint. The lock
+    /// is held across the exchange so concurrent sends mint only one.
+    async fn access_token(&self) -> Result<String> {
+        let mut cached = self.access_token.lock().await;
+        let now = chrono::Utc::now().timestamp();
+        if let Some(token) = cached.as_ref() {
+            if token.expires_at - ACCESS_TOKEN_REFRESH_MARGIN_SECS > now {
+                return Ok(token.value.clone());
+            }
+        }
+
+        let assertion = self.assertion(now)?;
+        let response = self
+            .http_client
+            .post(&self.account.token_uri)
+            .form(&[
+                ("grant_type", JWT_BEARER_GRANT),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "FCM access token request failed with status {}: {}",
+                status,
+                body.chars().take(200).collect::<String>()
+            ));
+        }
+
+        let token: TokenResponse = response.json().await?;
+        *cached = Some(CachedToken {
+            value: token.access_token.clone(),
+            expires_at: now + token.expires_in,
+        });
+        Ok(token.access_token)
+    }
+
+    /// Self-signed RS256 JWT for the OAuth2 JWT-bearer grant.
+    fn assertion(&self, now: i64) -> Result<String> {
+        let mut header = json!({ "alg": "RS256", "typ": "JWT" });
+        if let Some(ref kid) = self.account.private_key_id {
+            header["kid"] = Value::String(kid.clone());
+        }
+        let claims = json!({
+            "iss": self.account.client_email,
+            "scope": FCM_SCOPE,
+            "aud": self.account.token_uri,
+            "iat": now,
+            "exp": now + ASSERTION_LIFETIME_SECS,
+        });
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+        );
+        let signature = self.signing_key.sign(signing_input.as_bytes());
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+impl PushTransport for FcmDelivery {
+    fn send<'a>(
+        &'a self,
+        registration: &'a RegistrationRow,
+        notification: &'a PushNotification,
+    ) -> BoxFuture<'a, Result<DeliveryReceipt, DeliveryError>> {
+        Box::pin(async move {
+            FcmDelivery::send(self, registration, notification)
+                .await
+                .map_err(|err| match err.downcast_ref::<FcmError>() {
+                    Some(fcm_err) if fcm_err.is_unregistered() => DeliveryError::InvalidToken {
+                        reason: "fcm_unregistered",
+                        detail: err.to_string(),
+                        response: response(&err),
+                    },
+                    _ => DeliveryError::Other(err),
+                })
+        })
+    }
+}
+
+/// The status FCM answered a rejected message with.
+pub(super) fn response(err: &anyhow::Error) -> Option<DeliveryReceipt> {
+    err.downcast_ref::<FcmError>().map(|fcm_err| DeliveryReceipt {
+        status: fcm_err.status,
+        ..DeliveryReceipt::default()
+    })
+}
+
+/// `messages:send` request body. Rich content, then custom data, is dropped
+/// if the message would be over FCM's size limit, which it rejects as a
+/// bad request rather than trimming.
+fn build_message(token: &str, notification: &PushNotification) -> Result<Value> {
+    let mut size = 0;
+    for (include_rich, include_custom_data) in [(true, true), (false, true), (false, false)] {
+        let message = message_body(token, notification, include_rich, include_custom_data);
+        size = payload_len(&message);
+        if size <= MAX_PAYLOAD_BYTES {
+            return Ok(json!({ "message": message }));
+        }
+    }
+    Err(anyhow!(
+        "FCM payload is {} bytes, limit is {}",
+        size,
+        MAX_PAYLOAD_BYTES
+    ))
+}
+
+fn payload_len(message: &Value) -> usize {
+    ["notification", "data"]
+        .iter()
+        .filter_map(|key| message.get(*key))
+        .map(|value| value.to_string().len())
+        .sum()
+}
+
+/// The `message` object. FCM data values must be strings, which
+/// `custom_data` already guarantees.
+fn message_body(
+    token: &str,
+    notification: &PushNotification,
+    include_rich: bool,
+    include_custom_data: bool,
+) -> Value {
+    let mut data: HashMap<String, String> = if include_custom_data {
+        notification.custom_data.clone()
+    } else {
+        HashMap::new()
+    };
+    let mut message = json!({
+        "token": token,
+        "android": { "priority": "high" },
+    });
+
+    // Data-only: the app's messaging service builds what is shown, or acts
+    // on a notification with nothing to show (a chat retraction).
+    let data_only = notification.mutable_content || !notification.has_alert();
+    if data_only {
+        if notification.has_alert() {
+            data.insert("title".to_string(), notification.title.clone());
+            data.insert("body".to_string(), notification.body.clone());
+        }
+        if let Some(ref thread_id) = notification.thread_id {
+            data.insert("threadId".to_string(), thread_id.clone());
+        }
+        // Data values are strings, so the structured content goes as JSON.
+        if let Some(rich) = notification
+            .rich
+            .as_ref()
+            .filter(|_| include_rich)
+            .and_then(|rich| serde_json::to_string(rich).ok())
+        {
+            data.insert("rich".to_string(), rich);
+        }
+    } else {
+        message["notification"] = json!({
+            "title": notification.title,
+            "body": notification.body,
+        });
+        if let Some(ref thread_id) = notification.thread_id {
+            message["android"]["notification"] = json!({ "tag": thread_id });
+        }
+    }
+    if notification.silent {
+        if data_only {
+            data.insert("silent".to_string(), "true".to_string());
+        } else {
+            message["android"]["notification"]["notification_priority"] = json!("PRIORITY_LOW");
+            message["android"]["notification"]["default_sound"] = json!(false);
+        }
+    }
+    if let Some(ref collapse_id) = notification.collapse_id {
+        message["android"]["collapse_key"] = json!(collapse_id);
+        if !data_only {
+            // The tag replaces the shown notification; collapse_key only
+            // drops undelivered ones.
+            message["android"]["notification"]["tag"] = json!(collapse_id);
+        }
+    }
+    message["data"] = json!(data);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::push::rich_content::RichContent;
+    use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn registration(token: &str) -> RegistrationRow {
+        RegistrationRow {
+            id: sqlx::types::Uuid::new_v4(),
+            did: "did:plc:alice".into(),
+            device_token: token.into(),
+            platform: PLATFORM_ANDROID.into(),
+            app_id: "blue.catbird.android".into(),
+            service_did: None,
+            age_restricted: false,
+            is_active: true,
+            apns_environment: None,
+            locale: None,
+            preference_overrides: None,
+            preview_key: None,
+        }
+    }
+
+    fn notification(mutable_content: bool) -> PushNotification {
+        PushNotification {
+            title: "New Message".into(),
+            body: "You have a new message".into(),
+            localization: None,
+            user_did: "did:plc:alice".into(),
+            custom_data: HashMap::from([("type".to_string(), "chat_message".to_string())]),
+            mutable_content,
+            rich: None,
+            thread_id: Some("chat:convo".into()),
+            collapse_id: None,
+            silent: false,
+            content_available: false,
+            badge: None,
+        }
+    }
+
+    /// A delivery pointed at `server` for both the token and send endpoints.
+    fn delivery(server: &MockServer, private_key: &RsaPrivateKey) -> FcmDelivery {
+        let account = ServiceAccount {
+            project_id: "catbird-test".into(),
+            private_key_id: Some("key-1".into()),
+            private_key: private_key
+                .to_pkcs8_pem(LineEnding::LF)
+                .unwrap()
+                .to_string(),
+            client_email: "push@catbird-test.iam.gserviceaccount.com".into(),
+            token_uri: format!("{}/token", server.uri()),
+        };
+        FcmDelivery::from_service_account(account, None, &server.uri()).unwrap()
+    }
+
+    async fn mount_token_endpoint(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "ya29.test",
+                "expires_in": 3599,
+                "token_type": "Bearer",
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn sends_with_a_cached_service_account_token() {
+        let server = MockServer::start().await;
+        mount_token_endpoint(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/v1/projects/catbird-test/messages:send"))
+            .and(header("authorization", "Bearer ya29.test"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "name": "projects/catbird-test/messages/1" })),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let private_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, 1024).unwrap();
+        let delivery = delivery(&server, &private_key);
+        let registration = registration("fcm-token");
+        delivery
+            .send(&registration, &notification(false))
+            .await
+            .unwrap();
+        let receipt = delivery
+            .send(&registration, &notification(false))
+            .await
+            .unwrap();
+        assert_eq!(
+            receipt.provider_id.as_deref(),
+            Some("projects/catbird-test/messages/1")
+        );
+
+        // The grant carries an RS256 assertion for the FCM scope.
+        let requests = server.received_requests().await.unwrap();
+        let form: HashMap<String, String> = url::form_urlencoded::parse(&requests[0].body)
+            .into_owned()
+            .collect();
+        assert_eq!(form["grant_type"], JWT_BEARER_GRANT);
+        let (signing_input, signature) = form["assertion"].rsplit_once('.').unwrap();
+
+        use rsa::{pkcs1v15::VerifyingKey, signature::Verifier};
+        let verifying_key = VerifyingKey::<Sha256>::new(private_key.to_public_key());
+        let signature = rsa::pkcs1v15::Signature::try_from(
+            URL_SAFE_NO_PAD.decode(signature).unwrap().as_slice(),
+        )
+        .unwrap();
+        verifying_key
+            .verify(signing_input.as_bytes(), &signature)
+            .expect("valid assertion signature");
+        let claims: Value = serde_json::from_slice(
+            &URL_SAFE_NO_PAD
+                .decode(signing_input.split('.').nth(1).unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(claims["scope"], FCM_SCOPE);
+        assert_eq!(claims["aud"], format!("{}/token", server.uri()));
+
+        let message: Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(message["message"]["token"], "fcm-token");
+        assert_eq!(message["message"]["notification"]["title"], "New Message");
+    }
+
+    #[tokio::test]
+    async fn unregistered_tokens_are_invalid() {
+        let server = MockServer::start().await;
+        mount_token_endpoint(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/v1/projects/catbird-test/messages:send"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                "error": {
+                    "code": 404,
+                    "message": "Requested entity was not found.",
+                    "status": "NOT_FOUND",
+                    "details": [{
+                        "@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError",
+                        "errorCode": "UNREGISTERED"
+                    }]
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let private_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, 1024).unwrap();
+        let delivery = delivery(&server, &private_key);
+        let err = PushTransport::send(&delivery, &registration("gone"), &notification(false))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            DeliveryError::InvalidToken {
+                reason: "fcm_unregistered",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn only_final_errors_deactivate() {
+        let error = |status, body| FcmError::from_response(status, &body);
+        assert!(!error(
+            503,
+            json!({ "error": { "status": "UNAVAILABLE", "message": "try later" } })
+        )
+        .is_unregistered());
+        assert!(!error(
+            400,
+            json!({ "error": { "status": "INVALID_ARGUMENT", "message": "Invalid JSON payload" } })
+        )
+        .is_unregistered());
+        assert!(error(
+            400,
+            json!({ "error": {
+                "status": "INVALID_ARGUMENT",
+                "message": "The registration token is not a valid FCM registration token"
+            } })
+        )
+        .is_unregistered());
+        assert!(!error(500, Value::Null).is_unregistered());
+    }
+
+    #[test]
+    fn mutable_notifications_are_data_only() {
+        let message = build_message("t", &notification(true)).unwrap();
+        assert!(message["message"].get("notification").is_none());
+        assert_eq!(message["message"]["data"]["title"], "New Message");
+        assert_eq!(message["message"]["data"]["threadId"], "chat:convo");
+        assert_eq!(message["message"]["data"]["type"], "chat_message");
+
+        let message = build_message("t", &notification(false)).unwrap();
+        assert_eq!(
+            message["message"]["notification"]["body"],
+            "You have a new message"
+        );
+        assert_eq!(
+            message["message"]["android"]["notification"]["tag"],
+            "chat:convo"
+        );
+        assert!(message["message"]["data"].get("title").is_none());
+
+        let retraction = PushNotification::chat_retraction("did:plc:bob", "convo", None, None);
+        let message = build_message("t", &retraction).unwrap();
+        assert!(message["message"].get("notification").is_none());
+        assert!(message["message"]["data"].get("title").is_none());
+        assert_eq!(message["message"]["data"]["type"], "chat_retract");
+        assert_eq!(message["message"]["data"]["threadId"], "chat:convo");
+    }
+
+    #[test]
+    fn oversized_messages_drop_rich_content_before_custom_data() {
+        let mut rich_notification = notification(true);
+        rich_notification.rich = Some(RichContent {
+            parent_text: Some("x".repeat(MAX_PAYLOAD_BYTES)),
+            ..RichContent::default()
+        });
+        let message = build_message("t", &rich_notification).unwrap();
+        assert!(message["message"]["data"].get("rich").is_none());
+        assert_eq!(message["message"]["data"]["type"], "chat_message");
+
+        rich_notification.rich = None;
+        rich_notification
+            .custom_data
+            .insert("encryptedPreview".into(), "x".repeat(MAX_PAYLOAD_BYTES));
+        let message = build_message("t", &rich_notification).unwrap();
+        assert!(message["message"]["data"].get("type").is_none());
+        assert_eq!(message["message"]["data"]["title"], "New Message");
+        assert!(payload_len(&message["message"]) <= MAX_PAYLOAD_BYTES);
+
+        rich_notification.body = "x".repeat(MAX_PAYLOAD_BYTES);
+        assert!(build_message("t", &rich_notification).is_err());
+    }
+}
@@ -0,0 +1,594 @@
+//! Label-aware suppression.
+//!
+//! The recipient's label settings (`adultContentPref`, `contentLabelPref`,
+//! `labelersPref`) are synced from `app.bsky.actor.getPreferences` next to
+//! their muted words. Labels for the actor and the event's record are read
+//! from each subscribed labeler's own `com.atproto.label.queryLabels`, with
+//! resolved endpoints and label sets cached in-process.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::services::ssrf::validate_pds_url;
+
+/// Bluesky's own moderation service; applies to every account regardless of
+/// `labelersPref`, as in the app.
+pub const BSKY_MODERATION_DID: &str = "did:plc:ar7c4by46qjdydhdevvrndac";
+
+const ADULT_CONTENT_PREF_TYPE: &str = "app.bsky.actor.defs#adultContentPref";
+const CONTENT_LABEL_PREF_TYPE: &str = "app.bsky.actor.defs#contentLabelPref";
+const LABELERS_PREF_TYPE: &str = "app.bsky.actor.defs#labelersPref";
+
+const ADULT_LABELS: &[&str] = &["porn", "sexual", "nudity"];
+const GRAPHIC_MEDIA_LABEL: &str = "graphic-media";
+
+const PLC_DIRECTORY_URL: &str = "https://plc.directory";
+
+const LABEL_TTL: Duration = Duration::from_secs(5 * 60);
+const ENDPOINT_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long a labeler that couldn't be asked is not asked again; its
+/// sources fail straight away in the meantime.
+const FAILURE_TTL: Duration = Duration::from_secs(60);
+
+/// What the recipient's label settings say to do with a notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LabelVerdict {
+    Deliver,
+    /// Deliver without the record's text.
+    Redact,
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentLabelPref {
+    pub label: String,
+    #[serde(
+        rename = "labelerDid",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub labeler_did: Option<String>,
+    /// `ignore`, `show`, `warn`, or `hide`.
+    pub visibility: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LabelPreferences {
+    #[serde(rename = "adultContentEnabled", default)]
+    pub adult_content_enabled: bool,
+    /// Subscribed labelers, not including `BSKY_MODERATION_DID`.
+    #[serde(default)]
+    pub labelers: Vec<String>,
+    #[serde(rename = "contentLabels", default)]
+    pub content_labels: Vec<ContentLabelPref>,
+}
+
+impl LabelPreferences {
+    /// Reads the label settings out of a `getPreferences` response or a
+    /// `putPreferences` body.
+    pub fn from_preferences(payload: &Value) -> Self {
+        let mut prefs = Self::default();
+        let items = payload
+            .get("preferences")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for item in items {
+            match item.get("$type").and_then(Value::as_str) {
+                Some(ADULT_CONTENT_PREF_TYPE) => {
+                    prefs.adult_content_enabled = item
+                        .get("enabled")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false);
+                }
+                Some(CONTENT_LABEL_PREF_TYPE) => {
+                    if let Ok(pref) = serde_json::from_value::<ContentLabelPref>(item.clone()) {
+                        prefs.content_labels.push(pref);
+                    }
+                }
+                Some(LABELERS_PREF_TYPE) => {
+                    prefs.labelers = item
+                        .get("labelers")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|labeler| labeler.get("did").and_then(Value::as_str))
+                        .filter(|did| *did != BSKY_MODERATION_DID)
+                        .map(str::to_owned)
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        prefs
+    }
+
+    /// Every labeler whose labels apply to this account.
+    pub fn sources(&self) -> Vec<String> {
+        std::iter::once(BSKY_MODERATION_DID.to_string())
+            .chain(self.labelers.iter().cloned())
+            .collect()
+    }
+
+    fn visibility(&self, label: &str, labeler_did: &str) -> Option<&str> {
+        let global = || {
+            self.content_labels
+                .iter()
+                .find(|pref| pref.label == label && pref.labeler_did.is_none())
+        };
+        self.content_labels
+            .iter()
+            .find(|pref| pref.label == label && pref.labeler_did.as_deref() == Some(labeler_did))
+            .or_else(global)
+            .map(|pref| pref.visibility.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Label {
+    pub src: String,
+    pub uri: String,
+    pub val: String,
+    #[serde(default)]
+    pub neg: bool,
+    #[serde(default)]
+    pub exp: Option<String>,
+}
+
+impl Label {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        !self.neg
+            && !self
+                .exp
+                .as_deref()
+                .and_then(|exp| DateTime::parse_from_rfc3339(exp).ok())
+                .is_some_and(|exp| exp.with_timezone(&Utc) < now)
+    }
+}
+
+/// Applies the recipient's settings to the labels on a notification's actor
+/// and record. `age_restricted` registrations never see adult or graphic
+/// content, whatever the account has opted into.
+pub fn verdict(
+    labels: &[Label],
+    prefs: &LabelPreferences,
+    age_restricted: bool,
+    now: DateTime<Utc>,
+) -> LabelVerdict {
+    let sources = prefs.sources();
+    labels
+        .iter()
+        .filter(|label| label.is_active(now) && sources.contains(&label.src))
+        .map(|label| label_verdict(label, prefs, age_restricted))
+        .max()
+        .unwrap_or(LabelVerdict::Deliver)
+}
+
+fn label_verdict(label: &Label, prefs: &LabelPreferences, age_restricted: bool) -> LabelVerdict {
+    let value = label.val.as_str();
+    match value {
+        "!hide" | "!takedown" | "!suspend" => return LabelVerdict::Drop,
+        "!warn" => return LabelVerdict::Redact,
+        _ => {}
+    }
+
+    let adult = ADULT_LABELS.contains(&value);
+    if (adult || value == GRAPHIC_MEDIA_LABEL) && age_restricted {
+        return LabelVerdict::Drop;
+    }
+    if adult && !prefs.adult_content_enabled {
+        return LabelVerdict::Drop;
+    }
+
+    let default_visibility = match value {
+        "porn" => Some("hide"),
+        "sexual" | GRAPHIC_MEDIA_LABEL => Some("warn"),
+        // Custom labeler values only act on an explicit setting.
+        _ => None,
+    };
+    match prefs.visibility(value, &label.src).or(default_visibility) {
+        Some("hide") => LabelVerdict::Drop,
+        Some("warn") => LabelVerdict::Redact,
+        _ => LabelVerdict::Deliver,
+    }
+}
+
+/// In-process cache over labelers' `queryLabels`. Expired entries are
+/// swept by `purge_expired` from the worker's maintenance pass.
+#[derive(Clone, Default)]
+pub struct LabelCache {
+    endpoints: Arc<DashMap<String, (Instant, Option<String>)>>,
+    labels: Arc<DashMap<(String, String), (Instant, Vec<Label>)>>,
+    failures: Arc<DashMap<String, Instant>>,
+}
+
+impl LabelCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn purge_expired(&self) {
+        self.endpoints
+            .retain(|_, (fetched_at, _)| fetched_at.elapsed() < ENDPOINT_TTL);
+        self.labels
+            .retain(|_, (fetched_at, _)| fetched_at.elapsed() < LABEL_TTL);
+        self.failures
+            .retain(|_, failed_at| failed_at.elapsed() < FAILURE_TTL);
+    }
+
+    /// Labels from `sources` on any of `uris`. Errors when a labeler could
+    /// not be asked, so the caller can fail towards redaction.
+    pub async fn labels_for(
+        &self,
+        http_client: &reqwest::Client,
+        sources: &[String],
+        uris: &[String],
+    ) -> Result<Vec<Label>> {
+        let mut labels = Vec::new();
+        let mut failed = None;
+        for source in sources {
+            match self.labels_from(http_client, source, uris).await {
+                Ok(found) => labels.extend(found),
+                Err(err) => failed = Some(err),
+            }
+        }
+        match failed {
+            Some(err) => Err(err),
+            None => Ok(labels),
+        }
+    }
+
+    async fn labels_from(
+        &self,
+        http_client: &reqwest::Client,
+        source: &str,
+        uris: &[String],
+    ) -> Result<Vec<Label>> {
+        let mut labels = Vec::new();
+        let mut missing = Vec::new();
+        for uri in uris {
+            let key = (source.to_string(), uri.clone());
+            match self.labels.get(&key) {
+                Some(entry) if entry.0.elapsed() < LABEL_TTL => labels.extend(entry.1.clone()),
+                _ => missing.push(uri.clone()),
+            }
+        }
+        if missing.is_empty() {
+            return Ok(labels);
+        }
+
+        if let Some(failed_at) = self.failures.get(source) {
+            if failed_at.elapsed() < FAILURE_TTL {
+                return Err(anyhow!("labeler {source} failed recently"));
+            }
+        }
+        let fetched = match self.fetch_labels(http_client, source, &missing).await {
+            Ok(Some(fetched)) => fetched,
+            // Not a labeler (any more); nothing it says applies.
+            Ok(None) => return Ok(labels),
+            Err(err) => {
+                self.failures.insert(source.to_string(), Instant::now());
+                return Err(err);
+            }
+        };
+
+        let mut by_uri: HashMap<String, Vec<Label>> = missing
+            .iter()
+            .map(|uri| (uri.clone(), Vec::new()))
+            .collect();
+        for label in fetched {
+            if let Some(bucket) = by_uri.get_mut(&label.uri) {
+                bucket.push(label);
+            }
+        }
+        let now = Instant::now();
+        for (uri, found) in by_uri {
+            labels.extend(found.iter().cloned());
+            self.labels.insert((source.to_string(), uri), (now, found));
+        }
+        Ok(labels)
+    }
+
+    async fn fetch_labels(
+        &self,
+        http_client: &reqwest::Client,
+        source: &str,
+        uris: &[String],
+    ) -> Result<Option<Vec<Label>>> {
+        let Some(endpoint) = self.endpoint(http_client, source).await? else {
+            return Ok(None);
+        };
+        query_labels(http_client, &endpoint, source, uris)
+            .await
+            .map(Some)
+    }
+
+    async fn endpoint(&self, http_client: &reqwest::Client, did: &str) -> Result<Option<String>> {
+        if let Some(entry) = self.endpoints.get(did) {
+            if entry.0.elapsed() < ENDPOINT_TTL {
+                return Ok(entry.1.clone());
+            }
+        }
+
+        let doc_url = if let Some(rest) = did.strip_prefix("did:web:") {
+            format!("https://{}/.well-known/did.json", rest.replace(':', "/"))
+        } else if did.starts_with("did:plc:") {
+            format!("{PLC_DIRECTORY_URL}/{did}")
+        } else {
+            return Err(anyhow!("unsupported labeler DID method: {did}"));
+        };
+        // A did:web host comes straight from the account's labelersPref.
+        validate_pds_url(&doc_url)
+            .map_err(|err| anyhow!("labeler DID {did} is not resolvable: {err}"))?;
+        let response = http_client.get(doc_url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "labeler DID {did} returned HTTP {}",
+                response.status()
+            ));
+        }
+        let doc: Value = response.json().await?;
+        let endpoint = labeler_endpoint_from_did_doc(&doc)
+            .filter(|endpoint| validate_pds_url(endpoint).is_ok());
+
+        self.endpoints
+            .insert(did.to_string(), (Instant::now(), endpoint.clone()));
+        Ok(endpoint)
+    }
+}
+
+async fn query_labels(
+    http_client: &reqwest::Client,
+    endpoint: &str,
+    source: &str,
+    uris: &[String],
+) -> Result<Vec<Label>> {
+    let mut url = Url::parse(&format!("{endpoint}/xrpc/com.atproto.label.queryLabels"))?;
+    {
+        let mut query = url.query_pairs_mut();
+        for uri in uris {
+            query.append_pair("uriPatterns", uri);
+        }
+        query.append_pair("sources", source);
+        query.append_pair("limit", "250");
+    }
+
+    let response = http_client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "queryLabels on {source} returned HTTP {}",
+            response.status()
+        ));
+    }
+    let payload: Value = response.json().await?;
+    Ok(payload
+        .get("labels")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|label| serde_json::from_value::<Label>(label.clone()).ok())
+        // Labelers may only speak for themselves.
+        .filter(|label| label.src == source)
+        .collect())
+}
+
+fn labeler_endpoint_from_did_doc(doc: &Value) -> Option<String> {
+    doc.get("service")?
+        .as_array()?
+        .iter()
+        .find(|service| {
+            service
+                .get("id")
+                .and_then(Value::as_str)
+                .is_some_and(|id| id == "#atproto_labeler" || id.ends_with("#atproto_labeler"))
+        })?
+        .get("serviceEndpoint")?
+        .as_str()
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const LABELER: &str = "did:plc:labeler";
+
+    fn label(src: &str, val: &str) -> Label {
+        Label {
+            src: src.to_string(),
+            uri: "did:plc:actor".to_string(),
+            val: val.to_string(),
+            neg: false,
+            exp: None,
+        }
+    }
+
+    fn prefs() -> LabelPreferences {
+        LabelPreferences::from_preferences(&json!({
+            "preferences": [
+                { "$type": "app.bsky.actor.defs#adultContentPref", "enabled": true },
+                {
+                    "$type": "app.bsky.actor.defs#labelersPref",
+                    "labelers": [{ "did": LABELER }]
+                },
+                {
+                    "$type": "app.bsky.actor.defs#contentLabelPref",
+                    "labelerDid": LABELER,
+                    "label": "spider",
+                    "visibility": "hide"
+                },
+                {
+                    "$type": "app.bsky.actor.defs#contentLabelPref",
+                    "label": "nudity",
+                    "visibility": "warn"
+                }
+            ]
+        }))
+    }
+
+    #[test]
+    fn parses_label_settings_from_preferences() {
+        let prefs = prefs();
+        assert!(prefs.adult_content_enabled);
+        assert_eq!(prefs.labelers, vec![LABELER.to_string()]);
+        assert_eq!(prefs.content_labels.len(), 2);
+        assert_eq!(
+            prefs.sources(),
+            vec![BSKY_MODERATION_DID.to_string(), LABELER.to_string()]
+        );
+    }
+
+    #[test]
+    fn hide_and_warn_settings_drop_or_redact() {
+        let prefs = prefs();
+        let now = Utc::now();
+        assert_eq!(
+            verdict(&[label(LABELER, "spider")], &prefs, false, now),
+            LabelVerdict::Drop
+        );
+        assert_eq!(
+            verdict(&[label(BSKY_MODERATION_DID, "nudity")], &prefs, false, now),
+            LabelVerdict::Redact
+        );
+        assert_eq!(
+            verdict(&[label(LABELER, "!hide")], &prefs, false, now),
+            LabelVerdict::Drop
+        );
+        assert_eq!(verdict(&[], &prefs, false, now), LabelVerdict::Deliver);
+    }
+
+    #[test]
+    fn unsubscribed_negated_and_expired_labels_are_ignored() {
+        let prefs = prefs();
+        let now = Utc::now();
+        let mut negated = label(LABELER, "!hide");
+        negated.neg = true;
+        let mut expired = label(LABELER, "!hide");
+        expired.exp = Some("2020-01-01T00:00:00.000Z".to_string());
+
+        assert_eq!(
+            verdict(
+                &[label("did:plc:stranger", "!hide"), negated, expired],
+                &prefs,
+                false,
+                now
+            ),
+            LabelVerdict::Deliver
+        );
+    }
+
+    #[test]
+    fn adult_content_is_dropped_for_age_restricted_or_opted_out_accounts() {
+        let now = Utc::now();
+        let porn = [label(BSKY_MODERATION_DID, "sexual")];
+
+        assert_eq!(verdict(&porn, &prefs(), false, now), LabelVerdict::Redact);
+        assert_eq!(verdict(&porn, &prefs(), true, now), LabelVerdict::Drop);
+        assert_eq!(
+            verdict(&porn, &LabelPreferences::default(), false, now),
+            LabelVerdict::Drop
+        );
+        assert_eq!(
+            verdict(
+                &[label(BSKY_MODERATION_DID, "graphic-media")],
+                &prefs(),
+                true,
+                now
+            ),
+            LabelVerdict::Drop
+        );
+    }
+
+    #[tokio::test]
+    async fn query_labels_keeps_only_the_asked_labelers_labels() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.label.queryLabels"))
+            .and(query_param("sources", LABELER))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "labels": [
+                    { "src": LABELER, "uri": "did:plc:actor", "val": "spider", "cts": "2026-01-01T00:00:00Z" },
+                    { "src": "did:plc:other", "uri": "did:plc:actor", "val": "!hide", "cts": "2026-01-01T00:00:00Z" }
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let labels = query_labels(
+            &reqwest::Client::new(),
+            &server.uri(),
+            LABELER,
+            &["did:plc:actor".to_string()],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(labels, vec![label(LABELER, "spider")]);
+    }
+
+    #[tokio::test]
+    async fn failing_labelers_are_not_asked_again_until_the_failure_expires() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.label.queryLabels"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache = LabelCache::new();
+        cache
+            .endpoints
+            .insert(LABELER.to_string(), (Instant::now(), Some(server.uri())));
+        let sources = [LABELER.to_string()];
+        let uris = ["did:plc:actor".to_string()];
+        let client = reqwest::Client::new();
+        assert!(cache.labels_for(&client, &sources, &uris).await.is_err());
+        assert!(cache.labels_for(&client, &sources, &uris).await.is_err());
+
+        cache.purge_expired();
+        assert!(cache.failures.contains_key(LABELER));
+        let expired = Instant::now() - FAILURE_TTL;
+        cache.failures.insert(LABELER.to_string(), expired);
+        cache.purge_expired();
+        assert!(cache.failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn did_web_labelers_on_private_hosts_are_not_resolved() {
+        let cache = LabelCache::new();
+        let err = cache
+            .endpoint(&reqwest::Client::new(), "did:web:127.0.0.1")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not resolvable"));
+    }
+
+    #[test]
+    fn labeler_endpoint_comes_from_the_did_document() {
+        let doc = json!({
+            "service": [
+                { "id": "#atproto_pds", "type": "AtprotoPersonalDataServer", "serviceEndpoint": "https://pds.example" },
+                { "id": "#atproto_labeler", "type": "AtprotoLabeler", "serviceEndpoint": "https://labeler.example/" }
+            ]
+        });
+        assert_eq!(
+            labeler_endpoint_from_did_doc(&doc).as_deref(),
+            Some("https://labeler.example")
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Push notification string catalogs.
+//!
+//! Alerts carry APNs loc-keys so iOS renders them from the app bundle in the
+//! device's language. Transports that need finished text (Web Push, FCM)
+//! get the same keys rendered server-side from the catalogs in `locales/`,
+//! in the locale the device sent to `registerPush`. Keys and the `%@` /
+//! `%1$@` placeholders follow Apple's `.strings` format, so the client
+//! bundle and these catalogs can share one source of translations.
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+/// Locale notifications are built in, and the fallback for missing keys.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// BCP 47 tags are at most 35 characters in practice (RFC 5646 §4.4.1).
+const MAX_LOCALE_LEN: usize = 35;
+
+const CATALOG_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.json")),
+    ("de", include_str!("locales/de.json")),
+    ("es", include_str!("locales/es.json")),
+    ("fr", include_str!("locales/fr.json")),
+    ("ja", include_str!("locales/ja.json")),
+    ("pt", include_str!("locales/pt.json")),
+];
+
+lazy_static! {
+    static ref CATALOGS: HashMap<&'static str, HashMap<String, String>> = CATALOG_SOURCES
+        .iter()
+        .map(|(locale, source)| {
+            let catalog = serde_json::from_str(source)
+                .unwrap_or_else(|err| panic!("invalid {locale} push string catalog: {err}"));
+            (*locale, catalog)
+        })
+        .collect();
+}
+
+/// Localizable alert text: the catalog keys and arguments an alert was
+/// rendered from, kept so it can be re-rendered per device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalizedText {
+    pub title_key: &'static str,
+    pub body_key: &'static str,
+    pub body_args: Vec<String>,
+}
+
+impl LocalizedText {
+    pub fn new(title_key: &'static str, body_key: &'static str, body_args: Vec<String>) -> Self {
+        Self {
+            title_key,
+            body_key,
+            body_args,
+        }
+    }
+
+    pub fn title(&self, locale: &str) -> String {
+        render(locale, self.title_key, &[])
+    }
+
+    pub fn body(&self, locale: &str) -> String {
+        render(locale, self.body_key, &self.body_args)
+    }
+}
+
+/// Checks a locale sent by a client and returns it in canonical
+/// `language-REGION` form ("pt_br" becomes "pt-BR").
+pub fn normalize_locale(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let valid = !value.is_empty()
+        && value.len() <= MAX_LOCALE_LEN
+        && value
+            .split(['-', '_'])
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid {
+        return Err(format!("Invalid locale: {value}"));
+    }
+
+    let mut parts = value.split(['-', '_']);
+    let mut normalized = parts.next().unwrap_or_default().to_ascii_lowercase();
+    for part in parts {
+        normalized.push('-');
+        if part.len() == 2 {
+            normalized.push_str(&part.to_ascii_uppercase());
+        } else {
+            normalized.push_str(part);
+        }
+    }
+    Ok(normalized)
+}
+
+/// The bundled catalog for a device locale: an exact match, then the
+/// language alone ("pt-BR" uses "pt"), then English.
+pub fn catalog_locale(locale: Option<&str>) -> &'static str {
+    let Some(locale) = locale else {
+        return DEFAULT_LOCALE;
+    };
+    let language = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    CATALOG_SOURCES
+        .iter()
+        .map(|(name, _)| *name)
+        .find(|name| name.eq_ignore_ascii_case(locale))
+        .or_else(|| {
+            CATALOG_SOURCES
+                .iter()
+                .map(|(name, _)| *name)
+                .find(|name| *name == language)
+        })
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// The template for `key`, from `locale` or else English. A key missing
+/// from every catalog renders as itself, as it would on iOS.
+fn template(locale: &str, key: &'static str) -> &'static str {
+    let catalogs: &'static HashMap<&'static str, HashMap<String, String>> = &CATALOGS;
+    catalogs
+        .get(catalog_locale(Some(locale)))
+        .and_then(|catalog| catalog.get(key))
+        .or_else(|| {
+            catalogs
+                .get(DEFAULT_LOCALE)
+                .and_then(|catalog| catalog.get(key))
+        })
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// Renders `key` in `locale`, substituting `%@` (next argument), `%n$@`
+/// (n-th argument, 1-based) and `%%`.
+pub fn render(locale: &str, key: &'static str, args: &[String]) -> String {
+    format_template(template(locale, key), args)
+}
+
+fn format_template(template: &str, args: &[String]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut next_arg = 0;
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let spec = &rest[start + 1..];
+        if let Some(after) = spec.strip_prefix('%') {
+            out.push('%');
+            rest = after;
+            continue;
+        }
+        if let Some(after) = spec.strip_prefix('@') {
+            out.push_str(args.get(next_arg).map(String::as_str).unwrap_or_default());
+            next_arg += 1;
+            rest = after;
+            continue;
+        }
+        let digits = spec.bytes().take_while(u8::is_ascii_digit).count();
+        if let (Some(after), Ok(position)) = (
+            spec[digits..].strip_prefix("$@"),
+            spec[..digits].parse::<usize>(),
+        ) {
+            if let Some(arg) = position.checked_sub(1).and_then(|index| args.get(index)) {
+                out.push_str(arg);
+            }
+            rest = after;
+            continue;
+        }
+        out.push('%');
+        rest = spec;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn renders_sequential_and_positional_placeholders() {
+        assert_eq!(
+            format_template("%@ liked your post", &args(&["Alice"])),
+            "Alice liked your post"
+        );
+        assert_eq!(format_template("%2$@ / %1$@", &args(&["a", "b"])), "b / a");
+        assert_eq!(format_template("100%% %@", &args(&["done"])), "100% done");
+        assert_eq!(format_template("50% off", &[]), "50% off");
+        assert_eq!(format_template("%@ and %@", &args(&["a"])), "a and ");
+    }
+
+    #[test]
+    fn resolves_locales_to_bundled_catalogs() {
+        assert_eq!(catalog_locale(None), "en");
+        assert_eq!(catalog_locale(Some("ja")), "ja");
+        assert_eq!(catalog_locale(Some("pt-BR")), "pt");
+        assert_eq!(catalog_locale(Some("ES_419")), "es");
+        assert_eq!(catalog_locale(Some("ko-KR")), "en");
+    }
+
+    #[test]
+    fn falls_back_to_english_then_the_key() {
+        assert_eq!(
+            render("ko", "PUSH_LIKE_BODY", &args(&["Alice"])),
+            "Alice liked your post"
+        );
+        assert_eq!(render("de", "PUSH_NOT_A_KEY", &[]), "PUSH_NOT_A_KEY");
+        assert_eq!(
+            render("de", "PUSH_LIKE_BODY", &args(&["Alice"])),
+            "Alice gefällt dein Beitrag"
+        );
+    }
+
+    #[test]
+    fn every_catalog_covers_english_with_the_same_arguments() {
+        let english = &CATALOGS[DEFAULT_LOCALE];
+        let markers = args(&["\u{1}", "\u{2}"]);
+        for (locale, catalog) in CATALOGS.iter() {
+            assert_eq!(catalog.len(), english.len(), "{locale} has extra keys");
+            for (key, english_template) in english {
+                let template = catalog
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{locale} is missing {key}"));
+                let expected = format_template(english_template, &markers);
+                let rendered = format_template(template, &markers);
+                for marker in &markers {
+                    assert_eq!(
+                        rendered.contains(marker.as_str()),
+                        expected.contains(marker.as_str()),
+                        "{locale} {key} uses different arguments"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn normalizes_client_locales() {
+        assert_eq!(normalize_locale("en").unwrap(), "en");
+        assert_eq!(normalize_locale("pt_br").unwrap(), "pt-BR");
+        assert_eq!(normalize_locale("zh-Hant-TW").unwrap(), "zh-Hant-TW");
+        assert!(normalize_locale("").is_err());
+        assert!(normalize_locale("en--US").is_err());
+        assert!(normalize_locale("en US").is_err());
+        assert!(normalize_locale(&"a".repeat(36)).is_err());
+    }
+}
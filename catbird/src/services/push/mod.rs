@@ -1,34 +1,66 @@
 pub mod apns;
+pub mod badges;
+pub mod chat_preview;
+pub mod coalesce;
+pub mod dead_letter;
 pub mod decision;
+pub mod deliveries;
+pub mod fcm;
+pub mod labels;
+pub mod localization;
 pub mod moderation_cache;
+pub mod muted_words;
+pub mod notification_kind;
 pub mod preferences;
+pub mod profiles;
 pub mod queue;
+pub mod quiet_hours;
 pub mod registry;
+pub mod rich_content;
+pub mod send_test;
 pub mod subscriptions;
+pub mod transport;
 pub mod types;
+pub mod web_push;
 
-use std::collections::HashSet;
+use std::future::Future;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use chrono::Utc;
-use sqlx::{Pool, Postgres};
+use dashmap::DashMap;
+use sqlx::{postgres::PgListener, Pool, Postgres};
 
 use crate::{
     config::{AppState, PushConfig},
     error::AppError,
-    middleware::JacquardDpopData,
-    models::CatbirdSession,
+    metrics,
+    middleware::{record_auth_failure, JacquardDpopData, RateLimiter},
+    models::{AuthFailureSource, CatbirdSession, SessionAuthKind},
 };
 
 use self::{
     apns::ApnsDelivery,
+    badges::BadgeCounter,
+    coalesce::{build_summary, PushCoalescer},
+    dead_letter::DeadLetterQueue,
     decision::{PushDecisionEngine, QueueDisposition},
+    deliveries::{DeliveryLog, LoggedEvent},
+    fcm::FcmDelivery,
+    labels::LabelCache,
     moderation_cache::ModerationCache,
+    notification_kind::NotificationKind,
     preferences::PushPreferences,
-    queue::PushQueue,
+    profiles::ProfileHydrator,
+    queue::{PushQueue, LEASE_SECONDS, MAX_EVENT_AGE_HOURS},
+    quiet_hours::QuietHoursMode,
     registry::PushRegistry,
+    rich_content::ParentPosts,
+    send_test::SEND_TEST_LIMIT,
     subscriptions::PushSubscriptions,
+    transport::{DeliveryError, DeliveryReceipt, PushNotification, PushTransport, PushTransports},
+    types::{QueueRow, RegistrationRow},
+    web_push::WebPushDelivery,
 };
 
 #[derive(Clone)]
@@ -38,9 +70,19 @@ pub struct PushServices {
     pub preferences: PushPreferences,
     pub subscriptions: PushSubscriptions,
     pub moderation_cache: ModerationCache,
+    pub badges: BadgeCounter,
+    pub labels: LabelCache,
+    pub profiles: ProfileHydrator,
+    pub parent_posts: ParentPosts,
     pub queue: PushQueue,
+    pub dead_letters: DeadLetterQueue,
+    pub coalescer: PushCoalescer,
     pub decision: PushDecisionEngine,
-    pub apns: Option<ApnsDelivery>,
+    pub deliveries: DeliveryLog,
+    pub transports: PushTransports,
+    pub revoked: RevokedAccounts,
+    /// Per-account `sendTest` calls; see `SEND_TEST_LIMIT`.
+    pub test_limiter: Arc<RateLimiter>,
 }
 
 impl PushServices {
@@ -50,46 +92,119 @@ impl PushServices {
             .clone()
             .ok_or_else(|| anyhow!("push.service_did must be configured when push is enabled"))?;
 
+        let registry = PushRegistry::new(db_pool.clone(), service_did);
+        let transports = PushTransports {
+            apns: ApnsDelivery::new(&config.apns, registry.clone())?,
+            web_push: WebPushDelivery::new(&config.web_push)?,
+            fcm: FcmDelivery::new(&config.fcm)?,
+        };
+
         Ok(Self {
-            registry: PushRegistry::new(db_pool.clone(), service_did),
+            registry,
             preferences: PushPreferences::new(db_pool.clone()),
             subscriptions: PushSubscriptions::new(db_pool.clone()),
             moderation_cache: ModerationCache::new(db_pool.clone(), config.sync_interval_seconds),
-            queue: PushQueue::new(db_pool),
+            badges: BadgeCounter::new(db_pool.clone(), config.sync_interval_seconds),
+            labels: LabelCache::new(),
+            profiles: ProfileHydrator::new(db_pool.clone(), &config.appview_url),
+            parent_posts: ParentPosts::new(&config.appview_url),
+            coalescer: PushCoalescer::new(db_pool.clone(), config.coalesce_window_seconds),
+            deliveries: DeliveryLog::new(db_pool.clone(), config.delivery_log_retention_days),
+            dead_letters: DeadLetterQueue::new(db_pool.clone()),
+            queue: PushQueue::new(db_pool, config.max_attempts),
             decision: PushDecisionEngine::new(),
-            apns: ApnsDelivery::new(&config.apns)?,
+            transports,
+            revoked: RevokedAccounts::default(),
+            test_limiter: Arc::new(RateLimiter::new()),
             config,
         })
     }
 
     pub fn spawn_worker(self: Arc<Self>, state: Arc<AppState>) {
-        if self.apns.is_none() {
-            tracing::warn!("Skipping push worker startup because APNs is not configured");
+        if self.transports.is_empty() {
+            tracing::warn!("Skipping push worker startup because no push transport is configured");
             return;
         }
 
-        let self_clone = self.clone();
-        let state_clone = state.clone();
         tokio::spawn(async move {
-            self_clone.run_worker_loop(state_clone).await;
+            self.run_worker_loop(state).await;
         });
+    }
 
-        let self_clone = self.clone();
-        let state_clone = state.clone();
-        tokio::spawn(async move {
-            self_clone.run_chat_push_subscriber(state_clone).await;
-        });
+    /// Sends through `transport`, rendered in the registration's locale,
+    /// and records the attempt in the delivery log.
+    async fn send_logged(
+        &self,
+        transport: &dyn PushTransport,
+        event: LoggedEvent<'_>,
+        registration: &RegistrationRow,
+        notification: &PushNotification,
+    ) -> Result<DeliveryReceipt, DeliveryError> {
+        let notification = notification.for_locale(registration.locale.as_deref());
+        let started = std::time::Instant::now();
+        let result = match with_step_timeout(
+            "send",
+            SEND_TIMEOUT,
+            transport.send(registration, &notification),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(err) => Err(DeliveryError::Other(err)),
+        };
+        self.deliveries
+            .record_attempt(event, registration, &result, started.elapsed())
+            .await;
+        result
     }
 
-    async fn run_worker_loop(self: Arc<Self>, state: Arc<AppState>) {
-        let Some(apns) = self.apns.clone() else {
+    /// Deactivate a registration the push service reported as dead, and
+    /// drop the account from chat polling once it has no devices left.
+    async fn deactivate_dead_token(
+        &self,
+        state: &AppState,
+        registration: &RegistrationRow,
+        reason: &str,
+    ) {
+        tracing::info!(
+            did = %registration.did,
+            platform = %registration.platform,
+            reason,
+            "Deactivating invalid push token"
+        );
+        if let Err(err) = self
+            .registry
+            .deactivate_invalid_token(&registration.did, &registration.device_token, reason)
+            .await
+        {
+            tracing::error!(error = %err, "Failed to deactivate invalid push token");
             return;
-        };
+        }
+        if let Some(push_db) = state.push_db.as_ref() {
+            let scheduler =
+                crate::services::chat_poll::scheduler::ChatPollScheduler::new(push_db.clone());
+            if let Err(err) = scheduler
+                .unenroll_account_if_no_active_devices(&registration.did)
+                .await
+            {
+                tracing::warn!(did = %registration.did, error = %err, "Chat poll unenroll (push token death) failed");
+            }
+        }
+    }
 
+    async fn run_worker_loop(self: Arc<Self>, state: Arc<AppState>) {
         let batch_size = i64::from(self.config.queue_batch_size.max(1));
         let poll_interval = std::time::Duration::from_millis(self.config.queue_poll_interval_ms);
 
-        tracing::info!("Push queue worker started");
+        tracing::info!(
+            concurrency = self.config.worker_concurrency.max(1),
+            "Push queue worker started"
+        );
+
+        let mut wakeups = self.listen_for_wakeups().await;
+        let lane_slots = Arc::new(tokio::sync::Semaphore::new(
+            self.config.worker_concurrency.max(1) as usize,
+        ));
 
         // Purge lingering queue rows for revoked accounts every ~60s
         let purge_interval = std::time::Duration::from_secs(60);
@@ -107,241 +222,48 @@ impl PushServices {
                         tracing::warn!(error = %err, "Failed to purge revoked account queue rows")
                     }
                 }
+                self.revoked.purge_expired();
+                self.labels.purge_expired();
+                self.test_limiter.cleanup(SEND_TEST_LIMIT.window).await;
+                match self.queue.dead_letter_abandoned().await {
+                    Ok(0) => {}
+                    Ok(n) => {
+                        tracing::warn!(
+                            count = n,
+                            "Dead-lettered exhausted push events with expired leases"
+                        )
+                    }
+                    Err(err) => {
+                        tracing::warn!(error = %err, "Failed to dead-letter abandoned push events")
+                    }
+                }
+                match self.dead_letters.count().await {
+                    Ok(count) => metrics::set_push_dead_letter_depth(count as f64),
+                    Err(err) => {
+                        tracing::warn!(error = %err, "Failed to count push dead letters")
+                    }
+                }
+                if let Err(err) = self.coalescer.purge_idle().await {
+                    tracing::warn!(error = %err, "Failed to purge idle push coalesce groups");
+                }
+                if let Err(err) = self.profiles.purge_expired().await {
+                    tracing::warn!(error = %err, "Failed to purge expired push actor profiles");
+                }
+                if let Err(err) = self.deliveries.purge_expired().await {
+                    tracing::warn!(error = %err, "Failed to purge expired push delivery log entries");
+                }
             }
 
-            match self.queue.claim_ready(batch_size).await {
-                Ok(rows) if rows.is_empty() => {
-                    tokio::time::sleep(poll_interval).await;
-                }
-                Ok(rows) => {
-                    // Track DIDs discovered to be revoked mid-batch so we can
-                    // skip remaining rows without hitting the decision engine
-                    let mut revoked_dids: HashSet<String> = HashSet::new();
-
-                    for row in rows {
-                        // Fast-path: skip rows for DIDs already known revoked in this batch
-                        if revoked_dids.contains(&row.recipient_did) {
-                            tracing::debug!(
-                                recipient = %row.recipient_did,
-                                notification_type = %row.notification_type,
-                                "Skipping push event for revoked account (batch cache)"
-                            );
-                            if let Err(err) = self.queue.delete(row.id).await {
-                                tracing::error!(error = %err, "Failed to delete revoked-account push event");
-                            }
-                            continue;
-                        }
+            self.flush_coalesced_groups(&state, batch_size).await;
 
-                        // Staleness guard: drop events that sat in the queue
-                        // past this age instead of delivering a long-dead
-                        // notification. Protects against any future dormant
-                        // producer/pipeline reactivation spraying weeks-old
-                        // backlog (e.g. the 383k-row incident), independent of
-                        // notification_type.
-                        let age = time::OffsetDateTime::now_utc() - row.created_at;
-                        if age > time::Duration::hours(24) {
-                            tracing::debug!(
-                                recipient = %row.recipient_did,
-                                notification_type = %row.notification_type,
-                                age_secs = age.whole_seconds(),
-                                "Dropping stale queued push event"
-                            );
-                            if let Err(err) = self.queue.delete(row.id).await {
-                                tracing::error!(error = %err, "Failed to delete stale push event");
-                            }
-                            continue;
-                        }
+            // Claim only once a lane is free to start on it.
+            drop(lane_slots.acquire().await);
 
-                        // Bound the whole decision. Nothing inside this path
-                        // carries a timeout of its own, and it performs network
-                        // work (session refresh, moderation sync) while the
-                        // worker processes rows strictly sequentially — so one
-                        // hung upstream connection stops ALL push delivery
-                        // indefinitely, with no error logged because nothing
-                        // ever returns. That is how the queue reached 2000+
-                        // events with the worker silent.
-                        let evaluated = tokio::time::timeout(
-                            DECISION_TIMEOUT,
-                            self.decision.evaluate(&state, &self, &row),
-                        )
-                        .await;
-                        let evaluated = match evaluated {
-                            Ok(result) => result,
-                            Err(_elapsed) => {
-                                // Requeue rather than drop: a timeout may be
-                                // transient, and dropping would silently lose a
-                                // deliverable notification.
-                                tracing::warn!(
-                                    recipient = %row.recipient_did,
-                                    notification_type = %row.notification_type,
-                                    timeout_secs = DECISION_TIMEOUT.as_secs(),
-                                    "Push decision timed out; scheduling retry and moving on"
-                                );
-                                if let Err(update_err) = self
-                                    .queue
-                                    .retry_later(row.id, row.attempts, "decision_timeout")
-                                    .await
-                                {
-                                    tracing::error!(error = %update_err, "Failed to schedule push retry after timeout");
-                                }
-                                continue;
-                            }
-                        };
-                        match evaluated {
-                            Ok(QueueDisposition::Drop(reason)) => {
-                                tracing::debug!(
-                                    recipient = %row.recipient_did,
-                                    notification_type = %row.notification_type,
-                                    reason = reason,
-                                    "Dropping queued push event"
-                                );
-                                if let Err(err) = self.queue.delete(row.id).await {
-                                    tracing::error!(error = %err, "Failed to delete dropped push event");
-                                }
-                            }
-                            Ok(QueueDisposition::Deliver(deliveries)) => {
-                                let mut transient_error = None;
-
-                                for (registration, notification) in deliveries {
-                                    match apns.send(&registration, &notification).await {
-                                        Ok(delivered_env) => {
-                                            if registration.apns_environment.as_deref()
-                                                != Some(delivered_env)
-                                            {
-                                                tracing::info!(
-                                                    did = %registration.did,
-                                                    token = %registration.device_token,
-                                                    env = delivered_env,
-                                                    "Learned APNs environment"
-                                                );
-                                                if let Err(err) = self
-                                                    .registry
-                                                    .set_apns_environment(
-                                                        &registration.did,
-                                                        &registration.device_token,
-                                                        delivered_env,
-                                                    )
-                                                    .await
-                                                {
-                                                    tracing::warn!(error = %err, "Failed to persist learned APNs environment");
-                                                }
-                                            }
-                                        }
-                                        Err(err) if is_invalid_token(&err) => {
-                                            tracing::info!(
-                                                did = %registration.did,
-                                                token = %registration.device_token,
-                                                "Deactivating invalid APNs token"
-                                            );
-                                            if let Err(update_err) = self
-                                                .registry
-                                                .deactivate_invalid_token(
-                                                    &registration.did,
-                                                    &registration.device_token,
-                                                    "apns_unregistered",
-                                                )
-                                                .await
-                                            {
-                                                tracing::error!(error = %update_err, "Failed to deactivate invalid APNs token");
-                                            } else if let Some(push_db) = state.push_db.as_ref() {
-                                                let scheduler = crate::services::chat_poll::scheduler::ChatPollScheduler::new(push_db.clone());
-                                                if let Err(err) = scheduler
-                                                    .unenroll_account_if_no_active_devices(
-                                                        &registration.did,
-                                                    )
-                                                    .await
-                                                {
-                                                    tracing::warn!(did = %registration.did, error = %err, "Chat poll unenroll (APNs token death) failed");
-                                                }
-                                            }
-                                        }
-                                        Err(err) if is_auth_revocation_error(&err) => {
-                                            tracing::info!(
-                                                recipient = %row.recipient_did,
-                                                error = %err,
-                                                "Auth revoked during delivery; skipping remaining events for account"
-                                            );
-                                            revoked_dids.insert(row.recipient_did.clone());
-                                            break;
-                                        }
-                                        Err(err) => {
-                                            transient_error = Some(err);
-                                            break;
-                                        }
-                                    }
-                                }
-
-                                if revoked_dids.contains(&row.recipient_did) {
-                                    // Auth was revoked during delivery — delete, don't retry
-                                    if let Err(err) = self.queue.delete(row.id).await {
-                                        tracing::error!(error = %err, "Failed to delete revoked-account push event");
-                                    }
-                                } else if let Some(err) = transient_error {
-                                    // Known limitation: retrying the row re-sends to
-                                    // registrations that already succeeded this attempt.
-                                    // Acceptable for rare, genuinely transient errors
-                                    // (network/5xx) — deterministic failures like
-                                    // BadDeviceToken must never reach this arm (they're
-                                    // classified invalid and deactivate the token instead).
-                                    tracing::warn!(
-                                        recipient = %row.recipient_did,
-                                        notification_type = %row.notification_type,
-                                        error = %err,
-                                        "Transient push delivery failure; scheduling retry"
-                                    );
-                                    if let Err(update_err) = self
-                                        .queue
-                                        .retry_later(row.id, row.attempts, &err.to_string())
-                                        .await
-                                    {
-                                        tracing::error!(error = %update_err, "Failed to schedule push retry");
-                                    }
-                                } else if let Err(err) = self.queue.delete(row.id).await {
-                                    tracing::error!(error = %err, "Failed to delete delivered push event");
-                                }
-                            }
-                            Err(err) if is_auth_revocation_error(&err) => {
-                                tracing::info!(
-                                    recipient = %row.recipient_did,
-                                    error = %err,
-                                    "Auth revoked during decision evaluation; skipping remaining events for account"
-                                );
-                                revoked_dids.insert(row.recipient_did.clone());
-                                if let Err(del_err) = self.queue.delete(row.id).await {
-                                    tracing::error!(error = %del_err, "Failed to delete revoked-account push event");
-                                }
-                            }
-                            Err(err) if is_unregistered_account_error(&err) => {
-                                // Terminal, not transient: without a
-                                // push_accounts row this event fails the same
-                                // way on every attempt.
-                                tracing::info!(
-                                    recipient = %row.recipient_did,
-                                    notification_type = %row.notification_type,
-                                    "Recipient has no push account; dropping undeliverable event"
-                                );
-                                if let Err(del_err) = self.queue.delete(row.id).await {
-                                    tracing::error!(error = %del_err, "Failed to delete undeliverable push event");
-                                }
-                            }
-                            Err(err) => {
-                                tracing::warn!(
-                                    recipient = %row.recipient_did,
-                                    notification_type = %row.notification_type,
-                                    error = %err,
-                                    "Push decision pipeline failed; scheduling retry"
-                                );
-                                if let Err(update_err) = self
-                                    .queue
-                                    .retry_later(row.id, row.attempts, &err.to_string())
-                                    .await
-                                {
-                                    tracing::error!(error = %update_err, "Failed to schedule push retry");
-                                }
-                            }
-                        }
-                    }
+            match self.queue.claim_ready(batch_size).await {
+                Ok(rows) if rows.is_empty() => {
+                    self.wait_for_events(&mut wakeups, poll_interval).await;
                 }
+                Ok(rows) => self.start_lanes(&state, &lane_slots, rows).await,
                 Err(err) => {
                     tracing::error!(error = %err, "Push queue lease failed");
                     tokio::time::sleep(poll_interval).await;
@@ -350,291 +272,609 @@ impl PushServices {
         }
     }
 
-    async fn run_chat_push_subscriber(self: Arc<Self>, state: Arc<AppState>) {
-        use futures_util::StreamExt;
+    /// Subscribes to the queue's insert notifications. `None` leaves the
+    /// worker polling until the next attempt.
+    async fn listen_for_wakeups(&self) -> Option<PgListener> {
+        let mut listener = match PgListener::connect_with(self.queue.pool()).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                tracing::warn!(error = %err, "Push queue LISTEN connection failed; polling only");
+                return None;
+            }
+        };
+        if let Err(err) = listener.listen(QUEUE_NOTIFY_CHANNEL).await {
+            tracing::warn!(error = %err, "Push queue LISTEN failed; polling only");
+            return None;
+        }
+        Some(listener)
+    }
 
-        let Some(apns) = self.apns.clone() else {
+    /// Waits for an insert notification, or `poll_interval` at most. Rows
+    /// that become due without an insert (retries, quiet-hour deferrals) and
+    /// closing coalesce windows are picked up by the poll.
+    async fn wait_for_events(
+        &self,
+        wakeups: &mut Option<PgListener>,
+        poll_interval: std::time::Duration,
+    ) {
+        let Some(listener) = wakeups.as_mut() else {
+            tokio::time::sleep(poll_interval).await;
+            *wakeups = self.listen_for_wakeups().await;
             return;
         };
+        match tokio::time::timeout(poll_interval, listener.recv()).await {
+            Ok(Ok(_notification)) | Err(_) => {}
+            Ok(Err(err)) => {
+                // `recv` reconnects by itself on the next call; just don't
+                // spin while the database is away.
+                tracing::warn!(error = %err, "Push queue notification stream failed");
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    }
 
-        tracing::info!("Chat push Redis subscriber starting");
+    /// Starts one task per recipient in a claimed batch and returns. At
+    /// most `worker_concurrency` lanes run at once; the rest wait for a
+    /// slot. Each lane sends its recipient's events one after another in
+    /// claim order and finishes on its own, so a slow recipient holds up
+    /// neither the rest of the batch nor the next claim.
+    async fn start_lanes(
+        self: &Arc<Self>,
+        state: &Arc<AppState>,
+        lane_slots: &Arc<tokio::sync::Semaphore>,
+        rows: Vec<QueueRow>,
+    ) {
+        // One getProfiles round trip for the whole batch instead of a lookup
+        // per row inside the decision engine.
+        let actor_dids: Vec<String> = rows
+            .iter()
+            .filter(|row| row.notification_type != NotificationKind::ChatMessage)
+            .map(|row| row.actor_did.clone())
+            .collect();
+        if let Err(err) = with_step_timeout(
+            "hydration",
+            HYDRATION_TIMEOUT,
+            self.profiles.prefetch(&state.http_client, &actor_dids),
+        )
+        .await
+        {
+            tracing::warn!(error = %err, "Push actor profile prefetch abandoned");
+        }
 
-        loop {
-            let client = match redis::Client::open(state.config.redis.url.as_str()) {
-                Ok(c) => c,
-                Err(err) => {
-                    tracing::error!(error = %err, "Failed to create Redis client for chat push subscriber");
-                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                    continue;
-                }
-            };
+        for lane in recipient_lanes(rows) {
+            let services = Arc::clone(self);
+            let state = Arc::clone(state);
+            let lane_slots = Arc::clone(lane_slots);
+            tokio::spawn(async move { services.run_lane(&state, &lane_slots, lane).await });
+        }
+    }
 
-            let mut pubsub = match client.get_async_pubsub().await {
-                Ok(ps) => ps,
-                Err(err) => {
-                    tracing::error!(error = %err, "Failed to get Redis pubsub connection for chat push subscriber");
-                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                    continue;
-                }
-            };
+    /// Runs one lane, renewing its rows' leases until it has finished with
+    /// all of them, including while it waits for a slot. Until then no
+    /// other instance can claim the recipient. Every step of a row has its
+    /// own timeout, so a lane always finishes; if its instance dies, the
+    /// leases lapse within `LEASE_SECONDS`.
+    async fn run_lane(
+        &self,
+        state: &Arc<AppState>,
+        lane_slots: &tokio::sync::Semaphore,
+        rows: Vec<QueueRow>,
+    ) {
+        let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+        let work = async {
+            let _slot = lane_slots.acquire().await;
+            self.process_lane(state, rows).await;
+        };
+        tokio::pin!(work);
 
-            if let Err(err) = pubsub.subscribe("chat_push").await {
-                tracing::error!(error = %err, "Failed to subscribe to chat_push channel");
-                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                continue;
+        let mut renew =
+            tokio::time::interval(std::time::Duration::from_secs((LEASE_SECONDS / 3) as u64));
+        renew.tick().await;
+        loop {
+            tokio::select! {
+                _ = &mut work => break,
+                _ = renew.tick() => {
+                    if let Err(err) = self.queue.renew_leases(&ids).await {
+                        tracing::warn!(error = %err, "Failed to renew push queue leases");
+                    }
+                }
             }
+        }
+    }
 
-            tracing::info!("Subscribed to chat_push Redis channel");
+    /// Processes one recipient's rows in order.
+    async fn process_lane(&self, state: &Arc<AppState>, rows: Vec<QueueRow>) {
+        for row in rows {
+            metrics::add_push_events_in_flight(1.0);
+            let outcome = self.process_row(state, row).await;
+            metrics::add_push_events_in_flight(-1.0);
+            metrics::record_push_event_processed(outcome);
+        }
+    }
 
-            let mut stream = pubsub.on_message();
+    /// Decides on and delivers one claimed row, then deletes, reschedules,
+    /// or defers it. Returns the outcome recorded in metrics.
+    async fn process_row(&self, state: &Arc<AppState>, row: QueueRow) -> &'static str {
+        // Fast-path: skip rows for DIDs already known revoked
+        if self.revoked.contains(&row.recipient_did) {
+            tracing::debug!(
+                recipient = %row.recipient_did,
+                notification_type = %row.notification_type,
+                "Skipping push event for revoked account (worker cache)"
+            );
+            if let Err(err) = self.queue.delete(row.id).await {
+                tracing::error!(error = %err, "Failed to delete revoked-account push event");
+            }
+            return "revoked";
+        }
 
-            while let Some(msg) = stream.next().await {
-                let payload: String = match msg.get_payload() {
-                    Ok(p) => p,
-                    Err(err) => {
-                        tracing::warn!(error = %err, "Failed to get chat_push message payload");
-                        continue;
-                    }
-                };
+        // Staleness guard: drop events that sat in the queue
+        // past this age instead of delivering a long-dead
+        // notification. Protects against any future dormant
+        // producer/pipeline reactivation spraying weeks-old
+        // backlog (e.g. the 383k-row incident), independent of
+        // notification_type.
+        let age = time::OffsetDateTime::now_utc() - row.created_at;
+        if age > time::Duration::hours(i64::from(MAX_EVENT_AGE_HOURS)) {
+            tracing::debug!(
+                recipient = %row.recipient_did,
+                notification_type = %row.notification_type,
+                age_secs = age.whole_seconds(),
+                "Dropping stale queued push event"
+            );
+            if let Err(err) = self.queue.delete(row.id).await {
+                tracing::error!(error = %err, "Failed to delete stale push event");
+            }
+            return "stale";
+        }
 
-                let event: crate::services::chat_poll::types::ChatPushEvent =
-                    match serde_json::from_str(&payload) {
-                        Ok(e) => e,
-                        Err(err) => {
-                            tracing::warn!(error = %err, "Failed to deserialize ChatPushEvent");
-                            continue;
+        // Backstop for the whole decision. The network steps inside it carry
+        // their own timeouts; this catches whatever else might hang, so one
+        // stuck recipient can never hold its lane forever.
+        let evaluated = with_step_timeout(
+            "decision",
+            DECISION_TIMEOUT,
+            self.decision.evaluate(state, self, &row),
+        )
+        .await;
+        let evaluated = match evaluated {
+            Ok(result) => result,
+            Err(_elapsed) => {
+                // Requeue rather than drop: a timeout may be
+                // transient, and dropping would silently lose a
+                // deliverable notification.
+                tracing::warn!(
+                    recipient = %row.recipient_did,
+                    notification_type = %row.notification_type,
+                    timeout_secs = DECISION_TIMEOUT.as_secs(),
+                    "Push decision timed out; scheduling retry and moving on"
+                );
+                self.deliveries
+                    .record_decision((&row).into(), "error", "decision_timeout")
+                    .await;
+                if let Err(update_err) = self
+                    .queue
+                    .retry_later(row.id, row.attempts, "decision_timeout")
+                    .await
+                {
+                    tracing::error!(error = %update_err, "Failed to schedule push retry after timeout");
+                }
+                return "retried";
+            }
+        };
+        match evaluated {
+            Ok(QueueDisposition::Drop(reason)) => {
+                tracing::debug!(
+                    recipient = %row.recipient_did,
+                    notification_type = %row.notification_type,
+                    reason = reason,
+                    "Dropping queued push event"
+                );
+                self.deliveries
+                    .record_decision((&row).into(), "dropped", reason)
+                    .await;
+                if let Err(err) = self.queue.delete(row.id).await {
+                    tracing::error!(error = %err, "Failed to delete dropped push event");
+                }
+                "dropped"
+            }
+            Ok(QueueDisposition::Defer(delay_seconds)) => {
+                tracing::debug!(
+                    recipient = %row.recipient_did,
+                    notification_type = %row.notification_type,
+                    delay_seconds,
+                    "Deferring push event until quiet hours end"
+                );
+                self.deliveries
+                    .record_decision((&row).into(), "deferred", "quiet_hours")
+                    .await;
+                if let Err(err) = self
+                    .queue
+                    .defer_for_quiet_hours(row.id, delay_seconds)
+                    .await
+                {
+                    tracing::error!(error = %err, "Failed to defer push event for quiet hours");
+                }
+                "deferred"
+            }
+            Ok(QueueDisposition::Coalesce(actor_label)) => {
+                match self.coalescer.absorb(&row, &actor_label).await {
+                    Ok(()) => {
+                        let reason = if row.quiet_deferred {
+                            "quiet_hours_digest"
+                        } else {
+                            "grouped"
+                        };
+                        self.deliveries
+                            .record_decision((&row).into(), "coalesced", reason)
+                            .await;
+                        self.count_for_badge(state, &row).await;
+                        if let Err(err) = self.queue.delete(row.id).await {
+                            tracing::error!(error = %err, "Failed to delete coalesced push event");
                         }
-                    };
-
-                // Claim the queue row before doing anything else: only an
-                // UNLEASED row (one the durable worker hasn't picked up) is
-                // deleted here, so at most one path ever delivers this event.
-                let dedupe_key = event.dedupe_key();
-                match self.queue.claim_by_dedupe_key(&dedupe_key).await {
-                    Ok(true) => {}
-                    Ok(false) => {
-                        // Durable worker owns it (or it was already delivered).
-                        continue;
-                    }
-                    Err(err) => {
-                        tracing::warn!(error = %err, "Chat push claim failed; leaving to durable path");
-                        continue;
+                        "coalesced"
                     }
-                }
-
-                // Mirror decision.rs's preferences + moderation checks: the
-                // claim already consumed the row, so on either "drop" or
-                // check-error we must not silently swallow the event —
-                // check-errors requeue (delay 0) so the durable path can
-                // decide fail-closed, matching the durable worker's own
-                // error handling.
-                let prefs = match self.preferences.get_or_create(&event.recipient_did).await {
-                    Ok(prefs) => prefs,
                     Err(err) => {
                         tracing::warn!(
+                            recipient = %row.recipient_did,
+                            notification_type = %row.notification_type,
                             error = %err,
-                            did = %event.recipient_did,
-                            "Chat push fast-path preferences lookup failed; requeuing"
+                            "Failed to coalesce push event; scheduling retry"
                         );
-                        if let Err(err) = crate::services::chat_poll::poller::enqueue_push(
-                            self.queue.pool(),
-                            &event,
-                            0,
-                        )
-                        .await
+                        if let Err(update_err) = self
+                            .queue
+                            .retry_later(row.id, row.attempts, &err.to_string())
+                            .await
                         {
-                            tracing::error!(error = %err, "Failed to requeue chat push after preferences lookup failure");
+                            tracing::error!(error = %update_err, "Failed to schedule push retry");
                         }
-                        continue;
+                        "retried"
                     }
-                };
-                if !prefs.is_push_enabled_for("chat_message") {
-                    tracing::debug!(
-                        did = %event.recipient_did,
-                        "Chat push fast-path dropped: preferences_disabled"
-                    );
-                    continue;
                 }
+            }
+            Ok(QueueDisposition::Deliver(deliveries)) => {
+                let mut transient_error = None;
+                let mut revoked = false;
+                let badge = self.count_for_badge(state, &row).await;
 
-                match self
-                    .moderation_cache
-                    .is_actor_muted_or_blocked(&event.recipient_did, &event.sender_did)
-                    .await
-                {
-                    Ok(false) => {}
-                    Ok(true) => {
-                        tracing::debug!(
-                            did = %event.recipient_did,
-                            actor = %event.sender_did,
-                            "Chat push fast-path dropped: actor_muted_or_blocked"
-                        );
+                for (registration, mut notification) in deliveries {
+                    notification.badge = badge;
+                    let Some(transport) = self.transports.for_platform(&registration.platform)
+                    else {
                         continue;
-                    }
-                    Err(err) => {
-                        tracing::warn!(
-                            error = %err,
-                            did = %event.recipient_did,
-                            "Chat push fast-path moderation lookup failed; requeuing"
-                        );
-                        if let Err(err) = crate::services::chat_poll::poller::enqueue_push(
-                            self.queue.pool(),
-                            &event,
-                            0,
-                        )
+                    };
+                    match self
+                        .send_logged(transport, (&row).into(), &registration, &notification)
                         .await
-                        {
-                            tracing::error!(error = %err, "Failed to requeue chat push after moderation lookup failure");
+                    {
+                        Ok(_) => {}
+                        Err(DeliveryError::InvalidToken { reason, .. }) => {
+                            self.deactivate_dead_token(state, &registration, reason)
+                                .await;
+                        }
+                        Err(DeliveryError::Other(err)) if is_auth_revocation_error(&err) => {
+                            tracing::info!(
+                                recipient = %row.recipient_did,
+                                error = %err,
+                                "Auth revoked during delivery; skipping remaining events for account"
+                            );
+                            self.revoked.insert(&row.recipient_did);
+                            revoked = true;
+                            break;
+                        }
+                        Err(DeliveryError::Other(err)) => {
+                            transient_error = Some(err);
+                            break;
                         }
-                        continue;
                     }
                 }
 
-                // Look up active registrations for recipient
-                let registrations = match self
-                    .registry
-                    .list_active_registrations(&event.recipient_did)
+                if revoked {
+                    // Auth was revoked during delivery — delete, don't retry
+                    if let Err(err) = self.queue.delete(row.id).await {
+                        tracing::error!(error = %err, "Failed to delete revoked-account push event");
+                    }
+                    "revoked"
+                } else if let Some(err) = transient_error {
+                    // Known limitation: retrying the row re-sends to
+                    // registrations that already succeeded this attempt.
+                    // Acceptable for rare, genuinely transient errors
+                    // (network/5xx) — dead tokens must never reach this
+                    // arm (transports report them as
+                    // `DeliveryError::InvalidToken` and we deactivate).
+                    tracing::warn!(
+                        recipient = %row.recipient_did,
+                        notification_type = %row.notification_type,
+                        error = %err,
+                        "Transient push delivery failure; scheduling retry"
+                    );
+                    if let Err(update_err) = self
+                        .queue
+                        .retry_later(row.id, row.attempts, &err.to_string())
+                        .await
+                    {
+                        tracing::error!(error = %update_err, "Failed to schedule push retry");
+                    }
+                    "retried"
+                } else {
+                    if let Err(err) = self.queue.delete(row.id).await {
+                        tracing::error!(error = %err, "Failed to delete delivered push event");
+                    }
+                    "delivered"
+                }
+            }
+            Err(err) if is_auth_revocation_error(&err) => {
+                tracing::info!(
+                    recipient = %row.recipient_did,
+                    error = %err,
+                    "Auth revoked during decision evaluation; skipping remaining events for account"
+                );
+                self.revoked.insert(&row.recipient_did);
+                if let Err(del_err) = self.queue.delete(row.id).await {
+                    tracing::error!(error = %del_err, "Failed to delete revoked-account push event");
+                }
+                "revoked"
+            }
+            Err(err) if is_unregistered_account_error(&err) => {
+                // Terminal, not transient: without a
+                // push_accounts row this event fails the same
+                // way on every attempt.
+                tracing::info!(
+                    recipient = %row.recipient_did,
+                    notification_type = %row.notification_type,
+                    "Recipient has no push account; dropping undeliverable event"
+                );
+                if let Err(del_err) = self.queue.delete(row.id).await {
+                    tracing::error!(error = %del_err, "Failed to delete undeliverable push event");
+                }
+                "dropped"
+            }
+            Err(err) => {
+                tracing::warn!(
+                    recipient = %row.recipient_did,
+                    notification_type = %row.notification_type,
+                    error = %err,
+                    "Push decision pipeline failed; scheduling retry"
+                );
+                self.deliveries
+                    .record_decision((&row).into(), "error", &err.to_string())
+                    .await;
+                if let Err(update_err) = self
+                    .queue
+                    .retry_later(row.id, row.attempts, &err.to_string())
                     .await
                 {
-                    Ok(r) => r,
-                    Err(err) => {
-                        tracing::warn!(error = %err, "Failed to look up registrations for chat push");
-                        continue;
-                    }
-                };
+                    tracing::error!(error = %update_err, "Failed to schedule push retry");
+                }
+                "retried"
+            }
+        }
+    }
 
-                if registrations.is_empty() {
-                    // No device to deliver to — the claim already consumed
-                    // the event, matching the durable path's Drop disposition.
+    /// Sends one summary per coalesce group whose window has closed.
+    async fn flush_coalesced_groups(&self, state: &AppState, batch_size: i64) {
+        let groups = match self.coalescer.claim_due(batch_size).await {
+            Ok(groups) => groups,
+            Err(err) => {
+                tracing::error!(error = %err, "Push coalesce group claim failed");
+                return;
+            }
+        };
+
+        for group in groups {
+            let registrations = match self
+                .registry
+                .list_active_registrations(&group.recipient_did)
+                .await
+            {
+                Ok(registrations) => registrations,
+                Err(err) => {
+                    tracing::warn!(error = %err, "Failed to load registrations for push summary");
+                    if let Err(err) = self.coalescer.retry_later(&group).await {
+                        tracing::error!(error = %err, "Failed to re-arm push coalesce group");
+                    }
                     continue;
                 }
+            };
 
-                // Build notification
-                let mut custom_data = std::collections::HashMap::new();
-                custom_data.insert("type".to_string(), "chat_message".to_string());
-                custom_data.insert("recipientDid".to_string(), event.recipient_did.clone());
-                custom_data.insert("convoId".to_string(), event.convo_id.clone());
-                custom_data.insert("messageId".to_string(), event.message_id.clone());
-                custom_data.insert("senderDid".to_string(), event.sender_did.clone());
-                let truncated_text: String = event.message_text.chars().take(200).collect();
-                custom_data.insert("messageText".to_string(), truncated_text);
-
-                let notification = apns::ApnsNotification {
-                    title: "New Message".to_string(),
-                    body: "You have a new message".to_string(),
-                    user_did: event.recipient_did.clone(),
-                    custom_data,
-                    mutable_content: true,
-                    thread_id: Some(format!("chat:{}", event.convo_id)),
+            let prefs = match self.preferences.get(&group.recipient_did).await {
+                Ok(prefs) => prefs.unwrap_or_default(),
+                Err(err) => {
+                    tracing::warn!(error = %err, "Failed to load preferences for push summary");
+                    if let Err(err) = self.coalescer.retry_later(&group).await {
+                        tracing::error!(error = %err, "Failed to re-arm push coalesce group");
+                    }
+                    continue;
+                }
+            };
+            // Device overrides may have changed since the group was opened;
+            // a digest holds mixed types and went through them per event.
+            let registrations: Vec<RegistrationRow> =
+                match group.notification_type.parse::<NotificationKind>() {
+                    Ok(kind) => registrations
+                        .into_iter()
+                        .filter(|registration| {
+                            prefs
+                                .with_overrides(registration.preference_overrides.as_ref())
+                                .is_push_enabled_for(kind)
+                        })
+                        .collect(),
+                    Err(_) => registrations,
                 };
 
-                // Fan out to all devices
-                let mut delivered_count = 0usize;
-                for registration in &registrations {
-                    match apns.send(registration, &notification).await {
-                        Ok(delivered_env) => {
-                            delivered_count += 1;
-                            if registration.apns_environment.as_deref() != Some(delivered_env) {
-                                tracing::info!(
-                                    did = %registration.did,
-                                    token = %registration.device_token,
-                                    env = delivered_env,
-                                    "Learned APNs environment (chat push fast-path)"
-                                );
-                                if let Err(err) = self
-                                    .registry
-                                    .set_apns_environment(
-                                        &registration.did,
-                                        &registration.device_token,
-                                        delivered_env,
-                                    )
-                                    .await
-                                {
-                                    tracing::warn!(error = %err, "Failed to persist learned APNs environment (chat push fast-path)");
-                                }
-                            }
-                        }
-                        Err(err) if is_invalid_token(&err) => {
-                            tracing::info!(
-                                did = %registration.did,
-                                token = %registration.device_token,
-                                "Deactivating invalid APNs token (chat push fast-path)"
-                            );
-                            if let Err(update_err) = self
-                                .registry
-                                .deactivate_invalid_token(
-                                    &registration.did,
-                                    &registration.device_token,
-                                    "apns_unregistered",
-                                )
-                                .await
-                            {
-                                tracing::error!(error = %update_err, "Failed to deactivate invalid APNs token");
-                            } else if let Some(push_db) = state.push_db.as_ref() {
-                                let scheduler =
-                                    crate::services::chat_poll::scheduler::ChatPollScheduler::new(
-                                        push_db.clone(),
-                                    );
-                                if let Err(err) = scheduler
-                                    .unenroll_account_if_no_active_devices(&registration.did)
-                                    .await
-                                {
-                                    tracing::warn!(did = %registration.did, error = %err, "Chat poll unenroll (APNs token death) failed");
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            tracing::warn!(
-                                error = %err,
-                                did = %event.recipient_did,
-                                token = %registration.device_token,
-                                "Chat push fast-path delivery failed"
-                            );
-                        }
-                    }
+            // The window may have closed inside the recipient's quiet hours.
+            let quiet_hours = &prefs.quiet_hours;
+            let now = Utc::now();
+            let quiet_until = quiet_hours.active_until(now);
+            if let (Some(until), QuietHoursMode::Defer) = (quiet_until, quiet_hours.mode) {
+                if let Err(err) = self
+                    .coalescer
+                    .defer(&group, (until - now).num_seconds())
+                    .await
+                {
+                    tracing::error!(error = %err, "Failed to defer push summary for quiet hours");
                 }
+                continue;
+            }
 
-                // We claimed the row; if NOTHING was delivered, hand it back
-                // to the durable path so the notification isn't lost.
-                if delivered_count == 0 {
-                    if let Err(err) = crate::services::chat_poll::poller::enqueue_push(
-                        self.queue.pool(),
-                        &event,
-                        0,
-                    )
+            let mut notification = build_summary(&group);
+            notification.silent = quiet_until.is_some();
+            notification.badge = self.current_badge(&group.recipient_did).await;
+            let logged = LoggedEvent {
+                recipient_did: &group.recipient_did,
+                notification_type: &group.notification_type,
+                queue_event_id: None,
+            };
+            let mut transient_error = None;
+            for registration in &registrations {
+                let Some(transport) = self.transports.for_platform(&registration.platform) else {
+                    continue;
+                };
+                match self
+                    .send_logged(transport, logged, registration, &notification)
                     .await
-                    {
-                        tracing::error!(error = %err, "Failed to requeue chat push after fast-path failure");
+                {
+                    Ok(_) => {}
+                    Err(DeliveryError::InvalidToken { reason, .. }) => {
+                        self.deactivate_dead_token(state, registration, reason)
+                            .await;
                     }
+                    Err(DeliveryError::Other(err)) => transient_error = Some(err),
                 }
             }
 
-            // Stream ended — reconnect
-            tracing::warn!("Chat push Redis subscription stream ended; reconnecting...");
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            if let Some(err) = transient_error {
+                // Same trade-off as the queue: a retry re-sends to devices
+                // that already got it, but the collapse id makes that a
+                // silent replacement rather than a duplicate banner.
+                tracing::warn!(
+                    recipient = %group.recipient_did,
+                    notification_type = %group.notification_type,
+                    error = %err,
+                    "Transient push summary failure; scheduling retry"
+                );
+                if let Err(err) = self.coalescer.retry_later(&group).await {
+                    tracing::error!(error = %err, "Failed to re-arm push coalesce group");
+                }
+            }
         }
     }
-}
 
-fn is_invalid_token(err: &anyhow::Error) -> bool {
-    if let Some(a2_err) = err.downcast_ref::<a2::Error>() {
-        if let a2::Error::ResponseError(response) = a2_err {
-            if response.code == 410 {
-                return true;
-            }
-            // ApnsDelivery::send already retries BadDeviceToken once against
-            // the other environment, so a BadDeviceToken reaching here means
-            // both endpoints rejected the token — it's genuinely invalid,
-            // not just aimed at the wrong environment.
-            if let Some(body) = response.error.as_ref() {
-                if body.reason == a2::ErrorReason::BadDeviceToken {
-                    return true;
+    /// Counts `row` toward the recipient's badge and returns the new
+    /// total. Chat messages were already counted by the chat poller, and a
+    /// retried event by its first attempt; both just read the total.
+    async fn count_for_badge(&self, state: &Arc<AppState>, row: &QueueRow) -> Option<u32> {
+        if row.notification_type == NotificationKind::ChatMessage || row.badge_counted {
+            return self.current_badge(&row.recipient_did).await;
+        }
+        match self
+            .badges
+            .count_notification(state, &row.recipient_did)
+            .await
+        {
+            Ok(badge) => {
+                if let Err(err) = self.queue.mark_badge_counted(row.id).await {
+                    tracing::warn!(recipient = %row.recipient_did, error = %err, "Failed to mark push event as counted");
                 }
+                Some(badge)
+            }
+            Err(err) => {
+                tracing::warn!(recipient = %row.recipient_did, error = %err, "Failed to count push event toward badge");
+                None
+            }
+        }
+    }
+
+    async fn current_badge(&self, account_did: &str) -> Option<u32> {
+        match self.badges.total(account_did).await {
+            Ok(badge) => Some(badge),
+            Err(err) => {
+                tracing::warn!(recipient = %account_did, error = %err, "Failed to load push badge count");
+                None
             }
         }
     }
 
-    let message = err.to_string().to_ascii_lowercase();
-    message.contains("unregistered")
+    /// Sends the current badge, with no alert, to the account's APNs
+    /// devices. Called after an unread count drops.
+    pub async fn send_badge_update(&self, state: &AppState, account_did: &str) {
+        let Some(badge) = self.current_badge(account_did).await else {
+            return;
+        };
+        let notification = PushNotification::badge_only(account_did, badge);
+        self.send_to_devices(state, account_did, "badge", &notification)
+            .await;
+    }
+
+    /// Tells every device of the account to remove the notifications it
+    /// was shown for a chat conversation once it has been read elsewhere,
+    /// or for `message_id` once that message is deleted, and drops the
+    /// ones still queued so they never alert.
+    pub async fn retract_chat_notifications(
+        &self,
+        state: &AppState,
+        account_did: &str,
+        convo_id: &str,
+        message_id: Option<&str>,
+    ) {
+        if let Err(err) = self
+            .queue
+            .discard_chat_events(account_did, convo_id, message_id)
+            .await
+        {
+            tracing::warn!(did = %account_did, error = %err, "Failed to discard queued chat push events");
+        }
+        let badge = self.current_badge(account_did).await;
+        let notification =
+            PushNotification::chat_retraction(account_did, convo_id, message_id, badge);
+        self.send_to_devices(state, account_did, "chat_retract", &notification)
+            .await;
+    }
+
+    /// Best-effort send of a notification that isn't a queued event: a
+    /// lost one is corrected by the next alert, so failures aren't retried.
+    async fn send_to_devices(
+        &self,
+        state: &AppState,
+        account_did: &str,
+        notification_type: &str,
+        notification: &PushNotification,
+    ) {
+        let registrations = match self.registry.list_active_registrations(account_did).await {
+            Ok(registrations) => registrations,
+            Err(err) => {
+                tracing::warn!(did = %account_did, notification_type, error = %err, "Failed to load registrations for push update");
+                return;
+            }
+        };
+
+        let logged = LoggedEvent {
+            recipient_did: account_did,
+            notification_type,
+            queue_event_id: None,
+        };
+        for registration in &registrations {
+            let Some(transport) = self
+                .transports
+                .for_notification(&registration.platform, notification)
+            else {
+                continue;
+            };
+            match self
+                .send_logged(transport, logged, registration, notification)
+                .await
+            {
+                Ok(_) => {}
+                Err(DeliveryError::InvalidToken { reason, .. }) => {
+                    self.deactivate_dead_token(state, registration, reason)
+                        .await;
+                }
+                Err(DeliveryError::Other(err)) => {
+                    tracing::debug!(did = %account_did, notification_type, error = %err, "Push update failed");
+                }
+            }
+        }
+    }
 }
 
 pub(crate) async fn resolve_background_session(
@@ -654,6 +894,31 @@ pub(crate) async fn resolve_background_session(
         .as_ref()
         .ok_or_else(|| anyhow!("Jacquard client not configured"))?;
 
+    // Accounts with push or chat polling need warm tokens even while the app
+    // is closed, so background use counts as activity for the refresher.
+    if let Err(err) = auth_store.record_session_activity(session_id).await {
+        tracing::debug!(error = %err, "Failed to record background session activity");
+    }
+
+    let app_password_session =
+        match crate::services::app_password::resolve_session(state, auth_store, session_id).await
+        {
+            Ok(found) => found,
+            Err(err) => {
+                record_auth_failure(auth_store, session_id, AuthFailureSource::Background, &err)
+                    .await;
+                return Err(err.into());
+            }
+        };
+    if let Some((session, dpop)) = app_password_session {
+        if session.did != account_did {
+            return Err(anyhow!(
+                "App-password session belongs to a different DID than expected"
+            ));
+        }
+        return Ok((session, dpop));
+    }
+
     if let Some(mapped_did) = auth_store.lookup_did_for_session(session_id).await? {
         if mapped_did != account_did {
             tracing::warn!(
@@ -666,11 +931,22 @@ pub(crate) async fn resolve_background_session(
 
     let did = Did::new(account_did)
         .map_err(|err| anyhow!("Invalid DID in push background session: {}", err))?;
-    let session_data = jacquard_client
-        .registry
-        .get(&did, session_id, true)
-        .await
-        .map_err(|err| anyhow!("Jacquard session lookup failed: {}", err))?;
+    let session_data = match crate::services::token_refresher::oauth_session(
+        auth_store,
+        jacquard_client,
+        &did,
+        session_id,
+        state.config.token_refresher.claim_ttl_seconds,
+        "Jacquard session lookup failed",
+    )
+    .await
+    {
+        Ok(data) => data,
+        Err(err) => {
+            record_auth_failure(auth_store, session_id, AuthFailureSource::Background, &err).await;
+            return Err(err.into());
+        }
+    };
 
     let expires_at = session_data
         .token_set
@@ -704,28 +980,120 @@ pub(crate) async fn resolve_background_session(
         access_token_expires_at: expires_at,
         created_at: Utc::now(),
         last_used_at: Utc::now(),
+        auth_kind: SessionAuthKind::OAuth,
     };
 
     Ok((session, dpop))
 }
 
-/// Upper bound on evaluating one queued event. The decision path performs
-/// network work with no internal timeout, and the worker is sequential, so an
-/// unbounded hang wedges every pending notification behind it.
+/// Channel the `push_event_queue_notify` trigger signals on every insert.
+const QUEUE_NOTIFY_CHANNEL: &str = "push_event_queue";
+
+/// Upper bound on evaluating one queued event, on top of the per-step
+/// timeouts below. A hang anywhere else in the decision would otherwise hold
+/// the recipient's lane, and every later event for them, indefinitely.
 const DECISION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
+/// Moderation state refresh from the recipient's PDS.
+pub(crate) const MODERATION_SYNC_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Actor profile and label lookups. Both fall back (no name, redacted text)
+/// rather than fail the event.
+pub(crate) const HYDRATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Unread count fetched from the recipient's PDS to seed the badge. On
+/// timeout the event is counted locally instead.
+pub(crate) const BADGE_SEED_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// One transport send to one device.
+const SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long an account found revoked mid-delivery is skipped without a
+/// decision; by then `purge_revoked_accounts` has cleared its queue.
+const REVOKED_ACCOUNT_TTL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Runs one push worker step under its own timeout, counting timeouts by
+/// step in metrics.
+pub(crate) async fn with_step_timeout<T>(
+    step: &'static str,
+    limit: std::time::Duration,
+    future: impl Future<Output = T>,
+) -> Result<T> {
+    match tokio::time::timeout(limit, future).await {
+        Ok(output) => Ok(output),
+        Err(_elapsed) => {
+            metrics::record_push_step_timeout(step);
+            Err(StepTimeout {
+                step,
+                seconds: limit.as_secs(),
+            }
+            .into())
+        }
+    }
+}
+
+/// A pipeline step that ran past its limit; see `with_step_timeout`.
+#[derive(Debug, thiserror::Error)]
+#[error("push {step} timed out after {seconds}s")]
+pub(crate) struct StepTimeout {
+    pub step: &'static str,
+    pub seconds: u64,
+}
+
+/// Accounts whose auth was found revoked during processing. Shared by every
+/// worker lane, so their remaining events are dropped without another
+/// decision.
+#[derive(Clone, Default)]
+pub struct RevokedAccounts {
+    seen: Arc<DashMap<String, std::time::Instant>>,
+}
+
+impl RevokedAccounts {
+    pub fn insert(&self, did: &str) {
+        self.seen.insert(did.to_string(), std::time::Instant::now());
+    }
+
+    pub fn contains(&self, did: &str) -> bool {
+        self.seen
+            .get(did)
+            .is_some_and(|seen_at| seen_at.elapsed() < REVOKED_ACCOUNT_TTL)
+    }
+
+    pub fn purge_expired(&self) {
+        self.seen
+            .retain(|_, seen_at| seen_at.elapsed() < REVOKED_ACCOUNT_TTL);
+    }
+}
+
+/// Splits a claimed batch into one lane per recipient, keeping claim order
+/// within each lane and ordering lanes by their oldest event.
+fn recipient_lanes(rows: Vec<QueueRow>) -> Vec<Vec<QueueRow>> {
+    let mut lanes: Vec<Vec<QueueRow>> = Vec::new();
+    let mut lane_by_recipient: std::collections::HashMap<String, usize> =
+        std::collections::HashMap::new();
+    for row in rows {
+        match lane_by_recipient.get(&row.recipient_did) {
+            Some(&lane) => lanes[lane].push(row),
+            None => {
+                lane_by_recipient.insert(row.recipient_did.clone(), lanes.len());
+                lanes.push(vec![row]);
+            }
+        }
+    }
+    lanes
+}
+
+/// True when the account's session is gone for good: a permanent OAuth /
+/// refresh failure, or a session that no longer exists. Retrying can never
+/// succeed, so callers drop the work (and usually the account's enrolment).
 pub(crate) fn is_auth_revocation_error(err: &anyhow::Error) -> bool {
-    let message = err.to_string().to_ascii_lowercase();
-    message.contains("invalid_grant")
-        || message.contains("invalid_token")
-        || message.contains("no refresh token")
-        || message.contains("no per-session oauth data")
-        || message.contains("session not found")
-        || message.contains("session expired")
-        // Jacquard phrases a missing session as "session does not exist".
-        // Without this the event is rescheduled forever, because the session
-        // is never coming back.
-        || message.contains("session does not exist")
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<AppError>())
+        .any(|app_err| match app_err {
+            AppError::OAuth(failure) => failure.permanent,
+            AppError::SessionExpired | AppError::InvalidSession => true,
+            _ => false,
+        })
 }
 
 /// True when the recipient has no `push_accounts` row.
@@ -748,30 +1116,45 @@ pub(crate) fn push_unavailable_error() -> AppError {
 #[cfg(test)]
 mod terminal_failure_tests {
     use super::*;
+    use crate::error::OAuthFailure;
+
+    fn oauth_failure(permanent: bool) -> anyhow::Error {
+        AppError::OAuth(OAuthFailure {
+            kind: crate::error::OAuthFailureKind::GrantRejected,
+            permanent,
+            http_status: Some(400),
+            error_code: Some("invalid_grant".into()),
+            message: "Jacquard session lookup failed".into(),
+        })
+        .into()
+    }
 
     #[test]
-    fn session_does_not_exist_is_auth_revocation() {
-        // Observed verbatim in production: 57 occurrences in six minutes,
-        // each one rescheduled forever because this phrasing was not matched.
-        let err = anyhow::anyhow!("Jacquard session lookup failed: session does not exist");
+    fn missing_session_is_auth_revocation() {
+        // Observed in production: 57 occurrences in six minutes, each one
+        // rescheduled forever because the session was never coming back.
+        let err: anyhow::Error = AppError::OAuth(OAuthFailure::from_session_error(
+            "Jacquard session lookup failed",
+            &jacquard_oauth::session::Error::SessionNotFound,
+        ))
+        .into();
         assert!(is_auth_revocation_error(&err));
     }
 
     #[test]
-    fn existing_auth_revocation_phrasings_still_match() {
-        for message in [
-            "invalid_grant",
-            "invalid_token",
-            "no refresh token",
-            "no per-session oauth data",
-            "session not found",
-            "session expired",
-        ] {
-            assert!(
-                is_auth_revocation_error(&anyhow::anyhow!("{message}")),
-                "regressed on {message}"
-            );
-        }
+    fn permanent_failures_are_auth_revocation() {
+        assert!(is_auth_revocation_error(&oauth_failure(true)));
+        assert!(is_auth_revocation_error(&AppError::SessionExpired.into()));
+        assert!(is_auth_revocation_error(
+            &anyhow::Error::from(AppError::InvalidSession).context("resolving session")
+        ));
+    }
+
+    #[test]
+    fn transient_oauth_failures_stay_retryable() {
+        assert!(!is_auth_revocation_error(&oauth_failure(false)));
+        // Strings are no longer sniffed: only the typed failure counts.
+        assert!(!is_auth_revocation_error(&anyhow::anyhow!("invalid_grant")));
     }
 
     #[test]
@@ -800,3 +1183,69 @@ mod terminal_failure_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod worker_tests {
+    use super::*;
+
+    fn row(id: i64, recipient_did: &str) -> QueueRow {
+        QueueRow {
+            id,
+            recipient_did: recipient_did.to_string(),
+            actor_did: "did:plc:actor".to_string(),
+            notification_type: NotificationKind::Like,
+            event_cid: format!("bafy{id}"),
+            event_path: format!("app.bsky.feed.like/{id}"),
+            subject_uri: None,
+            thread_root_uri: None,
+            event_record_json: serde_json::json!({}),
+            event_timestamp: id,
+            created_at: time::OffsetDateTime::now_utc(),
+            attempts: 1,
+            quiet_deferred: false,
+            badge_counted: false,
+        }
+    }
+
+    #[test]
+    fn lanes_keep_each_recipients_claim_order() {
+        let lanes = recipient_lanes(vec![
+            row(1, "did:plc:alice"),
+            row(2, "did:plc:bob"),
+            row(3, "did:plc:alice"),
+            row(4, "did:plc:carol"),
+            row(5, "did:plc:bob"),
+        ]);
+
+        let ids: Vec<Vec<i64>> = lanes
+            .iter()
+            .map(|lane| lane.iter().map(|row| row.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![1, 3], vec![2, 5], vec![4]]);
+    }
+
+    #[test]
+    fn revoked_accounts_are_shared_between_clones() {
+        let revoked = RevokedAccounts::default();
+        let lane_view = revoked.clone();
+
+        revoked.insert("did:plc:gone");
+        assert!(lane_view.contains("did:plc:gone"));
+        assert!(!lane_view.contains("did:plc:alice"));
+
+        lane_view.purge_expired();
+        assert!(revoked.contains("did:plc:gone"));
+    }
+
+    #[tokio::test]
+    async fn step_timeout_reports_the_step() {
+        let err = with_step_timeout(
+            "send",
+            std::time::Duration::from_millis(10),
+            std::future::pending::<()>(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "push send timed out after 0s");
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
+use dashmap::DashSet;
 use reqwest::Method;
 use serde_json::Value;
 use sqlx::{Pool, Postgres, Row};
@@ -13,10 +14,17 @@ use crate::{
     services::{AtProtoClient, ProxyResponse},
 };
 
+use super::{
+    labels::LabelPreferences,
+    muted_words::{muted_words_from_preferences, MutedWord},
+};
+
 #[derive(Clone)]
 pub struct ModerationCache {
     db_pool: Pool<Postgres>,
     sync_interval: Duration,
+    /// Accounts whose follow graph is being re-fetched in the background.
+    follow_syncs: Arc<DashSet<String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -31,13 +39,25 @@ impl ModerationCache {
         Self {
             db_pool,
             sync_interval: Duration::seconds(sync_interval_seconds as i64),
+            follow_syncs: Arc::new(DashSet::new()),
         }
     }
 
-    pub async fn ensure_fresh(&self, state: &Arc<AppState>, user_did: &str) -> Result<()> {
+    /// Re-syncs stale moderation state for `user_did`. The follow graph is
+    /// only fetched when `include_follows` is set or an `exclude-following`
+    /// muted word needs it — most accounts never do — and then in the
+    /// background: a large graph takes longer than the push worker waits, so
+    /// callers decide on the cached follows until it lands.
+    pub async fn ensure_fresh(
+        &self,
+        state: &Arc<AppState>,
+        user_did: &str,
+        include_follows: bool,
+    ) -> Result<()> {
         let account = sqlx::query(
             r#"
-            SELECT session_id, pds_url, last_actor_sync_at, last_list_sync_at, auth_revoked_at
+            SELECT session_id, pds_url, last_actor_sync_at, last_list_sync_at,
+                   last_follow_sync_at, auth_revoked_at
             FROM push_accounts
             WHERE account_did = $1
             "#,
@@ -71,8 +91,15 @@ impl ModerationCache {
             .try_get::<Option<OffsetDateTime>, _>("last_list_sync_at")?
             .map(|ts| now - ts >= self.sync_interval)
             .unwrap_or(true);
-
-        if !actor_stale && !list_stale {
+        let include_follows =
+            include_follows || self.has_exclude_following_muted_words(user_did).await?;
+        let follows_stale = include_follows
+            && account
+                .try_get::<Option<OffsetDateTime>, _>("last_follow_sync_at")?
+                .map(|ts| now - ts >= self.sync_interval)
+                .unwrap_or(true);
+
+        if !actor_stale && !list_stale && !follows_stale {
             return Ok(());
         }
 
@@ -125,9 +152,41 @@ impl ModerationCache {
             .await?;
         }
 
+        if follows_stale {
+            self.spawn_follow_sync(state, session, dpop);
+        }
+
         Ok(())
     }
 
+    /// Re-fetches `session`'s follow graph off the caller's path, at most
+    /// once at a time per account.
+    fn spawn_follow_sync(
+        &self,
+        state: &Arc<AppState>,
+        session: CatbirdSession,
+        dpop: JacquardDpopData,
+    ) {
+        if !self.follow_syncs.insert(session.did.clone()) {
+            return;
+        }
+        let cache = self.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = cache
+                .refresh_follows_for_session(&state, &session, &dpop)
+                .await
+            {
+                tracing::warn!(
+                    user_did = %session.did,
+                    error = %err,
+                    "Follow graph sync failed; keeping the cached follows"
+                );
+            }
+            cache.follow_syncs.remove(&session.did);
+        });
+    }
+
     pub async fn refresh_actor_relationships_for_session(
         &self,
         state: &Arc<AppState>,
@@ -160,6 +219,168 @@ impl ModerationCache {
         Ok(())
     }
 
+    pub async fn refresh_follows_for_session(
+        &self,
+        state: &Arc<AppState>,
+        session: &CatbirdSession,
+        dpop: &JacquardDpopData,
+    ) -> Result<()> {
+        self.sync_follows(state, session, dpop).await?;
+        sqlx::query(
+            "UPDATE push_accounts SET last_follow_sync_at = NOW(), updated_at = NOW() WHERE account_did = $1",
+        )
+        .bind(&session.did)
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn upsert_follow(
+        &self,
+        user_did: &str,
+        followed_did: &str,
+        follow_rkey: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_follows (user_did, followed_did, follow_rkey)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_did, followed_did)
+            DO UPDATE SET follow_rkey = COALESCE(EXCLUDED.follow_rkey, user_follows.follow_rkey)
+            "#,
+        )
+        .bind(user_did)
+        .bind(followed_did)
+        .bind(follow_rkey)
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the follow stored under `follow_rkey`. Returns false when no
+    /// row carries that rkey (the follow was created without one, or before
+    /// the last sync), in which case the caller must re-sync to find it.
+    pub async fn remove_follow_by_rkey(&self, user_did: &str, follow_rkey: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM user_follows WHERE user_did = $1 AND follow_rkey = $2")
+                .bind(user_did)
+                .bind(follow_rkey)
+                .execute(&self.db_pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Marks the follow cache stale so the next `ensure_fresh` that needs it
+    /// re-fetches the whole graph.
+    pub async fn invalidate_follows(&self, user_did: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE push_accounts SET last_follow_sync_at = NULL, updated_at = NOW() WHERE account_did = $1",
+        )
+        .bind(user_did)
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn replace_muted_words(&self, user_did: &str, words: &[MutedWord]) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_muted_words (user_did, muted_words_json, updated_at)
+            VALUES ($1, $2::jsonb, NOW())
+            ON CONFLICT (user_did)
+            DO UPDATE
+            SET muted_words_json = EXCLUDED.muted_words_json,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(user_did)
+        .bind(serde_json::to_value(words)?)
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn replace_label_preferences(
+        &self,
+        user_did: &str,
+        prefs: &LabelPreferences,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_label_preferences (user_did, label_prefs_json, updated_at)
+            VALUES ($1, $2::jsonb, NOW())
+            ON CONFLICT (user_did)
+            DO UPDATE
+            SET label_prefs_json = EXCLUDED.label_prefs_json,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(user_did)
+        .bind(serde_json::to_value(prefs)?)
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Mirrors a proxied `app.bsky.actor.putPreferences`, which replaces the
+    /// whole preference set — a body without `mutedWordsPref` clears them.
+    pub async fn apply_put_preferences(&self, user_did: &str, body: &Value) -> Result<()> {
+        self.replace_muted_words(user_did, &muted_words_from_preferences(body))
+            .await?;
+        self.replace_label_preferences(user_did, &LabelPreferences::from_preferences(body))
+            .await
+    }
+
+    /// The recipient's label settings; accounts never synced get the app's
+    /// defaults (adult content off, only Bluesky's moderation labeler).
+    pub async fn label_preferences(&self, user_did: &str) -> Result<LabelPreferences> {
+        let row =
+            sqlx::query("SELECT label_prefs_json FROM user_label_preferences WHERE user_did = $1")
+                .bind(user_did)
+                .fetch_optional(&self.db_pool)
+                .await?;
+
+        Ok(row
+            .map(|row| row.try_get::<Value, _>("label_prefs_json"))
+            .transpose()?
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    pub async fn muted_words(&self, user_did: &str) -> Result<Vec<MutedWord>> {
+        let row = sqlx::query("SELECT muted_words_json FROM user_muted_words WHERE user_did = $1")
+            .bind(user_did)
+            .fetch_optional(&self.db_pool)
+            .await?;
+
+        Ok(row
+            .map(|row| row.try_get::<Value, _>("muted_words_json"))
+            .transpose()?
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    async fn has_exclude_following_muted_words(&self, user_did: &str) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM user_muted_words m,
+                     jsonb_array_elements(m.muted_words_json) AS word
+                WHERE m.user_did = $1
+                  AND word->>'actorTarget' = 'exclude-following'
+            ) AS needed
+            "#,
+        )
+        .bind(user_did)
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(row.try_get::<bool, _>("needed")?)
+    }
+
     pub async fn upsert_actor_mute(&self, user_did: &str, muted_did: &str) -> Result<()> {
         sqlx::query(
             r#"
@@ -366,6 +587,22 @@ impl ModerationCache {
         Ok(row.try_get::<bool, _>("filtered")?)
     }
 
+    pub async fn is_following(&self, user_did: &str, actor_did: &str) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM user_follows WHERE user_did = $1 AND followed_did = $2
+            ) AS following
+            "#,
+        )
+        .bind(user_did)
+        .bind(actor_did)
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(row.try_get::<bool, _>("following")?)
+    }
+
     pub async fn is_thread_muted(&self, user_did: &str, thread_root_uri: &str) -> Result<bool> {
         let row = sqlx::query(
             r#"
@@ -400,6 +637,16 @@ impl ModerationCache {
                 "blocks",
             )
             .await?;
+        let preferences = self
+            .fetch_xrpc_json(state, session, dpop, "app.bsky.actor.getPreferences", None)
+            .await?;
+        self.replace_muted_words(&session.did, &muted_words_from_preferences(&preferences))
+            .await?;
+        self.replace_label_preferences(
+            &session.did,
+            &LabelPreferences::from_preferences(&preferences),
+        )
+        .await?;
 
         let mut tx = self.db_pool.begin().await?;
         sqlx::query("DELETE FROM user_mutes WHERE user_did = $1")
@@ -435,6 +682,39 @@ impl ModerationCache {
         Ok(())
     }
 
+    async fn sync_follows(
+        &self,
+        state: &Arc<AppState>,
+        session: &CatbirdSession,
+        dpop: &JacquardDpopData,
+    ) -> Result<()> {
+        let follows = self.fetch_follows(state, session, dpop).await?;
+
+        let mut tx = self.db_pool.begin().await?;
+        sqlx::query("DELETE FROM user_follows WHERE user_did = $1")
+            .bind(&session.did)
+            .execute(&mut *tx)
+            .await?;
+
+        for (followed_did, follow_rkey) in follows {
+            sqlx::query(
+                r#"
+                INSERT INTO user_follows (user_did, followed_did, follow_rkey)
+                VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(&session.did)
+            .bind(followed_did)
+            .bind(follow_rkey)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn sync_list_relationships(
         &self,
         state: &Arc<AppState>,
@@ -577,6 +857,54 @@ impl ModerationCache {
         Ok(dids)
     }
 
+    /// `(followed DID, follow record rkey)` for everyone `session.did`
+    /// follows. The rkey comes from `viewer.following`, which the AppView
+    /// fills in because the viewer is the account being listed.
+    async fn fetch_follows(
+        &self,
+        state: &Arc<AppState>,
+        session: &CatbirdSession,
+        dpop: &JacquardDpopData,
+    ) -> Result<Vec<(String, Option<String>)>> {
+        let mut cursor = None::<String>;
+        let mut follows = Vec::new();
+        let mut pages = 0usize;
+
+        loop {
+            let mut query = format!("actor={}&limit=100", urlencoding::encode(&session.did));
+            if let Some(cursor_value) = cursor.as_deref() {
+                query.push_str("&cursor=");
+                query.push_str(&urlencoding::encode(cursor_value));
+            }
+
+            let payload = self
+                .fetch_xrpc_json(
+                    state,
+                    session,
+                    dpop,
+                    "app.bsky.graph.getFollows",
+                    Some(&query),
+                )
+                .await?;
+
+            if let Some(items) = payload.get("follows").and_then(|value| value.as_array()) {
+                follows.extend(items.iter().filter_map(follow_from_profile_json));
+            }
+
+            pages += 1;
+            let next = payload
+                .get("cursor")
+                .and_then(|value| value.as_str())
+                .map(str::to_owned);
+            match advance_cursor(cursor.as_deref(), next, pages, "app.bsky.graph.getFollows")? {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        Ok(follows)
+    }
+
     async fn fetch_paginated_lists(
         &self,
         state: &Arc<AppState>,
@@ -735,6 +1063,18 @@ fn parse_json_body(body: &Bytes) -> Result<Value> {
     Ok(serde_json::from_slice(body)?)
 }
 
+fn follow_from_profile_json(profile: &Value) -> Option<(String, Option<String>)> {
+    let did = profile.get("did").and_then(Value::as_str)?;
+    let rkey = profile
+        .get("viewer")
+        .and_then(|viewer| viewer.get("following"))
+        .and_then(Value::as_str)
+        .and_then(|uri| uri.rsplit('/').next())
+        .filter(|rkey| !rkey.is_empty())
+        .map(str::to_owned);
+    Some((did.to_string(), rkey))
+}
+
 /// Upper bound on pages fetched from a single paginated XRPC endpoint.
 /// At 100 items per page this is 50,000 items — far beyond any real
 /// moderation list, while guaranteeing termination.
@@ -746,8 +1086,8 @@ const MAX_PAGINATION_PAGES: usize = 500;
 /// alone is not a termination guarantee: an upstream that repeats or pins its
 /// cursor spins forever. Because these loops never return `Err`, such a spin
 /// is invisible — no error is logged, the enclosing sync never records
-/// completion, and since the push worker awaits `ensure_fresh` inline while
-/// processing rows sequentially, one wedged account stalls *all* push
+/// completion, and back when the push worker awaited `ensure_fresh` inline
+/// while processing rows sequentially, one wedged account stalled *all* push
 /// delivery. That is exactly how notifications stopped for over a day.
 ///
 /// So termination rests on three conditions, not one: the cursor is absent
@@ -783,6 +1123,27 @@ fn advance_cursor(
 mod tests {
     use super::*;
 
+    #[test]
+    fn follow_rkey_is_taken_from_viewer_following() {
+        let profile = serde_json::json!({
+            "did": "did:plc:bob",
+            "handle": "bob.example",
+            "viewer": {
+                "following": "at://did:plc:alice/app.bsky.graph.follow/3kabc"
+            }
+        });
+        assert_eq!(
+            follow_from_profile_json(&profile),
+            Some(("did:plc:bob".to_string(), Some("3kabc".to_string())))
+        );
+
+        let without_viewer = serde_json::json!({ "did": "did:plc:carol" });
+        assert_eq!(
+            follow_from_profile_json(&without_viewer),
+            Some(("did:plc:carol".to_string(), None))
+        );
+    }
+
     #[test]
     fn absent_cursor_ends_pagination() {
         assert_eq!(advance_cursor(Some("a"), None, 1, "test").unwrap(), None);
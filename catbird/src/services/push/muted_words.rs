@@ -0,0 +1,297 @@
+//! Bluesky muted words (`app.bsky.actor.defs#mutedWordsPref`).
+//!
+//! Matching mirrors `hasMutedWord` in `@atproto/api` so a post hidden in the
+//! app is also kept off the lock screen: tags match for either target,
+//! everything else only for `content`, with the same single-character,
+//! phrase, and punctuation rules.
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const MUTED_WORDS_PREF_TYPE: &str = "app.bsky.actor.defs#mutedWordsPref";
+const TAG_FACET_TYPE: &str = "app.bsky.richtext.facet#tag";
+
+/// Languages without word separators; the app falls back to substring
+/// matching for them.
+const LANGUAGE_EXCEPTIONS: &[&str] = &["ja", "zh", "ko", "th", "vi"];
+
+lazy_static! {
+    static ref SPACE_OR_PUNCTUATION: Regex = Regex::new(r"[\s\p{P}]").unwrap();
+    static ref PUNCTUATION: Regex = Regex::new(r"\p{P}+").unwrap();
+    static ref EDGE_PUNCTUATION: Regex = Regex::new(r"^\p{P}+|\p{P}+$").unwrap();
+    static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MutedWord {
+    pub value: String,
+    /// `content` and/or `tag`.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// `all` or `exclude-following`.
+    #[serde(rename = "actorTarget", default = "default_actor_target")]
+    pub actor_target: String,
+    #[serde(rename = "expiresAt", default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+impl MutedWord {
+    pub fn excludes_following(&self) -> bool {
+        self.actor_target == "exclude-following"
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+            .is_some_and(|expires_at| expires_at.with_timezone(&Utc) < now)
+    }
+}
+
+/// Extracts the muted words from an `app.bsky.actor.getPreferences` response
+/// or a `putPreferences` body. No `mutedWordsPref` means no muted words.
+pub fn muted_words_from_preferences(payload: &Value) -> Vec<MutedWord> {
+    payload
+        .get("preferences")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|pref| pref.get("$type").and_then(Value::as_str) == Some(MUTED_WORDS_PREF_TYPE))
+        .filter_map(|pref| pref.get("items").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|item| serde_json::from_value::<MutedWord>(item.clone()).ok())
+        .map(|mut word| {
+            word.value = word.value.trim().trim_start_matches('#').to_string();
+            word
+        })
+        .filter(|word| !word.value.is_empty())
+        .collect()
+}
+
+/// True when `record` (a post-shaped event record) contains any active muted
+/// word. `actor_followed` is whether the recipient follows the author, for
+/// `exclude-following` words.
+pub fn has_muted_word(
+    words: &[MutedWord],
+    record: &Value,
+    actor_followed: bool,
+    now: DateTime<Utc>,
+) -> bool {
+    let text = record
+        .get("text")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_lowercase();
+    let tags = record_tags(record);
+    let language_exception = record
+        .get("langs")
+        .and_then(Value::as_array)
+        .and_then(|langs| langs.first())
+        .and_then(Value::as_str)
+        .is_some_and(|lang| LANGUAGE_EXCEPTIONS.contains(&lang));
+
+    words.iter().any(|word| {
+        if word.is_expired(now) || (word.excludes_following() && actor_followed) {
+            return false;
+        }
+        let muted = word.value.to_lowercase();
+        // `content` applies to tags as well.
+        if tags.contains(&muted) {
+            return true;
+        }
+        if !word.targets.iter().any(|target| target == "content") {
+            return false;
+        }
+        content_matches(&muted, &text, language_exception)
+    })
+}
+
+fn record_tags(record: &Value) -> Vec<String> {
+    let outline_tags = record
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str);
+    let facet_tags = record
+        .get("facets")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|facet| facet.get("features").and_then(Value::as_array))
+        .flatten()
+        .filter(|feature| feature.get("$type").and_then(Value::as_str) == Some(TAG_FACET_TYPE))
+        .filter_map(|feature| feature.get("tag").and_then(Value::as_str));
+    outline_tags
+        .chain(facet_tags)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn content_matches(muted: &str, text: &str, language_exception: bool) -> bool {
+    let muted_len = muted.chars().count();
+    if (muted_len == 1 || language_exception) && text.contains(muted) {
+        return true;
+    }
+    if muted_len > text.chars().count() {
+        return false;
+    }
+    if muted == text {
+        return true;
+    }
+    // Phrases and punctuated words can only match as substrings.
+    if SPACE_OR_PUNCTUATION.is_match(muted) && text.contains(muted) {
+        return true;
+    }
+
+    text.split(char::is_whitespace).any(|word| {
+        if word == muted {
+            return true;
+        }
+        // Ignore leading/trailing punctuation but keep internal punctuation
+        // (`s@ssy`) significant.
+        let trimmed = EDGE_PUNCTUATION.replace_all(word, "");
+        if trimmed == muted {
+            return true;
+        }
+        if muted_len > trimmed.chars().count() || !PUNCTUATION.is_match(&trimmed) {
+            return false;
+        }
+        let spaced = PUNCTUATION.replace_all(&trimmed, " ");
+        if spaced == muted || WHITESPACE.replace_all(&spaced, "") == muted {
+            return true;
+        }
+        PUNCTUATION.split(&trimmed).any(|part| part == muted)
+    })
+}
+
+fn default_actor_target() -> String {
+    "all".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn word(value: &str, targets: &[&str]) -> MutedWord {
+        MutedWord {
+            value: value.to_string(),
+            targets: targets.iter().map(|target| target.to_string()).collect(),
+            actor_target: default_actor_target(),
+            expires_at: None,
+        }
+    }
+
+    fn matches(words: &[MutedWord], record: Value) -> bool {
+        has_muted_word(words, &record, false, Utc::now())
+    }
+
+    #[test]
+    fn parses_muted_words_pref_from_preferences() {
+        let payload = json!({
+            "preferences": [
+                { "$type": "app.bsky.actor.defs#adultContentPref", "enabled": false },
+                {
+                    "$type": "app.bsky.actor.defs#mutedWordsPref",
+                    "items": [
+                        { "id": "1", "value": "#Spoilers", "targets": ["tag"] },
+                        {
+                            "value": "crypto",
+                            "targets": ["content", "tag"],
+                            "actorTarget": "exclude-following",
+                            "expiresAt": "2030-01-01T00:00:00.000Z"
+                        },
+                        { "value": "  ", "targets": ["content"] }
+                    ]
+                }
+            ]
+        });
+
+        let words = muted_words_from_preferences(&payload);
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].value, "Spoilers");
+        assert_eq!(words[0].actor_target, "all");
+        assert!(words[1].excludes_following());
+        assert!(muted_words_from_preferences(&json!({ "preferences": [] })).is_empty());
+    }
+
+    #[test]
+    fn content_words_match_whole_words_only() {
+        let words = [word("cat", &["content"])];
+        assert!(matches(&words, json!({ "text": "My cat is asleep" })));
+        assert!(matches(&words, json!({ "text": "look, a cat!" })));
+        assert!(!matches(&words, json!({ "text": "concatenate these" })));
+    }
+
+    #[test]
+    fn tag_only_words_ignore_plain_text() {
+        let words = [word("spoilers", &["tag"])];
+        assert!(!matches(&words, json!({ "text": "no spoilers here" })));
+        assert!(matches(
+            &words,
+            json!({ "text": "x", "tags": ["Spoilers"] })
+        ));
+        assert!(matches(
+            &words,
+            json!({
+                "text": "#spoilers",
+                "facets": [{
+                    "index": { "byteStart": 0, "byteEnd": 9 },
+                    "features": [{ "$type": "app.bsky.richtext.facet#tag", "tag": "spoilers" }]
+                }]
+            })
+        ));
+    }
+
+    #[test]
+    fn phrases_and_internal_punctuation_match() {
+        assert!(matches(
+            &[word("hot take", &["content"])],
+            json!({ "text": "here is my hot take on it" })
+        ));
+        assert!(matches(
+            &[word("sassy", &["content"])],
+            json!({ "text": "so s-a-s-s-y today" })
+        ));
+        assert!(matches(
+            &[word("s@ssy", &["content"])],
+            json!({ "text": "feeling s@ssy" })
+        ));
+    }
+
+    #[test]
+    fn languages_without_word_breaks_match_substrings() {
+        let words = [word("ねこ", &["content"])];
+        assert!(matches(
+            &words,
+            json!({ "text": "うちのねこがかわいい", "langs": ["ja"] })
+        ));
+    }
+
+    #[test]
+    fn expired_and_followed_exclusions_are_skipped() {
+        let mut expired = word("cat", &["content"]);
+        expired.expires_at = Some("2020-01-01T00:00:00.000Z".to_string());
+        assert!(!matches(&[expired], json!({ "text": "cat" })));
+
+        let mut exclude_following = word("cat", &["content"]);
+        exclude_following.actor_target = "exclude-following".to_string();
+        let record = json!({ "text": "cat" });
+        assert!(has_muted_word(
+            std::slice::from_ref(&exclude_following),
+            &record,
+            false,
+            Utc::now()
+        ));
+        assert!(!has_muted_word(
+            &[exclude_following],
+            &record,
+            true,
+            Utc::now()
+        ));
+    }
+}
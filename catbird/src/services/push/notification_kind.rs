@@ -0,0 +1,205 @@
+//! The kinds of event the push queue carries.
+//!
+//! Producers (catbird-firehose, the chat poller) write the wire name into
+//! `push_event_queue.notification_type`; it is parsed once when a row is
+//! claimed, and everything downstream matches on the enum. A name this
+//! build doesn't know is dead-lettered at claim time rather than sent as a
+//! generic notification.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationKind {
+    Mention,
+    Reply,
+    Like,
+    Follow,
+    Repost,
+    Quote,
+    LikeViaRepost,
+    RepostViaRepost,
+    /// New post from an account the recipient has an activity subscription to.
+    ActivityPost,
+    /// New reply from an account the recipient has an activity subscription to.
+    ActivityReply,
+    StarterpackJoined,
+    Verified,
+    Unverified,
+    ChatMessage,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 14] = [
+        Self::Mention,
+        Self::Reply,
+        Self::Like,
+        Self::Follow,
+        Self::Repost,
+        Self::Quote,
+        Self::LikeViaRepost,
+        Self::RepostViaRepost,
+        Self::ActivityPost,
+        Self::ActivityReply,
+        Self::StarterpackJoined,
+        Self::Verified,
+        Self::Unverified,
+        Self::ChatMessage,
+    ];
+
+    /// Name used in the queue, the delivery log, and the `reason` custom
+    /// data key clients read.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Mention => "mention",
+            Self::Reply => "reply",
+            Self::Like => "like",
+            Self::Follow => "follow",
+            Self::Repost => "repost",
+            Self::Quote => "quote",
+            Self::LikeViaRepost => "via_like",
+            Self::RepostViaRepost => "via_repost",
+            Self::ActivityPost => "activity_post",
+            Self::ActivityReply => "activity_reply",
+            Self::StarterpackJoined => "starterpack_joined",
+            Self::Verified => "verified",
+            Self::Unverified => "unverified",
+            Self::ChatMessage => "chat_message",
+        }
+    }
+
+    /// Catalog key of the notification title for a single event.
+    pub fn title_key(self) -> &'static str {
+        match self {
+            Self::Mention => "PUSH_MENTION_TITLE",
+            Self::Reply => "PUSH_REPLY_TITLE",
+            Self::Like => "PUSH_LIKE_TITLE",
+            Self::Follow => "PUSH_FOLLOW_TITLE",
+            Self::Repost => "PUSH_REPOST_TITLE",
+            Self::Quote => "PUSH_QUOTE_TITLE",
+            Self::LikeViaRepost => "PUSH_LIKE_VIA_REPOST_TITLE",
+            Self::RepostViaRepost => "PUSH_REPOST_VIA_REPOST_TITLE",
+            Self::ActivityPost => "PUSH_ACTIVITY_POST_TITLE",
+            Self::ActivityReply => "PUSH_ACTIVITY_REPLY_TITLE",
+            Self::StarterpackJoined => "PUSH_STARTERPACK_JOINED_TITLE",
+            Self::Verified => "PUSH_VERIFIED_TITLE",
+            Self::Unverified => "PUSH_UNVERIFIED_TITLE",
+            Self::ChatMessage => "PUSH_CHAT_TITLE",
+        }
+    }
+
+    /// Catalog key of the body for kinds whose body is "<actor> <action>",
+    /// taking the actor label as its one argument. `None` for kinds whose
+    /// body quotes the record text instead.
+    pub fn action_key(self) -> Option<&'static str> {
+        match self {
+            Self::Follow => Some("PUSH_FOLLOW_BODY"),
+            Self::Like => Some("PUSH_LIKE_BODY"),
+            Self::Repost => Some("PUSH_REPOST_BODY"),
+            Self::LikeViaRepost => Some("PUSH_LIKE_VIA_REPOST_BODY"),
+            Self::RepostViaRepost => Some("PUSH_REPOST_VIA_REPOST_BODY"),
+            Self::StarterpackJoined => Some("PUSH_STARTERPACK_JOINED_BODY"),
+            Self::Verified => Some("PUSH_VERIFIED_BODY"),
+            Self::Unverified => Some("PUSH_UNVERIFIED_BODY"),
+            Self::Mention
+            | Self::Reply
+            | Self::Quote
+            | Self::ActivityPost
+            | Self::ActivityReply
+            | Self::ChatMessage => None,
+        }
+    }
+
+    /// Kinds folded into one running summary per subject.
+    pub fn coalesces(self) -> bool {
+        matches!(self, Self::Like | Self::Repost | Self::Follow)
+    }
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownNotificationKind(pub String);
+
+impl fmt::Display for UnknownNotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown notification type: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownNotificationKind {}
+
+impl FromStr for NotificationKind {
+    type Err = UnknownNotificationKind;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| UnknownNotificationKind(value.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::push::localization::{self, DEFAULT_LOCALE};
+
+    #[test]
+    fn wire_names_round_trip() {
+        for kind in NotificationKind::ALL {
+            assert_eq!(kind.as_str().parse::<NotificationKind>(), Ok(kind));
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert_eq!(
+            "starterpack-joined".parse::<NotificationKind>(),
+            Err(UnknownNotificationKind("starterpack-joined".to_string()))
+        );
+        assert!("".parse::<NotificationKind>().is_err());
+        assert!("Like".parse::<NotificationKind>().is_err());
+    }
+
+    #[test]
+    fn every_kind_has_a_title_and_a_body_source() {
+        for kind in NotificationKind::ALL {
+            let title = localization::render(DEFAULT_LOCALE, kind.title_key(), &[]);
+            assert_ne!(title, kind.title_key(), "{kind} has no title");
+            if let Some(key) = kind.action_key() {
+                let body = localization::render(DEFAULT_LOCALE, key, &["Alice".to_string()]);
+                assert_ne!(body, key, "{kind} has no body");
+            }
+        }
+        assert_eq!(
+            localization::render(
+                DEFAULT_LOCALE,
+                NotificationKind::StarterpackJoined.action_key().unwrap(),
+                &["Alice".to_string()]
+            ),
+            "Alice joined Bluesky with your starter pack"
+        );
+        assert_eq!(NotificationKind::Mention.action_key(), None);
+    }
+
+    #[test]
+    fn only_likes_reposts_and_follows_coalesce() {
+        let coalesced: Vec<_> = NotificationKind::ALL
+            .into_iter()
+            .filter(|kind| kind.coalesces())
+            .collect();
+        assert_eq!(
+            coalesced,
+            vec![
+                NotificationKind::Like,
+                NotificationKind::Follow,
+                NotificationKind::Repost
+            ]
+        );
+    }
+}
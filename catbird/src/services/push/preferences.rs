@@ -2,7 +2,10 @@ use anyhow::Result;
 use serde_json::Value;
 use sqlx::{Pool, Postgres, Row};
 
-use super::types::{PushPreferencesDocument, PutPreferencesInput};
+use super::{
+    quiet_hours::QuietHours,
+    types::{PushPreferencesDocument, PutPreferencesInput},
+};
 
 #[derive(Clone)]
 pub struct PushPreferences {
@@ -69,4 +72,11 @@ impl PushPreferences {
         self.put(did, &updated).await?;
         Ok(updated)
     }
+
+    pub async fn put_quiet_hours(&self, did: &str, quiet_hours: QuietHours) -> Result<QuietHours> {
+        let mut current = self.get_or_create(did).await?;
+        current.quiet_hours = quiet_hours;
+        self.put(did, &current).await?;
+        Ok(current.quiet_hours)
+    }
 }
@@ -0,0 +1,328 @@
+//! Actor profile hydration for notification text.
+//!
+//! The worker warms the cache once per claimed batch with
+//! `app.bsky.actor.getProfiles`, so a burst of likes costs one AppView call
+//! per 25 distinct actors instead of one per event. Profiles are cached in
+//! `push_actor_profiles`; actors the AppView doesn't know are cached too, for
+//! a shorter time.
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+
+/// `getProfiles` accepts at most this many actors per call.
+const PROFILES_PER_REQUEST: usize = 25;
+
+const PROFILE_TTL_SECONDS: i64 = 60 * 60;
+const MISSING_PROFILE_TTL_SECONDS: i64 = 10 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActorProfile {
+    /// Display name, or `@handle` when it is blank.
+    pub label: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct ProfileHydrator {
+    db_pool: Pool<Postgres>,
+    appview_url: String,
+}
+
+impl ProfileHydrator {
+    pub fn new(db_pool: Pool<Postgres>, appview_url: &str) -> Self {
+        Self {
+            db_pool,
+            appview_url: appview_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Fetches every actor in `actor_dids` that isn't freshly cached.
+    /// Failures are logged and left for `get` to fall back on.
+    pub async fn prefetch(&self, http_client: &reqwest::Client, actor_dids: &[String]) {
+        let missing = match self.uncached(actor_dids).await {
+            Ok(missing) => missing,
+            Err(err) => {
+                tracing::warn!(error = %err, "Failed to read cached push actor profiles");
+                return;
+            }
+        };
+
+        for chunk in missing.chunks(PROFILES_PER_REQUEST) {
+            let fetched = match fetch_profiles(http_client, &self.appview_url, chunk).await {
+                Ok(fetched) => fetched,
+                Err(err) => {
+                    tracing::debug!(
+                        count = chunk.len(),
+                        error = %err,
+                        "AppView profile batch lookup failed for push actors"
+                    );
+                    continue;
+                }
+            };
+            for actor_did in chunk {
+                if let Err(err) = self.store(actor_did, fetched.get(actor_did)).await {
+                    tracing::warn!(actor = %actor_did, error = %err, "Failed to cache push actor profile");
+                }
+            }
+        }
+    }
+
+    /// The actor's profile, fetched on a cache miss. `None` when the AppView
+    /// has no profile or can't be reached.
+    pub async fn get(
+        &self,
+        http_client: &reqwest::Client,
+        actor_did: &str,
+    ) -> Option<ActorProfile> {
+        match self.cached(actor_did).await {
+            Ok(Some(cached)) => return cached,
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!(actor = %actor_did, error = %err, "Failed to read cached push actor profile");
+            }
+        }
+
+        self.prefetch(http_client, &[actor_did.to_string()]).await;
+        self.cached(actor_did).await.ok().flatten().flatten()
+    }
+
+    /// Removes entries too old to be served. Returns the number removed.
+    pub async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM push_actor_profiles WHERE fetched_at < NOW() - make_interval(secs => $1)",
+        )
+        .bind(PROFILE_TTL_SECONDS)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// `Some(None)` is a cached miss; `None` means nothing fresh is cached.
+    async fn cached(&self, actor_did: &str) -> Result<Option<Option<ActorProfile>>> {
+        let row = sqlx::query_as::<_, (Option<String>, Option<String>, bool)>(
+            r#"
+            SELECT label, avatar_url, found
+            FROM push_actor_profiles
+            WHERE actor_did = $1
+              AND fetched_at >= NOW() - make_interval(
+                    secs => CASE WHEN found THEN $2 ELSE $3 END
+                  )
+            "#,
+        )
+        .bind(actor_did)
+        .bind(PROFILE_TTL_SECONDS)
+        .bind(MISSING_PROFILE_TTL_SECONDS)
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row
+            .map(|(label, avatar_url, found)| found.then_some(ActorProfile { label, avatar_url })))
+    }
+
+    async fn uncached(&self, actor_dids: &[String]) -> Result<Vec<String>> {
+        let mut unique = actor_dids.to_vec();
+        unique.sort();
+        unique.dedup();
+
+        let missing = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT requested.actor_did
+            FROM UNNEST($1::text[]) AS requested(actor_did)
+            LEFT JOIN push_actor_profiles p
+              ON p.actor_did = requested.actor_did
+             AND p.fetched_at >= NOW() - make_interval(
+                    secs => CASE WHEN p.found THEN $2 ELSE $3 END
+                 )
+            WHERE p.actor_did IS NULL
+            "#,
+        )
+        .bind(&unique)
+        .bind(PROFILE_TTL_SECONDS)
+        .bind(MISSING_PROFILE_TTL_SECONDS)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(missing)
+    }
+
+    async fn store(&self, actor_did: &str, profile: Option<&ActorProfile>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO push_actor_profiles (actor_did, label, avatar_url, found, fetched_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (actor_did)
+            DO UPDATE
+            SET label = EXCLUDED.label,
+                avatar_url = EXCLUDED.avatar_url,
+                found = EXCLUDED.found,
+                fetched_at = NOW()
+            "#,
+        )
+        .bind(actor_did)
+        .bind(profile.and_then(|profile| profile.label.as_deref()))
+        .bind(profile.and_then(|profile| profile.avatar_url.as_deref()))
+        .bind(profile.is_some())
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Profiles by DID for one `getProfiles` call. Actors the AppView omits
+/// (deleted, taken down, unknown) are absent from the map.
+async fn fetch_profiles(
+    http_client: &reqwest::Client,
+    appview_url: &str,
+    actor_dids: &[String],
+) -> Result<HashMap<String, ActorProfile>> {
+    let mut url = Url::parse(&format!("{appview_url}/xrpc/app.bsky.actor.getProfiles"))?;
+    {
+        let mut query = url.query_pairs_mut();
+        for actor_did in actor_dids {
+            query.append_pair("actors", actor_did);
+        }
+    }
+
+    let response = http_client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("getProfiles returned HTTP {}", response.status()));
+    }
+
+    let payload: Value = response.json().await?;
+    Ok(payload
+        .get("profiles")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|profile| {
+            let did = profile.get("did").and_then(Value::as_str)?;
+            Some((did.to_string(), actor_profile_from_json(profile)))
+        })
+        .collect())
+}
+
+fn actor_profile_from_json(profile: &Value) -> ActorProfile {
+    ActorProfile {
+        label: actor_label_from_profile_json(profile),
+        avatar_url: profile
+            .get("avatar")
+            .and_then(Value::as_str)
+            .filter(|avatar| avatar.starts_with("https://"))
+            .map(str::to_string),
+    }
+}
+
+fn actor_label_from_profile_json(profile: &Value) -> Option<String> {
+    profile
+        .get("displayName")
+        .and_then(Value::as_str)
+        .and_then(clean_profile_label)
+        .or_else(|| {
+            profile
+                .get("handle")
+                .and_then(Value::as_str)
+                .and_then(clean_profile_label)
+                .map(|handle| {
+                    if handle.starts_with('@') {
+                        handle
+                    } else {
+                        format!("@{handle}")
+                    }
+                })
+        })
+}
+
+fn clean_profile_label(value: &str) -> Option<String> {
+    let collapsed = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        None
+    } else {
+        Some(collapsed.chars().take(80).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn profile_label_prefers_display_name_over_handle() {
+        let profile = json!({
+            "did": "did:plc:alice123",
+            "handle": "alice.example",
+            "displayName": "Alice Example"
+        });
+
+        assert_eq!(
+            actor_label_from_profile_json(&profile).as_deref(),
+            Some("Alice Example")
+        );
+    }
+
+    #[test]
+    fn profile_label_falls_back_to_handle_when_display_name_is_blank() {
+        let profile = json!({
+            "did": "did:plc:alice123",
+            "handle": "alice.example",
+            "displayName": "   "
+        });
+
+        assert_eq!(
+            actor_label_from_profile_json(&profile).as_deref(),
+            Some("@alice.example")
+        );
+    }
+
+    #[tokio::test]
+    async fn get_profiles_batch_maps_profiles_by_did() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfiles"))
+            .and(query_param("actors", "did:plc:bob"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "profiles": [
+                    {
+                        "did": "did:plc:alice",
+                        "handle": "alice.example",
+                        "displayName": "Alice",
+                        "avatar": "https://cdn.bsky.app/img/avatar/plain/did:plc:alice/bafy@jpeg"
+                    },
+                    { "did": "did:plc:bob", "handle": "bob.example" }
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let profiles = fetch_profiles(
+            &reqwest::Client::new(),
+            &server.uri(),
+            &[
+                "did:plc:alice".to_string(),
+                "did:plc:bob".to_string(),
+                "did:plc:gone".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles["did:plc:alice"].label.as_deref(), Some("Alice"));
+        assert!(profiles["did:plc:alice"].avatar_url.is_some());
+        assert_eq!(
+            profiles["did:plc:bob"],
+            ActorProfile {
+                label: Some("@bob.example".to_string()),
+                avatar_url: None,
+            }
+        );
+        assert!(!profiles.contains_key("did:plc:gone"));
+    }
+}
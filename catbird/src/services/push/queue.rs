@@ -2,20 +2,87 @@ use anyhow::Result;
 use serde_json::Value;
 use sqlx::{Pool, Postgres};
 
-use super::types::QueueRow;
+use crate::metrics;
+
+use super::types::{QueueRecord, QueueRow};
+
+/// Moves the queue rows selected by the `doomed` CTE into
+/// `push_event_dead_letter`, returning each one's notification type.
+const DEAD_LETTER_INSERT: &str = r#"
+    INSERT INTO push_event_dead_letter (
+        queue_event_id,
+        recipient_did,
+        actor_did,
+        notification_type,
+        event_cid,
+        event_path,
+        subject_uri,
+        thread_root_uri,
+        event_record_json,
+        event_timestamp,
+        dedupe_key,
+        quiet_deferred,
+        badge_counted,
+        attempts,
+        final_error,
+        queued_at
+    )
+    SELECT
+        id,
+        recipient_did,
+        actor_did,
+        notification_type,
+        event_cid,
+        event_path,
+        subject_uri,
+        thread_root_uri,
+        event_record_json,
+        event_timestamp,
+        dedupe_key,
+        quiet_deferred,
+        badge_counted,
+        attempts,
+        final_error,
+        created_at
+    FROM doomed
+    RETURNING notification_type
+"#;
+
+/// How long a claim holds its rows. The worker renews the lease while it is
+/// still working on them, so this only bounds how long a crashed instance's
+/// rows stay unavailable.
+pub const LEASE_SECONDS: i64 = 30;
+
+/// Events older than this are dropped by the worker instead of delivered,
+/// and left in the dead letter table by replay.
+pub const MAX_EVENT_AGE_HOURS: i32 = 24;
 
 #[derive(Clone)]
 pub struct PushQueue {
     db_pool: Pool<Postgres>,
+    max_attempts: i32,
 }
 
 impl PushQueue {
-    pub fn new(db_pool: Pool<Postgres>) -> Self {
-        Self { db_pool }
+    pub fn new(db_pool: Pool<Postgres>, max_attempts: u32) -> Self {
+        Self {
+            db_pool,
+            max_attempts: max_attempts.clamp(1, i32::MAX as u32) as i32,
+        }
     }
 
+    /// Leases up to `batch_size` ready events, oldest first.
+    ///
+    /// A recipient belongs to one claim at a time: recipients with a live
+    /// lease anywhere are skipped, and the transaction-scoped advisory lock
+    /// keeps two instances claiming concurrently from splitting one
+    /// recipient's events between them. That is what lets several instances
+    /// run workers without reordering anyone's notifications.
+    ///
+    /// Rows whose type this build doesn't know are dead-lettered instead of
+    /// returned.
     pub async fn claim_ready(&self, batch_size: i64) -> Result<Vec<QueueRow>> {
-        let rows = sqlx::query_as::<_, QueueRow>(
+        let records = sqlx::query_as::<_, QueueRecord>(
             r#"
             WITH claimed AS (
                 SELECT peq.id
@@ -24,12 +91,21 @@ impl PushQueue {
                 WHERE peq.available_at <= NOW()
                   AND (peq.leased_until IS NULL OR peq.leased_until < NOW())
                   AND (pa.auth_revoked_at IS NULL)
+                  -- Exhausted rows wait for `dead_letter_abandoned`.
+                  AND peq.attempts < $2
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM push_event_queue busy
+                      WHERE busy.recipient_did = peq.recipient_did
+                        AND busy.leased_until >= NOW()
+                  )
+                  AND pg_try_advisory_xact_lock(hashtext('push_event_queue:' || peq.recipient_did))
                 ORDER BY peq.created_at ASC
                 LIMIT $1
                 FOR UPDATE OF peq SKIP LOCKED
             )
             UPDATE push_event_queue q
-            SET leased_until = NOW() + INTERVAL '30 seconds',
+            SET leased_until = NOW() + make_interval(secs => $3),
                 attempts = q.attempts + 1,
                 updated_at = NOW()
             FROM claimed
@@ -46,16 +122,55 @@ impl PushQueue {
                 q.event_record_json,
                 q.event_timestamp,
                 q.created_at,
-                q.attempts
+                q.attempts,
+                q.quiet_deferred,
+                q.badge_counted
             "#,
         )
         .bind(batch_size)
+        .bind(self.max_attempts)
+        .bind(LEASE_SECONDS)
         .fetch_all(&self.db_pool)
         .await?;
 
+        let mut rows = Vec::with_capacity(records.len());
+        for record in records {
+            let id = record.id;
+            match QueueRow::try_from(record) {
+                Ok(row) => rows.push(row),
+                Err(err) => {
+                    if let Err(dead_letter_err) = self.dead_letter(id, &err.to_string()).await {
+                        tracing::error!(
+                            queue_event_id = id,
+                            error = %dead_letter_err,
+                            "Failed to dead-letter push event of unknown type"
+                        );
+                    }
+                }
+            }
+        }
         Ok(rows)
     }
 
+    /// Extends the lease on whichever of `ids` are still leased. Rows already
+    /// deleted, retried, or deferred are left alone.
+    pub async fn renew_leases(&self, ids: &[i64]) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE push_event_queue
+            SET leased_until = NOW() + make_interval(secs => $2)
+            WHERE id = ANY($1)
+              AND leased_until IS NOT NULL
+            "#,
+        )
+        .bind(ids)
+        .bind(LEASE_SECONDS)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Delete all queued events for accounts whose auth has been revoked.
     /// Returns the number of rows deleted.
     pub async fn purge_revoked_accounts(&self) -> Result<u64> {
@@ -82,7 +197,50 @@ impl PushQueue {
         Ok(())
     }
 
+    /// Deletes the chat events still queued for a conversation, or for one
+    /// message in it, once they have been read or deleted. That covers
+    /// events held by quiet hours or waiting on a retry, which would
+    /// otherwise alert after the retraction.
+    pub async fn discard_chat_events(
+        &self,
+        recipient_did: &str,
+        convo_id: &str,
+        message_id: Option<&str>,
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM push_event_queue
+            WHERE recipient_did = $1
+              AND notification_type = 'chat_message'
+              AND event_record_json->>'convoId' = $2
+              AND ($3::TEXT IS NULL OR event_cid = $3)
+            "#,
+        )
+        .bind(recipient_did)
+        .bind(convo_id)
+        .bind(message_id)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Records that an event has been counted toward its recipient's badge,
+    /// so a retry sends the current total instead of counting it again.
+    pub async fn mark_badge_counted(&self, id: i64) -> Result<()> {
+        sqlx::query("UPDATE push_event_queue SET badge_counted = TRUE WHERE id = $1")
+            .bind(id)
+            .execute(&self.db_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Reschedules a failed event with backoff, or dead-letters it once it
+    /// has used up `max_attempts`.
     pub async fn retry_later(&self, id: i64, attempts: i32, error: &str) -> Result<()> {
+        if attempts >= self.max_attempts {
+            return self.dead_letter(id, error).await;
+        }
         let backoff_seconds = i64::from((attempts.max(1) * 5).min(300));
         sqlx::query(
             r#"
@@ -102,22 +260,81 @@ impl PushQueue {
         Ok(())
     }
 
-    /// Atomically claim a queued event by dedupe key. Returns true only if an
-    /// UNLEASED row was deleted — the caller then owns delivery. Returns false
-    /// if the row is absent or the durable worker already leased it.
-    pub async fn claim_by_dedupe_key(&self, dedupe_key: &str) -> Result<bool> {
-        let result = sqlx::query(
+    /// Moves an event to `push_event_dead_letter` with its final error.
+    pub async fn dead_letter(&self, id: i64, error: &str) -> Result<()> {
+        let sql = format!(
             r#"
-            DELETE FROM push_event_queue
-            WHERE dedupe_key = $1
-              AND (leased_until IS NULL OR leased_until < NOW())
+            WITH doomed AS (
+                DELETE FROM push_event_queue
+                WHERE id = $1
+                RETURNING *, $2::text AS final_error
+            )
+            {DEAD_LETTER_INSERT}
+            "#
+        );
+        let moved = sqlx::query_scalar::<_, String>(&sql)
+            .bind(id)
+            .bind(error)
+            .fetch_all(&self.db_pool)
+            .await?;
+
+        for notification_type in &moved {
+            tracing::warn!(
+                queue_event_id = id,
+                notification_type = %notification_type,
+                error,
+                "Push event exhausted its attempts; moved to dead letter"
+            );
+            metrics::record_push_dead_letter(notification_type);
+        }
+        Ok(())
+    }
+
+    /// Dead-letters exhausted events whose last lease ran out without a
+    /// result, e.g. because the worker died or hung on them. Returns the
+    /// number moved.
+    pub async fn dead_letter_abandoned(&self) -> Result<u64> {
+        let sql = format!(
+            r#"
+            WITH doomed AS (
+                DELETE FROM push_event_queue
+                WHERE attempts >= $1
+                  AND (leased_until IS NULL OR leased_until < NOW())
+                RETURNING *, COALESCE(last_error, 'lease expired without a result') AS final_error
+            )
+            {DEAD_LETTER_INSERT}
+            "#
+        );
+        let moved = sqlx::query_scalar::<_, String>(&sql)
+            .bind(self.max_attempts)
+            .fetch_all(&self.db_pool)
+            .await?;
+
+        for notification_type in &moved {
+            metrics::record_push_dead_letter(notification_type);
+        }
+        Ok(moved.len() as u64)
+    }
+
+    /// Holds an event back until quiet hours end. The claim that found it in
+    /// quiet hours doesn't count as a delivery attempt.
+    pub async fn defer_for_quiet_hours(&self, id: i64, delay_seconds: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE push_event_queue
+            SET leased_until = NULL,
+                available_at = NOW() + make_interval(secs => $2),
+                attempts = GREATEST(attempts - 1, 0),
+                quiet_deferred = TRUE,
+                updated_at = NOW()
+            WHERE id = $1
             "#,
         )
-        .bind(dedupe_key)
+        .bind(id)
+        .bind(delay_seconds.max(0))
         .execute(&self.db_pool)
         .await?;
-
-        Ok(result.rows_affected() > 0)
+        Ok(())
     }
 
     pub async fn push_snapshot(&self, id: i64) -> Result<Option<Value>> {
@@ -0,0 +1,220 @@
+//! Per-account quiet hours (`blue.catbird.push.getQuietHours` /
+//! `putQuietHours`).
+//!
+//! The schedule is a daily local-time window in an IANA timezone, stored in
+//! the account's push preferences document. While it is active, events are
+//! either deferred until the window ends (and then delivered as one digest)
+//! or sent without sound. Chat and mentions can be let through.
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use super::notification_kind::NotificationKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietHoursMode {
+    /// Hold events until the window ends, then send one digest.
+    Defer,
+    /// Deliver immediately without sound.
+    Silent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHours {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Local start time, `HH:MM`.
+    #[serde(default = "default_start")]
+    pub start: String,
+    /// Local end time, `HH:MM`; earlier than `start` for overnight windows.
+    #[serde(default = "default_end")]
+    pub end: String,
+    /// IANA timezone name, e.g. `America/New_York`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    #[serde(default = "default_mode")]
+    pub mode: QuietHoursMode,
+    #[serde(default = "default_true")]
+    pub allow_chat: bool,
+    #[serde(default)]
+    pub allow_mentions: bool,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: default_start(),
+            end: default_end(),
+            timezone: default_timezone(),
+            mode: default_mode(),
+            allow_chat: true,
+            allow_mentions: false,
+        }
+    }
+}
+
+impl QuietHours {
+    /// Rejects schedules that could never be evaluated.
+    pub fn validate(&self) -> Result<(), String> {
+        parse_time(&self.start).ok_or_else(|| format!("invalid start time: {}", self.start))?;
+        parse_time(&self.end).ok_or_else(|| format!("invalid end time: {}", self.end))?;
+        self.timezone
+            .parse::<Tz>()
+            .map_err(|_| format!("unknown timezone: {}", self.timezone))?;
+        Ok(())
+    }
+
+    /// Whether `kind` is let through during quiet hours.
+    pub fn bypasses(&self, kind: NotificationKind) -> bool {
+        match kind {
+            NotificationKind::ChatMessage => self.allow_chat,
+            NotificationKind::Mention => self.allow_mentions,
+            _ => false,
+        }
+    }
+
+    /// When quiet hours are in effect at `now`, the instant they end.
+    pub fn active_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !self.enabled {
+            return None;
+        }
+        let tz = self.timezone.parse::<Tz>().ok()?;
+        let start = parse_time(&self.start)?;
+        let end = parse_time(&self.end)?;
+        if start == end {
+            return None;
+        }
+
+        let local = now.with_timezone(&tz);
+        let time = local.time();
+        let active = if start < end {
+            time >= start && time < end
+        } else {
+            time >= start || time < end
+        };
+        if !active {
+            return None;
+        }
+
+        // Past `end` today means we're in the evening half of an overnight
+        // window, which ends tomorrow.
+        let mut end_date = local.date_naive();
+        if time >= end {
+            end_date = end_date.succ_opt()?;
+        }
+        let naive_end = end_date.and_time(end);
+        // An end time inside a DST gap doesn't exist locally; use the first
+        // instant after the jump.
+        let end_local = tz.from_local_datetime(&naive_end).earliest().or_else(|| {
+            tz.from_local_datetime(&(naive_end + Duration::hours(1)))
+                .earliest()
+        })?;
+        Some(end_local.with_timezone(&Utc))
+    }
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M").ok()
+}
+
+fn default_start() -> String {
+    "22:00".to_string()
+}
+
+fn default_end() -> String {
+    "07:00".to_string()
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_mode() -> QuietHoursMode {
+    QuietHoursMode::Defer
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start: &str, end: &str, timezone: &str) -> QuietHours {
+        QuietHours {
+            enabled: true,
+            start: start.to_string(),
+            end: end.to_string(),
+            timezone: timezone.to_string(),
+            ..QuietHours::default()
+        }
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn overnight_window_ends_the_next_morning() {
+        let quiet = schedule("22:00", "07:00", "America/New_York");
+
+        // 23:30 EDT on June 1st.
+        assert_eq!(
+            quiet.active_until(utc("2026-06-02T03:30:00Z")),
+            Some(utc("2026-06-02T11:00:00Z"))
+        );
+        // 05:00 EDT on June 2nd, same window.
+        assert_eq!(
+            quiet.active_until(utc("2026-06-02T09:00:00Z")),
+            Some(utc("2026-06-02T11:00:00Z"))
+        );
+        // 12:00 EDT is outside it.
+        assert_eq!(quiet.active_until(utc("2026-06-02T16:00:00Z")), None);
+    }
+
+    #[test]
+    fn daytime_window_ends_the_same_day() {
+        let quiet = schedule("09:00", "17:00", "Europe/Berlin");
+        assert_eq!(
+            quiet.active_until(utc("2026-01-15T10:00:00Z")),
+            Some(utc("2026-01-15T16:00:00Z"))
+        );
+        assert_eq!(quiet.active_until(utc("2026-01-15T17:00:00Z")), None);
+    }
+
+    #[test]
+    fn disabled_or_empty_windows_are_never_active() {
+        let mut quiet = schedule("22:00", "07:00", "UTC");
+        quiet.enabled = false;
+        assert_eq!(quiet.active_until(utc("2026-06-01T23:00:00Z")), None);
+        assert_eq!(
+            schedule("08:00", "08:00", "UTC").active_until(utc("2026-06-01T08:00:00Z")),
+            None
+        );
+    }
+
+    #[test]
+    fn validation_rejects_bad_times_and_zones() {
+        assert!(schedule("22:00", "07:00", "Asia/Tokyo").validate().is_ok());
+        assert!(schedule("25:00", "07:00", "UTC").validate().is_err());
+        assert!(schedule("22:00", "7am", "UTC").validate().is_err());
+        assert!(schedule("22:00", "07:00", "Mars/Olympus")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn chat_bypasses_by_default_and_mentions_do_not() {
+        let quiet = QuietHours::default();
+        assert!(quiet.bypasses(NotificationKind::ChatMessage));
+        assert!(!quiet.bypasses(NotificationKind::Mention));
+        assert!(!quiet.bypasses(NotificationKind::Like));
+    }
+}
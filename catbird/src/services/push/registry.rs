@@ -6,7 +6,10 @@ use crate::{
     models::CatbirdSession,
 };
 
-use super::types::{PushAccountRow, RegisterPushInput, RegistrationRow, UnregisterPushInput};
+use super::types::{
+    PushAccountRow, PutDevicePreferencesInput, RegisterPushInput, RegistrationRow,
+    UnregisterPushInput,
+};
 
 #[derive(Clone)]
 pub struct PushRegistry {
@@ -82,18 +85,22 @@ impl PushRegistry {
                 app_id,
                 service_did,
                 age_restricted,
+                locale,
+                preview_key,
                 is_active,
                 last_registered_at,
                 last_error,
                 updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, TRUE, NOW(), NULL, NOW())
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, TRUE, NOW(), NULL, NOW())
             ON CONFLICT (device_token, did)
             DO UPDATE
             SET platform = EXCLUDED.platform,
                 app_id = EXCLUDED.app_id,
                 service_did = EXCLUDED.service_did,
                 age_restricted = EXCLUDED.age_restricted,
+                locale = EXCLUDED.locale,
+                preview_key = EXCLUDED.preview_key,
                 is_active = TRUE,
                 last_registered_at = NOW(),
                 last_invalidated_at = NULL,
@@ -107,6 +114,8 @@ impl PushRegistry {
         .bind(&input.app_id)
         .bind(&input.service_did)
         .bind(input.age_restricted.unwrap_or(false))
+        .bind(&input.locale)
+        .bind(&input.preview_key)
         .execute(&self.db_pool)
         .await?;
 
@@ -142,6 +151,38 @@ impl PushRegistry {
         Ok(())
     }
 
+    /// Stores (or with `None`, clears) a device's preference overrides.
+    /// Returns false when the session has no active registration for it.
+    pub async fn set_preference_overrides(
+        &self,
+        session: &CatbirdSession,
+        input: &PutDevicePreferencesInput,
+    ) -> Result<bool> {
+        self.touch_account_session(session).await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE user_devices
+            SET preference_overrides = $5,
+                updated_at = NOW()
+            WHERE did = $1
+              AND device_token = $2
+              AND platform = $3
+              AND app_id = $4
+              AND is_active = TRUE
+            "#,
+        )
+        .bind(&session.did)
+        .bind(&input.token)
+        .bind(&input.platform)
+        .bind(&input.app_id)
+        .bind(&input.overrides)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn deactivate_invalid_token(
         &self,
         did: &str,
@@ -180,7 +221,10 @@ impl PushRegistry {
                 service_did,
                 age_restricted,
                 is_active,
-                apns_environment
+                apns_environment,
+                locale,
+                preference_overrides,
+                preview_key
             FROM user_devices
             WHERE did = $1
               AND is_active = TRUE
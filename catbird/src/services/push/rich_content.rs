@@ -0,0 +1,321 @@
+//! Media and reply context for the iOS Notification Service Extension.
+//!
+//! Post notifications carry the post's embed thumbnails (images, external
+//! link cards, video posters) as CDN URLs, the text of the post being
+//! replied to, and the actor's avatar, so the extension can attach them to
+//! the banner. APNs rejects payloads over `MAX_APNS_PAYLOAD_BYTES`; when a
+//! notification doesn't fit, `trim` drops the least useful field first and
+//! the caller re-measures.
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::Value;
+
+/// APNs limit for a regular (non-VoIP) notification payload.
+pub const MAX_APNS_PAYLOAD_BYTES: usize = 4096;
+
+/// Thumbnails beyond this are never sent; the extension shows one anyway.
+const MAX_MEDIA: usize = 4;
+const MAX_ALT_TEXT_LEN: usize = 120;
+
+const IMAGE_CDN_URL: &str = "https://cdn.bsky.app/img/feed_thumbnail/plain";
+const VIDEO_CDN_URL: &str = "https://video.bsky.app/watch";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    External,
+    Video,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaAttachment {
+    pub kind: MediaKind,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+}
+
+/// Sent to APNs as the `rich` custom data key and to the other transports
+/// alongside their custom data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RichContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<MediaAttachment>,
+    /// Text of the post a reply answers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_text: Option<String>,
+}
+
+impl RichContent {
+    pub fn is_empty(&self) -> bool {
+        self.avatar_url.is_none() && self.media.is_empty() && self.parent_text.is_none()
+    }
+
+    /// Drops one field, least useful first: reply context, alt text, extra
+    /// thumbnails, the last thumbnail, then the avatar. Returns `false`
+    /// once there is nothing left to drop.
+    pub fn trim(&mut self) -> bool {
+        if self.parent_text.take().is_some() {
+            return true;
+        }
+        if let Some(media) = self.media.iter_mut().find(|media| media.alt.is_some()) {
+            media.alt = None;
+            return true;
+        }
+        if self.media.pop().is_some() {
+            return true;
+        }
+        self.avatar_url.take().is_some()
+    }
+}
+
+/// Thumbnails for a post record's embed, by CDN URL. `author_did` is the
+/// repo the record lives in, which owns its blobs.
+pub fn media_from_record(author_did: &str, record: &Value) -> Vec<MediaAttachment> {
+    let mut media = Vec::new();
+    if let Some(embed) = record.get("embed") {
+        collect_embed_media(author_did, embed, &mut media);
+    }
+    media.truncate(MAX_MEDIA);
+    media
+}
+
+fn collect_embed_media(author_did: &str, embed: &Value, media: &mut Vec<MediaAttachment>) {
+    match embed.get("$type").and_then(Value::as_str) {
+        Some("app.bsky.embed.images") => {
+            for image in embed
+                .get("images")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(cid) = image.get("image").and_then(blob_cid) {
+                    media.push(MediaAttachment {
+                        kind: MediaKind::Image,
+                        url: format!("{IMAGE_CDN_URL}/{author_did}/{cid}@jpeg"),
+                        alt: alt_text(image.get("alt")),
+                    });
+                }
+            }
+        }
+        Some("app.bsky.embed.external") => {
+            let external = embed.get("external");
+            if let Some(cid) = external.and_then(|e| e.get("thumb")).and_then(blob_cid) {
+                media.push(MediaAttachment {
+                    kind: MediaKind::External,
+                    url: format!("{IMAGE_CDN_URL}/{author_did}/{cid}@jpeg"),
+                    alt: alt_text(external.and_then(|e| e.get("title"))),
+                });
+            }
+        }
+        Some("app.bsky.embed.video") => {
+            if let Some(cid) = embed.get("video").and_then(blob_cid) {
+                media.push(MediaAttachment {
+                    kind: MediaKind::Video,
+                    url: format!("{VIDEO_CDN_URL}/{author_did}/{cid}/thumbnail.jpg"),
+                    alt: alt_text(embed.get("alt")),
+                });
+            }
+        }
+        Some("app.bsky.embed.recordWithMedia") => {
+            if let Some(inner) = embed.get("media") {
+                collect_embed_media(author_did, inner, media);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn alt_text(value: Option<&Value>) -> Option<String> {
+    value
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|alt| !alt.is_empty())
+        .map(|alt| alt.chars().take(MAX_ALT_TEXT_LEN).collect())
+}
+
+/// CID of a blob ref, in either the typed (`ref.$link`) or legacy (`cid`) form.
+fn blob_cid(blob: &Value) -> Option<&str> {
+    blob.get("ref")
+        .and_then(|r| r.get("$link"))
+        .or_else(|| blob.get("cid"))
+        .and_then(Value::as_str)
+        .filter(|cid| cid.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// AT-URI of the post a reply record answers.
+pub fn reply_parent_uri(record: &Value) -> Option<&str> {
+    record
+        .get("reply")
+        .and_then(|reply| reply.get("parent"))
+        .and_then(|parent| parent.get("uri"))
+        .and_then(Value::as_str)
+}
+
+/// Looks up post text for reply context with `app.bsky.feed.getPosts`.
+/// Replies are a small share of push traffic, so nothing is cached.
+#[derive(Clone)]
+pub struct ParentPosts {
+    appview_url: String,
+}
+
+impl ParentPosts {
+    pub fn new(appview_url: &str) -> Self {
+        Self {
+            appview_url: appview_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Text of the post at `uri`; `None` when the AppView doesn't return it
+    /// or it has no text.
+    pub async fn text(&self, http_client: &reqwest::Client, uri: &str) -> Result<Option<String>> {
+        let mut url = Url::parse(&format!("{}/xrpc/app.bsky.feed.getPosts", self.appview_url))?;
+        url.query_pairs_mut().append_pair("uris", uri);
+
+        let response = http_client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("getPosts returned HTTP {}", response.status()));
+        }
+
+        let payload: Value = response.json().await?;
+        Ok(post_text_from_json(&payload, uri))
+    }
+}
+
+fn post_text_from_json(payload: &Value, uri: &str) -> Option<String> {
+    payload
+        .get("posts")
+        .and_then(Value::as_array)?
+        .iter()
+        .find(|post| post.get("uri").and_then(Value::as_str) == Some(uri))
+        .and_then(|post| post.get("record"))
+        .and_then(|record| record.get("text"))
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn blob(cid: &str) -> Value {
+        json!({ "$type": "blob", "ref": { "$link": cid }, "mimeType": "image/jpeg", "size": 1 })
+    }
+
+    #[test]
+    fn extracts_image_external_and_video_thumbnails() {
+        let images = json!({ "embed": {
+            "$type": "app.bsky.embed.images",
+            "images": [
+                { "image": blob("bafyimage1"), "alt": "  a cat  " },
+                { "image": blob("bafyimage2"), "alt": "" },
+            ],
+        }});
+        assert_eq!(
+            media_from_record("did:plc:alice", &images),
+            vec![
+                MediaAttachment {
+                    kind: MediaKind::Image,
+                    url: "https://cdn.bsky.app/img/feed_thumbnail/plain/did:plc:alice/bafyimage1@jpeg"
+                        .to_string(),
+                    alt: Some("a cat".to_string()),
+                },
+                MediaAttachment {
+                    kind: MediaKind::Image,
+                    url: "https://cdn.bsky.app/img/feed_thumbnail/plain/did:plc:alice/bafyimage2@jpeg"
+                        .to_string(),
+                    alt: None,
+                },
+            ]
+        );
+
+        let external = json!({ "embed": {
+            "$type": "app.bsky.embed.external",
+            "external": { "uri": "https://example.com", "title": "Example", "thumb": blob("bafythumb") },
+        }});
+        let media = media_from_record("did:plc:alice", &external);
+        assert_eq!(media[0].kind, MediaKind::External);
+        assert_eq!(media[0].alt.as_deref(), Some("Example"));
+
+        let video = json!({ "embed": {
+            "$type": "app.bsky.embed.recordWithMedia",
+            "record": { "record": { "uri": "at://did:plc:bob/app.bsky.feed.post/1" } },
+            "media": { "$type": "app.bsky.embed.video", "video": blob("bafyvideo") },
+        }});
+        assert_eq!(
+            media_from_record("did:plc:alice", &video)[0].url,
+            "https://video.bsky.app/watch/did:plc:alice/bafyvideo/thumbnail.jpg"
+        );
+
+        assert!(media_from_record("did:plc:alice", &json!({ "text": "plain" })).is_empty());
+        let bad_cid = json!({ "embed": {
+            "$type": "app.bsky.embed.images",
+            "images": [{ "image": blob("../../etc") }],
+        }});
+        assert!(media_from_record("did:plc:alice", &bad_cid).is_empty());
+    }
+
+    #[test]
+    fn trims_least_useful_fields_first() {
+        let mut rich = RichContent {
+            avatar_url: Some("https://cdn.bsky.app/avatar".to_string()),
+            media: vec![
+                MediaAttachment {
+                    kind: MediaKind::Image,
+                    url: "https://cdn.bsky.app/1".to_string(),
+                    alt: Some("one".to_string()),
+                },
+                MediaAttachment {
+                    kind: MediaKind::Image,
+                    url: "https://cdn.bsky.app/2".to_string(),
+                    alt: None,
+                },
+            ],
+            parent_text: Some("parent".to_string()),
+        };
+
+        assert!(rich.trim());
+        assert_eq!(rich.parent_text, None);
+        assert!(rich.trim());
+        assert_eq!(rich.media[0].alt, None);
+        assert!(rich.trim());
+        assert_eq!(rich.media.len(), 1);
+        assert!(rich.trim());
+        assert!(rich.media.is_empty());
+        assert!(rich.avatar_url.is_some());
+        assert!(rich.trim());
+        assert!(rich.is_empty());
+        assert!(!rich.trim());
+    }
+
+    #[test]
+    fn reads_parent_uri_and_post_text() {
+        let record = json!({
+            "text": "agreed",
+            "reply": {
+                "root": { "uri": "at://did:plc:bob/app.bsky.feed.post/root", "cid": "bafyroot" },
+                "parent": { "uri": "at://did:plc:bob/app.bsky.feed.post/parent", "cid": "bafyparent" },
+            },
+        });
+        let uri = reply_parent_uri(&record).unwrap();
+        assert_eq!(uri, "at://did:plc:bob/app.bsky.feed.post/parent");
+
+        let payload = json!({ "posts": [
+            { "uri": uri, "record": { "text": " cats are great " } },
+        ]});
+        assert_eq!(
+            post_text_from_json(&payload, uri).as_deref(),
+            Some("cats are great")
+        );
+        assert_eq!(post_text_from_json(&json!({ "posts": [] }), uri), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! `blue.catbird.push.sendTest`: a synthetic event run through the real
+//! decision engine, then a notification sent to every active registration
+//! so each transport is exercised whatever the engine decided. Devices the
+//! engine would have delivered to get the notification it built; the rest
+//! get a plain test alert. The report says, per device, what a real event
+//! of the chosen type would have done and what the push service answered.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{config::AppState, middleware::RateLimitConfig};
+
+use super::{
+    decision::QueueDisposition,
+    deliveries::{failure_reason, LoggedEvent},
+    localization::{LocalizedText, DEFAULT_LOCALE},
+    notification_kind::NotificationKind,
+    transport::{DeliveryError, PushNotification},
+    types::{PushPreferencesDocument, QueueRow, RegistrationRow},
+    with_step_timeout, PushServices, DECISION_TIMEOUT,
+};
+
+const TEST_EVENT_PATH: &str = "app.bsky.feed.post/sendtest";
+const TEST_POST_TEXT: &str = "This is a test notification.";
+
+/// Each call pushes to every device on the account and hits every push
+/// service, so an account gets a few per window.
+pub const SEND_TEST_LIMIT: RateLimitConfig = RateLimitConfig {
+    max_requests: 5,
+    window: Duration::from_secs(600),
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestReport {
+    #[serde(rename = "type")]
+    pub notification_type: &'static str,
+    pub actor: String,
+    /// What the engine decided for a real event: `deliver`, `coalesce`
+    /// (held for a grouped summary), `defer` (quiet hours), `drop`, or
+    /// `error`.
+    pub decision: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub devices: Vec<DeviceReport>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceReport {
+    pub id: String,
+    pub platform: String,
+    pub app_id: String,
+    /// The rule that would keep a real event from this device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drop_reason: Option<&'static str>,
+    /// `sent`, `failed`, `invalid_token`, or `no_transport`.
+    pub outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_id: Option<String>,
+    /// APNs environment that answered, or the one tried first when the
+    /// request never got an answer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<&'static str>,
+    /// The accepting environment differed from the one on file and was
+    /// saved to the registration.
+    pub environment_learned: bool,
+    pub deactivated: bool,
+}
+
+pub async fn send_test(
+    state: &Arc<AppState>,
+    services: &PushServices,
+    account_did: &str,
+    kind: NotificationKind,
+    actor_did: &str,
+) -> Result<TestReport> {
+    let registrations = services
+        .registry
+        .list_active_registrations(account_did)
+        .await?;
+    let prefs = services.preferences.get_or_create(account_did).await?;
+
+    let row = test_event(account_did, kind, actor_did);
+    let evaluated = with_step_timeout(
+        "decision",
+        DECISION_TIMEOUT,
+        services.decision.evaluate(state, services, &row),
+    )
+    .await
+    .and_then(|result| result);
+
+    let mut error = None;
+    let mut drop_reason = None;
+    let mut delivered: HashMap<sqlx::types::Uuid, PushNotification> = HashMap::new();
+    let decision = match evaluated {
+        Ok(QueueDisposition::Deliver(deliveries)) => {
+            delivered.extend(
+                deliveries
+                    .into_iter()
+                    .map(|(registration, notification)| (registration.id, notification)),
+            );
+            "deliver"
+        }
+        Ok(QueueDisposition::Coalesce(_)) => "coalesce",
+        Ok(QueueDisposition::Defer(_)) => "defer",
+        Ok(QueueDisposition::Drop(reason)) => {
+            drop_reason = Some(reason);
+            "drop"
+        }
+        Err(err) => {
+            error = Some(err.to_string());
+            "error"
+        }
+    };
+
+    let logged = LoggedEvent {
+        recipient_did: account_did,
+        notification_type: "test",
+        queue_event_id: None,
+    };
+    let mut devices = Vec::with_capacity(registrations.len());
+    for registration in registrations {
+        let device_drop_reason = match decision {
+            "deliver" if !delivered.contains_key(&registration.id) => Some(device_drop_reason(
+                &prefs.with_overrides(registration.preference_overrides.as_ref()),
+                kind,
+            )),
+            _ => drop_reason,
+        };
+        let mut notification = delivered
+            .remove(&registration.id)
+            .unwrap_or_else(|| test_notification(account_did));
+        notification
+            .custom_data
+            .insert("test".to_string(), "true".to_string());
+
+        devices.push(
+            send_to_device(
+                state,
+                services,
+                logged,
+                registration,
+                &notification,
+                device_drop_reason,
+            )
+            .await,
+        );
+    }
+
+    Ok(TestReport {
+        notification_type: kind.as_str(),
+        actor: actor_did.to_string(),
+        decision,
+        error,
+        devices,
+    })
+}
+
+async fn send_to_device(
+    state: &Arc<AppState>,
+    services: &PushServices,
+    logged: LoggedEvent<'_>,
+    registration: RegistrationRow,
+    notification: &PushNotification,
+    drop_reason: Option<&'static str>,
+) -> DeviceReport {
+    let apns = services
+        .transports
+        .apns_for_platform(&registration.platform);
+    let mut report = DeviceReport {
+        id: registration.id.to_string(),
+        platform: registration.platform.clone(),
+        app_id: registration.app_id.clone(),
+        drop_reason,
+        outcome: "no_transport",
+        status: None,
+        reason: None,
+        provider_id: None,
+        environment: apns.map(|apns| apns.first_env(&registration)),
+        environment_learned: false,
+        deactivated: false,
+    };
+    let Some(transport) = services.transports.for_platform(&registration.platform) else {
+        return report;
+    };
+
+    match services
+        .send_logged(transport, logged, &registration, notification)
+        .await
+    {
+        Ok(receipt) => {
+            report.outcome = "sent";
+            report.status = Some(receipt.status);
+            report.provider_id = receipt.provider_id;
+            report.environment_learned = receipt.environment.is_some()
+                && receipt.environment != registration.apns_environment.as_deref();
+            report.environment = receipt.environment.or(report.environment);
+        }
+        Err(err) => {
+            if let Some(response) = err.response() {
+                report.status = Some(response.status);
+                report.provider_id = response.provider_id;
+                report.environment = response.environment.or(report.environment);
+            }
+            report.reason = Some(failure_reason(&err).to_string());
+            match err {
+                DeliveryError::InvalidToken { reason, .. } => {
+                    services
+                        .deactivate_dead_token(state, &registration, reason)
+                        .await;
+                    report.outcome = "invalid_token";
+                    report.deactivated = true;
+                }
+                DeliveryError::Other(_) => report.outcome = "failed",
+            }
+        }
+    }
+    report
+}
+
+/// A queue row for an event that never happened, shaped like the ones the
+/// firehose and chat poller write.
+fn test_event(account_did: &str, kind: NotificationKind, actor_did: &str) -> QueueRow {
+    let event_record_json = if kind == NotificationKind::ChatMessage {
+        json!({ "convoId": "sendtest", "messageId": "sendtest" })
+    } else {
+        json!({ "$type": "app.bsky.feed.post", "text": TEST_POST_TEXT })
+    };
+    QueueRow {
+        id: 0,
+        recipient_did: account_did.to_string(),
+        actor_did: actor_did.to_string(),
+        notification_type: kind,
+        event_cid: "sendtest".to_string(),
+        event_path: TEST_EVENT_PATH.to_string(),
+        subject_uri: None,
+        thread_root_uri: None,
+        event_record_json,
+        event_timestamp: chrono::Utc::now().timestamp(),
+        created_at: sqlx::types::time::OffsetDateTime::now_utc(),
+        attempts: 0,
+        quiet_deferred: false,
+        badge_counted: false,
+    }
+}
+
+fn test_notification(account_did: &str) -> PushNotification {
+    let text = LocalizedText::new("PUSH_TEST_TITLE", "PUSH_TEST_BODY", Vec::new());
+    PushNotification {
+        title: text.title(DEFAULT_LOCALE),
+        body: text.body(DEFAULT_LOCALE),
+        localization: Some(text),
+        user_did: account_did.to_string(),
+        custom_data: HashMap::new(),
+        mutable_content: false,
+        rich: None,
+        thread_id: None,
+        collapse_id: None,
+        silent: false,
+        content_available: false,
+        badge: None,
+    }
+}
+
+/// Why the engine left a device out of an otherwise delivered event, in
+/// the order it filters devices.
+fn device_drop_reason(
+    device_prefs: &PushPreferencesDocument,
+    kind: NotificationKind,
+) -> &'static str {
+    if !device_prefs.is_push_enabled_for(kind) {
+        "preferences_disabled"
+    } else if device_prefs.is_follows_only_for(kind) {
+        "actor_not_followed"
+    } else {
+        "labeled_content"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explains_devices_left_out_of_a_delivery() {
+        let kind = NotificationKind::Like;
+        let mut account = PushPreferencesDocument::default();
+        assert_eq!(device_drop_reason(&account, kind), "labeled_content");
+
+        account.like.include = "follows".to_string();
+        assert_eq!(device_drop_reason(&account, kind), "actor_not_followed");
+
+        let muted = account.with_overrides(Some(&json!({ "like": { "push": false } })));
+        assert_eq!(device_drop_reason(&muted, kind), "preferences_disabled");
+    }
+
+    #[test]
+    fn test_events_look_like_queued_ones() {
+        let row = test_event("did:plc:bob", NotificationKind::Mention, "did:plc:alice");
+        assert_eq!(row.recipient_did, "did:plc:bob");
+        assert_eq!(row.actor_did, "did:plc:alice");
+        assert_eq!(row.event_record_json["text"], TEST_POST_TEXT);
+
+        let chat = test_event(
+            "did:plc:bob",
+            NotificationKind::ChatMessage,
+            "did:plc:alice",
+        );
+        assert_eq!(chat.event_record_json["convoId"], "sendtest");
+    }
+}
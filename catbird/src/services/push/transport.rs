@@ -0,0 +1,237 @@
+//! Platform-neutral push delivery.
+//!
+//! The decision engine produces one `PushNotification` per event; each
+//! registration is then handed to the `PushTransport` for its
+//! `user_devices.platform`. Transports own everything platform-specific:
+//! payload shape, credentials, and recognising a dead token (APNs
+//! `Unregistered`, Web Push 404/410, FCM `UNREGISTERED`), which they report
+//! as `DeliveryError::InvalidToken` so the worker can deactivate the
+//! registration without knowing which service said so.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use futures_util::future::BoxFuture;
+
+use super::{
+    apns::{self, ApnsDelivery},
+    fcm::{self, FcmDelivery, PLATFORM_ANDROID},
+    localization::{self, LocalizedText, DEFAULT_LOCALE},
+    rich_content::RichContent,
+    types::RegistrationRow,
+    web_push::{self, WebPushDelivery, PLATFORM_WEB},
+};
+
+#[derive(Debug, Clone)]
+pub struct PushNotification {
+    /// Rendered in `DEFAULT_LOCALE`; see `for_locale`.
+    pub title: String,
+    pub body: String,
+    /// Catalog keys `title` and `body` were rendered from. APNs sends them
+    /// as loc-keys; other transports get the text re-rendered per device.
+    pub localization: Option<LocalizedText>,
+    pub user_did: String,
+    /// String key/value pairs delivered to the client app alongside the alert.
+    pub custom_data: HashMap<String, String>,
+    /// The client rewrites the notification before display (e.g. to decrypt
+    /// a chat preview): APNs `mutable-content`, a data-only FCM message.
+    pub mutable_content: bool,
+    /// Avatar, embed thumbnails, and reply context for the client to
+    /// attach; set together with `mutable_content`.
+    pub rich: Option<RichContent>,
+    /// Groups related notifications on the device.
+    pub thread_id: Option<String>,
+    /// A later notification with the same id replaces this one instead of
+    /// being shown alongside it (APNs `apns-collapse-id`, FCM `collapse_key`,
+    /// Web Push `Topic`). At most 32 URL-safe base64 characters, the
+    /// strictest of the three limits.
+    pub collapse_id: Option<String>,
+    /// Deliver without sound (quiet hours in `silent` mode).
+    pub silent: bool,
+    /// Wake the app to act on `custom_data` without showing anything
+    /// (APNs background push, a data-only FCM message); see
+    /// `chat_retraction`.
+    pub content_available: bool,
+    /// App icon badge (APNs only); see `badges`.
+    pub badge: Option<u32>,
+}
+
+impl PushNotification {
+    /// No alert, only a new badge: sent when the unread count drops so the
+    /// icon doesn't keep showing a stale number.
+    pub fn badge_only(user_did: &str, badge: u32) -> Self {
+        Self {
+            title: String::new(),
+            body: String::new(),
+            localization: None,
+            user_did: user_did.to_string(),
+            custom_data: HashMap::new(),
+            mutable_content: false,
+            rich: None,
+            thread_id: None,
+            collapse_id: None,
+            silent: true,
+            content_available: false,
+            badge: Some(badge),
+        }
+    }
+
+    /// No alert: tells the app to remove notifications already delivered
+    /// for a chat conversation (thread `chat:{convo_id}`), or only the one
+    /// for `message_id`. Carries the current badge, which the removal
+    /// usually lowers.
+    pub fn chat_retraction(
+        user_did: &str,
+        convo_id: &str,
+        message_id: Option<&str>,
+        badge: Option<u32>,
+    ) -> Self {
+        let mut custom_data = HashMap::new();
+        custom_data.insert("type".to_string(), "chat_retract".to_string());
+        custom_data.insert("convoId".to_string(), convo_id.to_string());
+        if let Some(message_id) = message_id {
+            custom_data.insert("messageId".to_string(), message_id.to_string());
+        }
+        Self {
+            title: String::new(),
+            body: String::new(),
+            localization: None,
+            user_did: user_did.to_string(),
+            custom_data,
+            mutable_content: false,
+            rich: None,
+            thread_id: Some(format!("chat:{convo_id}")),
+            collapse_id: None,
+            silent: true,
+            content_available: true,
+            badge,
+        }
+    }
+
+    pub fn has_alert(&self) -> bool {
+        !self.title.is_empty() || !self.body.is_empty() || self.localization.is_some()
+    }
+
+    pub fn is_badge_only(&self) -> bool {
+        !self.has_alert() && !self.content_available
+    }
+
+    /// This notification with `title` and `body` rendered in a device's
+    /// locale, or unchanged when there is nothing to translate.
+    pub fn for_locale(&self, locale: Option<&str>) -> Cow<'_, PushNotification> {
+        let catalog = localization::catalog_locale(locale);
+        match &self.localization {
+            Some(text) if catalog != DEFAULT_LOCALE => Cow::Owned(PushNotification {
+                title: text.title(catalog),
+                body: text.body(catalog),
+                ..self.clone()
+            }),
+            _ => Cow::Borrowed(self),
+        }
+    }
+}
+
+/// What the push service reported for a notification: the receipt of an
+/// accepted one, or the response to a rejected one (`DeliveryError::response`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeliveryReceipt {
+    /// HTTP status of the push service's response.
+    pub status: u16,
+    /// The service's id for the message: APNs `apns-id`, the FCM message
+    /// name, or the Web Push message `Location`.
+    pub provider_id: Option<String>,
+    /// APNs environment ("production"/"sandbox") that answered.
+    pub environment: Option<&'static str>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    /// The push service will never accept this token again; `reason` is
+    /// recorded in `user_devices.last_error` when it is deactivated.
+    #[error("invalid push token ({reason}): {detail}")]
+    InvalidToken {
+        reason: &'static str,
+        detail: String,
+        response: Option<DeliveryReceipt>,
+    },
+    /// Anything else, including transient failures worth retrying.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl DeliveryError {
+    /// The push service's response, when the failure is one it reported
+    /// rather than one that kept the request from reaching it.
+    pub fn response(&self) -> Option<DeliveryReceipt> {
+        match self {
+            DeliveryError::InvalidToken { response, .. } => response.clone(),
+            DeliveryError::Other(err) => provider_response(err),
+        }
+    }
+}
+
+/// The push service's response carried by a transport error, if any.
+pub fn provider_response(err: &anyhow::Error) -> Option<DeliveryReceipt> {
+    apns::response(err)
+        .or_else(|| fcm::response(err))
+        .or_else(|| web_push::response(err))
+}
+
+pub trait PushTransport: Send + Sync {
+    fn send<'a>(
+        &'a self,
+        registration: &'a RegistrationRow,
+        notification: &'a PushNotification,
+    ) -> BoxFuture<'a, Result<DeliveryReceipt, DeliveryError>>;
+}
+
+/// The configured transports; any of them may be absent.
+#[derive(Clone, Default)]
+pub struct PushTransports {
+    pub apns: Option<ApnsDelivery>,
+    pub web_push: Option<WebPushDelivery>,
+    pub fcm: Option<FcmDelivery>,
+}
+
+impl PushTransports {
+    pub fn is_empty(&self) -> bool {
+        self.apns.is_none() && self.web_push.is_none() && self.fcm.is_none()
+    }
+
+    /// Transport for a registration's platform, if one is configured.
+    pub fn for_platform(&self, platform: &str) -> Option<&dyn PushTransport> {
+        match platform {
+            PLATFORM_WEB => self.web_push.as_ref().map(|t| t as &dyn PushTransport),
+            PLATFORM_ANDROID => self.fcm.as_ref().map(|t| t as &dyn PushTransport),
+            // iOS, macOS, and rows registered before `platform` was sent.
+            _ => self.apns.as_ref().map(|t| t as &dyn PushTransport),
+        }
+    }
+
+    /// Transport for a registration's platform that can carry
+    /// `notification`: an update with nothing but a badge only means
+    /// something to APNs, and browsers get nothing without an alert. With
+    /// `userVisibleOnly`, a push the service worker doesn't display makes
+    /// Chromium show its own "updated in the background" notification.
+    pub fn for_notification(
+        &self,
+        platform: &str,
+        notification: &PushNotification,
+    ) -> Option<&dyn PushTransport> {
+        if notification.is_badge_only() {
+            self.apns_for_platform(platform)
+                .map(|t| t as &dyn PushTransport)
+        } else if platform == PLATFORM_WEB && !notification.has_alert() {
+            None
+        } else {
+            self.for_platform(platform)
+        }
+    }
+
+    /// The APNs transport, for registrations that go through it.
+    pub fn apns_for_platform(&self, platform: &str) -> Option<&ApnsDelivery> {
+        match platform {
+            PLATFORM_WEB | PLATFORM_ANDROID => None,
+            _ => self.apns.as_ref(),
+        }
+    }
+}
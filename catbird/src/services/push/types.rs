@@ -2,6 +2,11 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::FromRow;
 
+use super::{
+    notification_kind::{NotificationKind, UnknownNotificationKind},
+    quiet_hours::QuietHours,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatPreference {
     #[serde(default = "default_include")]
@@ -28,6 +33,14 @@ pub struct Preference {
     pub push: bool,
 }
 
+/// Catbird extension: fold bursts of likes, reposts, and follows into one
+/// updating summary ("Alice and 23 others liked your post").
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupingPreference {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivitySubscriptionPreference {
     #[serde(default)]
@@ -64,6 +77,12 @@ pub struct PushPreferencesDocument {
     pub unverified: Preference,
     #[serde(default)]
     pub verified: Preference,
+    #[serde(default)]
+    pub grouping: GroupingPreference,
+    /// Managed through `blue.catbird.push.*QuietHours`, not the
+    /// `app.bsky.notification` preference lexicons.
+    #[serde(default)]
+    pub quiet_hours: QuietHours,
 }
 
 impl Default for PushPreferencesDocument {
@@ -82,6 +101,8 @@ impl Default for PushPreferencesDocument {
             subscribed_post: Preference::default(),
             unverified: Preference::default(),
             verified: Preference::default(),
+            grouping: GroupingPreference::default(),
+            quiet_hours: QuietHours::default(),
         }
     }
 }
@@ -105,6 +126,12 @@ impl Default for FilterablePreference {
     }
 }
 
+impl Default for GroupingPreference {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
 impl Default for Preference {
     fn default() -> Self {
         Self {
@@ -115,19 +142,62 @@ impl Default for Preference {
 }
 
 impl PushPreferencesDocument {
-    pub fn is_push_enabled_for(&self, notification_type: &str) -> bool {
-        match notification_type {
-            "mention" => self.mention.push,
-            "reply" => self.reply.push,
-            "like" => self.like.push,
-            "follow" => self.follow.push,
-            "repost" => self.repost.push,
-            "quote" => self.quote.push,
-            "via_like" => self.like_via_repost.push,
-            "via_repost" => self.repost_via_repost.push,
-            "activity_post" | "activity_reply" => self.subscribed_post.push,
-            "chat_message" => self.chat.push,
-            _ => true,
+    pub fn is_push_enabled_for(&self, kind: NotificationKind) -> bool {
+        match kind {
+            NotificationKind::Mention => self.mention.push,
+            NotificationKind::Reply => self.reply.push,
+            NotificationKind::Like => self.like.push,
+            NotificationKind::Follow => self.follow.push,
+            NotificationKind::Repost => self.repost.push,
+            NotificationKind::Quote => self.quote.push,
+            NotificationKind::LikeViaRepost => self.like_via_repost.push,
+            NotificationKind::RepostViaRepost => self.repost_via_repost.push,
+            NotificationKind::ActivityPost | NotificationKind::ActivityReply => {
+                self.subscribed_post.push
+            }
+            NotificationKind::StarterpackJoined => self.starterpack_joined.push,
+            NotificationKind::Verified => self.verified.push,
+            NotificationKind::Unverified => self.unverified.push,
+            NotificationKind::ChatMessage => self.chat.push,
+        }
+    }
+
+    /// True when the user only wants this notification type from accounts
+    /// they follow (`include: "follows"`). Types without an `include`
+    /// filter always return false.
+    pub fn is_follows_only_for(&self, kind: NotificationKind) -> bool {
+        let preference = match kind {
+            NotificationKind::Mention => &self.mention,
+            NotificationKind::Reply => &self.reply,
+            NotificationKind::Like => &self.like,
+            NotificationKind::Follow => &self.follow,
+            NotificationKind::Repost => &self.repost,
+            NotificationKind::Quote => &self.quote,
+            NotificationKind::LikeViaRepost => &self.like_via_repost,
+            NotificationKind::RepostViaRepost => &self.repost_via_repost,
+            NotificationKind::ActivityPost
+            | NotificationKind::ActivityReply
+            | NotificationKind::StarterpackJoined
+            | NotificationKind::Verified
+            | NotificationKind::Unverified
+            | NotificationKind::ChatMessage => return false,
+        };
+        preference.include == "follows"
+    }
+
+    /// The preferences one device receives: this document with the
+    /// device's `putDevicePreferences` overrides applied. Overrides that no
+    /// longer parse are ignored rather than silencing the device.
+    pub fn with_overrides(&self, overrides: Option<&Value>) -> Self {
+        let Some(overrides) = overrides else {
+            return self.clone();
+        };
+        match PutPreferencesInput::deserialize(overrides) {
+            Ok(overrides) => overrides.apply_to(self.clone()),
+            Err(err) => {
+                tracing::warn!(error = %err, "Ignoring unreadable device push preference overrides");
+                self.clone()
+            }
         }
     }
 
@@ -146,6 +216,7 @@ impl PushPreferencesDocument {
             "subscribedPost": self.subscribed_post,
             "unverified": self.unverified,
             "verified": self.verified,
+            "grouping": self.grouping,
         })
     }
 }
@@ -169,6 +240,7 @@ pub struct PutPreferencesInput {
     pub subscribed_post: Option<Preference>,
     pub unverified: Option<Preference>,
     pub verified: Option<Preference>,
+    pub grouping: Option<GroupingPreference>,
 }
 
 impl PutPreferencesInput {
@@ -212,10 +284,19 @@ impl PutPreferencesInput {
         if let Some(value) = self.verified {
             prefs.verified = value;
         }
+        if let Some(value) = self.grouping {
+            prefs.grouping = value;
+        }
         prefs
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct PutQuietHoursInput {
+    #[serde(rename = "quietHours")]
+    pub quiet_hours: QuietHours,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct RegisterPushInput {
     #[serde(rename = "serviceDid")]
@@ -226,6 +307,40 @@ pub struct RegisterPushInput {
     pub app_id: String,
     #[serde(rename = "ageRestricted")]
     pub age_restricted: Option<bool>,
+    /// BCP 47 language tag of the device ("pt-BR"), used to render
+    /// notification text for transports without client-side localization.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Base64url SEC1 P-256 public key that chat message previews are
+    /// encrypted to (see `chat_preview`). Without one, chat notifications
+    /// carry no preview.
+    #[serde(default, rename = "previewKey")]
+    pub preview_key: Option<String>,
+}
+
+/// `blue.catbird.push.putDevicePreferences`: overrides for the device
+/// registered with `token`, in the `putPreferencesV2` shape. Fields left out
+/// follow the account preferences; `overrides: null` clears them all.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PutDevicePreferencesInput {
+    #[serde(rename = "serviceDid")]
+    pub service_did: String,
+    pub token: String,
+    pub platform: String,
+    #[serde(rename = "appId")]
+    pub app_id: String,
+    #[serde(default)]
+    pub overrides: Option<Value>,
+}
+
+/// `blue.catbird.push.sendTest`: the kind of event to simulate (default
+/// `mention`) and who it appears to come from (default the caller).
+#[derive(Debug, Clone, Deserialize)]
+pub struct SendTestInput {
+    #[serde(default, rename = "type")]
+    pub notification_type: Option<String>,
+    #[serde(default)]
+    pub actor: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -258,10 +373,18 @@ pub struct RegistrationRow {
     /// Which APNs environment ("production"/"sandbox") this token is known to
     /// belong to. NULL until try-and-learn observes a successful delivery.
     pub apns_environment: Option<String>,
+    /// Device locale from `registerPush`; NULL renders in English.
+    pub locale: Option<String>,
+    /// Partial preferences from `putDevicePreferences`, applied with
+    /// `PushPreferencesDocument::with_overrides`.
+    pub preference_overrides: Option<Value>,
+    /// Chat preview public key from `registerPush`, in canonical form.
+    pub preview_key: Option<String>,
 }
 
+/// A `push_event_queue` row as stored, before its type is parsed.
 #[derive(Debug, Clone, FromRow)]
-pub struct QueueRow {
+pub struct QueueRecord {
     pub id: i64,
     pub recipient_did: String,
     pub actor_did: String,
@@ -274,6 +397,52 @@ pub struct QueueRow {
     pub event_timestamp: i64,
     pub created_at: sqlx::types::time::OffsetDateTime,
     pub attempts: i32,
+    pub quiet_deferred: bool,
+    pub badge_counted: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueRow {
+    pub id: i64,
+    pub recipient_did: String,
+    pub actor_did: String,
+    pub notification_type: NotificationKind,
+    pub event_cid: String,
+    pub event_path: String,
+    pub subject_uri: Option<String>,
+    pub thread_root_uri: Option<String>,
+    pub event_record_json: Value,
+    pub event_timestamp: i64,
+    pub created_at: sqlx::types::time::OffsetDateTime,
+    pub attempts: i32,
+    /// Held back by quiet hours; folded into the recipient's digest once
+    /// the window has ended.
+    pub quiet_deferred: bool,
+    /// Already counted toward the recipient's badge by an earlier attempt.
+    pub badge_counted: bool,
+}
+
+impl TryFrom<QueueRecord> for QueueRow {
+    type Error = UnknownNotificationKind;
+
+    fn try_from(record: QueueRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            notification_type: record.notification_type.parse()?,
+            id: record.id,
+            recipient_did: record.recipient_did,
+            actor_did: record.actor_did,
+            event_cid: record.event_cid,
+            event_path: record.event_path,
+            subject_uri: record.subject_uri,
+            thread_root_uri: record.thread_root_uri,
+            event_record_json: record.event_record_json,
+            event_timestamp: record.event_timestamp,
+            created_at: record.created_at,
+            attempts: record.attempts,
+            quiet_deferred: record.quiet_deferred,
+            badge_counted: record.badge_counted,
+        })
+    }
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -299,3 +468,123 @@ fn default_include() -> String {
 fn default_true() -> bool {
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_only_applies_to_filterable_types() {
+        let mut prefs = PushPreferencesDocument::default();
+        assert!(!prefs.is_follows_only_for(NotificationKind::Like));
+
+        prefs.like.include = "follows".to_string();
+        prefs.like_via_repost.include = "follows".to_string();
+        assert!(prefs.is_follows_only_for(NotificationKind::Like));
+        assert!(prefs.is_follows_only_for(NotificationKind::LikeViaRepost));
+        assert!(!prefs.is_follows_only_for(NotificationKind::Reply));
+        assert!(!prefs.is_follows_only_for(NotificationKind::ChatMessage));
+        assert!(!prefs.is_follows_only_for(NotificationKind::ActivityPost));
+    }
+
+    #[test]
+    fn every_kind_is_switched_off_by_its_own_preference() {
+        let cases = [
+            (NotificationKind::Mention, "mention"),
+            (NotificationKind::Reply, "reply"),
+            (NotificationKind::Like, "like"),
+            (NotificationKind::Follow, "follow"),
+            (NotificationKind::Repost, "repost"),
+            (NotificationKind::Quote, "quote"),
+            (NotificationKind::LikeViaRepost, "likeViaRepost"),
+            (NotificationKind::RepostViaRepost, "repostViaRepost"),
+            (NotificationKind::ActivityPost, "subscribedPost"),
+            (NotificationKind::ActivityReply, "subscribedPost"),
+            (NotificationKind::StarterpackJoined, "starterpackJoined"),
+            (NotificationKind::Verified, "verified"),
+            (NotificationKind::Unverified, "unverified"),
+            (NotificationKind::ChatMessage, "chat"),
+        ];
+        assert_eq!(cases.len(), NotificationKind::ALL.len());
+
+        for (_, field) in cases {
+            let mut input = serde_json::Map::new();
+            input.insert(field.to_string(), json!({ "push": false }));
+            let input: PutPreferencesInput = serde_json::from_value(input.into()).unwrap();
+            let prefs = input.apply_to(PushPreferencesDocument::default());
+
+            let governed: Vec<_> = cases
+                .iter()
+                .filter(|(_, candidate)| *candidate == field)
+                .map(|(kind, _)| *kind)
+                .collect();
+            for kind in NotificationKind::ALL {
+                assert_eq!(
+                    prefs.is_push_enabled_for(kind),
+                    !governed.contains(&kind),
+                    "{field} vs {kind}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_queue_types_do_not_become_rows() {
+        let record = QueueRecord {
+            id: 9,
+            recipient_did: "did:plc:recipient".to_string(),
+            actor_did: "did:plc:actor".to_string(),
+            notification_type: "poke".to_string(),
+            event_cid: "bafy".to_string(),
+            event_path: "app.example.poke/1".to_string(),
+            subject_uri: None,
+            thread_root_uri: None,
+            event_record_json: json!({}),
+            event_timestamp: 0,
+            created_at: sqlx::types::time::OffsetDateTime::now_utc(),
+            attempts: 1,
+            quiet_deferred: false,
+            badge_counted: false,
+        };
+
+        let err = QueueRow::try_from(record.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown notification type: poke");
+
+        let row = QueueRow::try_from(QueueRecord {
+            notification_type: "starterpack_joined".to_string(),
+            ..record
+        })
+        .unwrap();
+        assert_eq!(row.notification_type, NotificationKind::StarterpackJoined);
+    }
+
+    #[test]
+    fn device_overrides_apply_over_account_preferences() {
+        let mut account = PushPreferencesDocument::default();
+        account.like.include = "follows".to_string();
+
+        let dms_only = json!({
+            "follow": { "push": false }, "like": { "push": false },
+            "likeViaRepost": { "push": false }, "mention": { "push": false },
+            "quote": { "push": false }, "reply": { "push": false },
+            "repost": { "push": false }, "repostViaRepost": { "push": false },
+            "starterpackJoined": { "push": false }, "subscribedPost": { "push": false },
+            "unverified": { "push": false }, "verified": { "push": false },
+        });
+        let ipad = account.with_overrides(Some(&dms_only));
+        for kind in NotificationKind::ALL {
+            assert_eq!(
+                ipad.is_push_enabled_for(kind),
+                kind == NotificationKind::ChatMessage,
+                "{kind}"
+            );
+        }
+
+        let phone = account.with_overrides(None);
+        assert!(phone.is_push_enabled_for(NotificationKind::Like));
+        assert!(phone.is_follows_only_for(NotificationKind::Like));
+
+        let unreadable = account.with_overrides(Some(&json!({ "like": "off" })));
+        assert!(unreadable.is_push_enabled_for(NotificationKind::Like));
+    }
+}
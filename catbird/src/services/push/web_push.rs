@@ -0,0 +1,679 @@
+//! Web Push delivery for browser clients (catmos-web).
+//!
+//! Browsers register with `platform: "web"` and send their
+//! `PushSubscription.toJSON()` as the `token`. Payloads are encrypted to the
+//! subscription's `p256dh` key and `auth` secret (RFC 8291, `aes128gcm`
+//! content coding from RFC 8188) and every request carries a VAPID JWT
+//! (RFC 8292) signed with the configured P-256 key. A 404 or 410 from the
+//! push service means the subscription is gone for good; the worker then
+//! deactivates the registration the same way it does for dead APNs tokens.
+
+use std::time::Duration;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes128Gcm, Nonce,
+};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::future::BoxFuture;
+use hkdf::Hkdf;
+use p256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey, SecretKey,
+};
+use rand::RngCore;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::config::WebPushConfig;
+
+use super::{
+    transport::{DeliveryError, DeliveryReceipt, PushNotification, PushTransport},
+    types::RegistrationRow,
+};
+
+/// `user_devices.platform` for Web Push registrations.
+pub const PLATFORM_WEB: &str = "web";
+
+/// Record size advertised in the aes128gcm header; every payload fits in one
+/// record.
+const RECORD_SIZE: u32 = 4096;
+/// salt (16) + rs (4) + idlen (1) + keyid (65-byte uncompressed point).
+const HEADER_LEN: usize = 86;
+const TAG_LEN: usize = 16;
+/// Push services only have to accept 4096-byte bodies (RFC 8291 §4). One
+/// byte goes to the last-record padding delimiter.
+const MAX_PLAINTEXT_LEN: usize = 4096 - HEADER_LEN - TAG_LEN - 1;
+/// RFC 8292 caps VAPID tokens at 24 hours; stay well under it.
+const VAPID_TOKEN_LIFETIME_SECS: i64 = 12 * 3600;
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebPushError {
+    #[error("push service rejected message with status {status}: {reason}")]
+    Rejected { status: u16, reason: String },
+    #[error("invalid Web Push subscription: {0}")]
+    InvalidSubscription(String),
+}
+
+impl WebPushError {
+    /// The subscription can never be delivered to again.
+    pub fn is_gone(&self) -> bool {
+        match self {
+            WebPushError::Rejected { status, .. } => matches!(status, 404 | 410),
+            WebPushError::InvalidSubscription(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebPushKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// A browser `PushSubscription`, as produced by `toJSON()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebPushSubscription {
+    pub endpoint: String,
+    pub keys: WebPushKeys,
+}
+
+impl WebPushSubscription {
+    /// Parse a registration token and check that its keys are usable.
+    pub fn parse(token: &str) -> Result<Self, WebPushError> {
+        let subscription: Self = serde_json::from_str(token).map_err(|e| {
+            WebPushError::InvalidSubscription(format!("not a PushSubscription: {e}"))
+        })?;
+        Url::parse(&subscription.endpoint)
+            .map_err(|e| WebPushError::InvalidSubscription(format!("invalid endpoint: {e}")))?;
+        subscription.ua_public_key()?;
+        subscription.auth_secret()?;
+        Ok(subscription)
+    }
+
+    /// Canonical form stored in `user_devices.device_token`, so registering
+    /// the same subscription twice (with `expirationTime`, different key
+    /// order, ...) updates one row.
+    pub fn to_token(&self) -> String {
+        json!({
+            "endpoint": self.endpoint,
+            "keys": { "p256dh": self.keys.p256dh, "auth": self.keys.auth },
+        })
+        .to_string()
+    }
+
+    /// Endpoints are chosen by the browser, so only public HTTPS hosts are
+    /// accepted before anything is stored.
+    pub fn validate_endpoint(&self) -> Result<(), WebPushError> {
+        if !self.endpoint.starts_with("https://") {
+            return Err(WebPushError::InvalidSubscription(
+                "endpoint must use https".into(),
+            ));
+        }
+        crate::services::ssrf::validate_pds_url(&self.endpoint)
+            .map_err(|_| WebPushError::InvalidSubscription("endpoint host is not allowed".into()))
+    }
+
+    fn ua_public_key(&self) -> Result<PublicKey, WebPushError> {
+        let bytes = decode_base64url(&self.keys.p256dh)
+            .ok_or_else(|| WebPushError::InvalidSubscription("p256dh is not base64url".into()))?;
+        PublicKey::from_sec1_bytes(&bytes)
+            .map_err(|_| WebPushError::InvalidSubscription("p256dh is not a P-256 point".into()))
+    }
+
+    fn auth_secret(&self) -> Result<[u8; 16], WebPushError> {
+        decode_base64url(&self.keys.auth)
+            .and_then(|bytes| <[u8; 16]>::try_from(bytes).ok())
+            .ok_or_else(|| WebPushError::InvalidSubscription("auth must be 16 bytes".into()))
+    }
+}
+
+/// Browsers emit unpadded base64url, but some libraries pad it.
+pub(super) fn decode_base64url(value: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(value.trim_end_matches('=')).ok()
+}
+
+#[derive(Clone)]
+pub struct WebPushDelivery {
+    http_client: reqwest::Client,
+    vapid_key: SigningKey,
+    /// Uncompressed VAPID public key, base64url: the browser's
+    /// `applicationServerKey` and the `k=` parameter on every request.
+    public_key: String,
+    subject: String,
+    ttl_seconds: u32,
+}
+
+impl WebPushDelivery {
+    pub fn new(config: &WebPushConfig) -> Result<Option<Self>> {
+        let (Some(private_key), Some(subject)) = (
+            config.vapid_private_key.as_deref(),
+            config.vapid_subject.as_deref(),
+        ) else {
+            tracing::info!(
+                "Web Push delivery is not configured; web registrations will be skipped"
+            );
+            return Ok(None);
+        };
+
+        let key_bytes = decode_base64url(private_key)
+            .context("push.web_push.vapid_private_key is not base64url")?;
+        let vapid_key = SigningKey::from_slice(&key_bytes)
+            .context("push.web_push.vapid_private_key is not a P-256 private key")?;
+        let public_key =
+            URL_SAFE_NO_PAD.encode(vapid_key.verifying_key().to_encoded_point(false).as_bytes());
+
+        // Never follow redirects: the endpoint was vetted at registration,
+        // whatever it redirects to was not.
+        let http_client = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        Ok(Some(Self {
+            http_client,
+            vapid_key,
+            public_key,
+            subject: subject.to_string(),
+            ttl_seconds: config.ttl_seconds,
+        }))
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    pub async fn send(
+        &self,
+        registration: &RegistrationRow,
+        notification: &PushNotification,
+    ) -> Result<DeliveryReceipt> {
+        let subscription = WebPushSubscription::parse(&registration.device_token)?;
+        let endpoint = Url::parse(&subscription.endpoint)?;
+
+        let plaintext = build_payload(notification)?;
+        let salt = random_bytes::<16>();
+        let sender_key = SecretKey::random(&mut rand::rngs::OsRng);
+        let body = encrypt(
+            &sender_key,
+            &subscription.ua_public_key()?,
+            &subscription.auth_secret()?,
+            salt,
+            &plaintext,
+        )?;
+
+        let authorization = self.vapid_authorization(&endpoint, chrono::Utc::now().timestamp())?;
+
+        let mut request = self
+            .http_client
+            .post(endpoint)
+            .header("TTL", self.ttl_seconds)
+            .header("Urgency", "high")
+            .header(reqwest::header::CONTENT_ENCODING, "aes128gcm")
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        if let Some(ref collapse_id) = notification.collapse_id {
+            // RFC 8030 §5.4: a pending message with the same topic is replaced.
+            request = request.header("Topic", collapse_id);
+        }
+        let response = request.body(body).send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(DeliveryReceipt {
+                status: status.as_u16(),
+                provider_id: response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .map(str::to_string),
+                environment: None,
+            });
+        }
+        let reason: String = response
+            .text()
+            .await
+            .unwrap_or_default()
+            .chars()
+            .take(200)
+            .collect();
+        Err(WebPushError::Rejected {
+            status: status.as_u16(),
+            reason,
+        }
+        .into())
+    }
+
+    /// `Authorization: vapid t=<jwt>, k=<public key>` for one push service.
+    fn vapid_authorization(&self, endpoint: &Url, now: i64) -> Result<String> {
+        let header = json!({ "typ": "JWT", "alg": "ES256" });
+        let claims = json!({
+            "aud": endpoint.origin().ascii_serialization(),
+            "exp": now + VAPID_TOKEN_LIFETIME_SECS,
+            "sub": self.subject,
+        });
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+        );
+        let signature: Signature = self.vapid_key.sign(signing_input.as_bytes());
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+            self.public_key
+        ))
+    }
+}
+
+impl PushTransport for WebPushDelivery {
+    fn send<'a>(
+        &'a self,
+        registration: &'a RegistrationRow,
+        notification: &'a PushNotification,
+    ) -> BoxFuture<'a, Result<DeliveryReceipt, DeliveryError>> {
+        Box::pin(async move {
+            WebPushDelivery::send(self, registration, notification)
+                .await
+                .map_err(|err| match err.downcast_ref::<WebPushError>() {
+                    Some(web_push_err) if web_push_err.is_gone() => DeliveryError::InvalidToken {
+                        reason: "webpush_gone",
+                        detail: err.to_string(),
+                        response: response(&err),
+                    },
+                    _ => DeliveryError::Other(err),
+                })
+        })
+    }
+}
+
+/// The status the push service answered a rejected message with.
+pub(super) fn response(err: &anyhow::Error) -> Option<DeliveryReceipt> {
+    match err.downcast_ref::<WebPushError>() {
+        Some(WebPushError::Rejected { status, .. }) => Some(DeliveryReceipt {
+            status: *status,
+            ..DeliveryReceipt::default()
+        }),
+        _ => None,
+    }
+}
+
+/// The JSON the catmos-web service worker hands to `showNotification`.
+/// Rich content, then custom data, is dropped if it would push the message
+/// past what every push service accepts.
+fn build_payload(notification: &PushNotification) -> Result<Vec<u8>> {
+    let mut payload = json!({ "data": notification.custom_data });
+    // Alert-less updates aren't sent to browsers (see
+    // `PushTransports::for_notification`), so this only guards the payload.
+    if notification.has_alert() {
+        payload["title"] = Value::String(notification.title.clone());
+        payload["body"] = Value::String(notification.body.clone());
+    }
+    if let Some(ref thread_id) = notification.thread_id {
+        payload["tag"] = Value::String(thread_id.clone());
+    }
+    if notification.silent {
+        payload["silent"] = Value::Bool(true);
+    }
+    if let Some(ref rich) = notification.rich {
+        payload["rich"] = serde_json::to_value(rich)?;
+    }
+
+    let mut bytes = serde_json::to_vec(&payload)?;
+    if bytes.len() > MAX_PLAINTEXT_LEN && notification.rich.is_some() {
+        if let Some(fields) = payload.as_object_mut() {
+            fields.remove("rich");
+        }
+        bytes = serde_json::to_vec(&payload)?;
+    }
+    if bytes.len() > MAX_PLAINTEXT_LEN {
+        payload["data"] = json!({});
+        bytes = serde_json::to_vec(&payload)?;
+    }
+    if bytes.len() > MAX_PLAINTEXT_LEN {
+        return Err(anyhow!(
+            "Web Push payload is {} bytes, limit is {}",
+            bytes.len(),
+            MAX_PLAINTEXT_LEN
+        ));
+    }
+    Ok(bytes)
+}
+
+pub(super) fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+pub(super) fn hkdf_expand<const N: usize>(salt: &[u8], ikm: &[u8], info: &[u8]) -> Result<[u8; N]> {
+    let mut okm = [0u8; N];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut okm)
+        .map_err(|_| anyhow!("HKDF output length {} is invalid", N))?;
+    Ok(okm)
+}
+
+/// Encrypt one message as a single aes128gcm record (RFC 8291 §3).
+fn encrypt(
+    sender_key: &SecretKey,
+    ua_public: &PublicKey,
+    auth_secret: &[u8; 16],
+    salt: [u8; 16],
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let as_public = sender_key.public_key().to_encoded_point(false);
+    let ua_public_bytes = ua_public.to_encoded_point(false);
+    let shared = p256::ecdh::diffie_hellman(sender_key.to_nonzero_scalar(), ua_public.as_affine());
+
+    let mut key_info = Vec::with_capacity(14 + 65 + 65);
+    key_info.extend_from_slice(b"WebPush: info\0");
+    key_info.extend_from_slice(ua_public_bytes.as_bytes());
+    key_info.extend_from_slice(as_public.as_bytes());
+    let ikm: [u8; 32] = hkdf_expand(auth_secret, shared.raw_secret_bytes(), &key_info)?;
+
+    let cek: [u8; 16] = hkdf_expand(&salt, &ikm, b"Content-Encoding: aes128gcm\0")?;
+    let nonce: [u8; 12] = hkdf_expand(&salt, &ikm, b"Content-Encoding: nonce\0")?;
+
+    let mut record = Vec::with_capacity(plaintext.len() + 1);
+    record.extend_from_slice(plaintext);
+    record.push(0x02); // last-record delimiter, no padding
+
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|_| anyhow!("invalid content-encryption key length"))?
+        .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+        .map_err(|_| anyhow!("aes128gcm encryption failed"))?;
+
+    let mut body = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.as_bytes().len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::push::rich_content::RichContent;
+    use std::collections::HashMap;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// What a browser does on receipt (RFC 8291 §3.4), for round-trip tests.
+    fn decrypt(ua_private: &SecretKey, auth_secret: &[u8; 16], body: &[u8]) -> Vec<u8> {
+        let salt = &body[..16];
+        let key_id_len = body[20] as usize;
+        let as_public = PublicKey::from_sec1_bytes(&body[21..21 + key_id_len]).unwrap();
+        let ciphertext = &body[21 + key_id_len..];
+
+        let shared =
+            p256::ecdh::diffie_hellman(ua_private.to_nonzero_scalar(), as_public.as_affine());
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(ua_private.public_key().to_encoded_point(false).as_bytes());
+        key_info.extend_from_slice(as_public.to_encoded_point(false).as_bytes());
+        let ikm: [u8; 32] = hkdf_expand(auth_secret, shared.raw_secret_bytes(), &key_info).unwrap();
+        let cek: [u8; 16] = hkdf_expand(salt, &ikm, b"Content-Encoding: aes128gcm\0").unwrap();
+        let nonce: [u8; 12] = hkdf_expand(salt, &ikm, b"Content-Encoding: nonce\0").unwrap();
+
+        let mut record = Aes128Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .unwrap();
+        assert_eq!(record.pop(), Some(0x02));
+        record
+    }
+
+    fn b64(value: &str) -> Vec<u8> {
+        decode_base64url(value).unwrap()
+    }
+
+    #[test]
+    fn encrypt_matches_rfc8291_example() {
+        // RFC 8291 Appendix A.
+        let sender_key =
+            SecretKey::from_slice(&b64("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw")).unwrap();
+        let ua_public = PublicKey::from_sec1_bytes(&b64(
+            "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+        ))
+        .unwrap();
+        let auth: [u8; 16] = b64("BTBZMqHH6r4Tts7J_aSIgg").try_into().unwrap();
+        let salt: [u8; 16] = b64("DGv6ra1nlYgDCS1FRnbzlw").try_into().unwrap();
+
+        let body = encrypt(
+            &sender_key,
+            &ua_public,
+            &auth,
+            salt,
+            b"When I grow up, I want to be a watermelon",
+        )
+        .unwrap();
+
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(body),
+            "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+        );
+    }
+
+    struct Browser {
+        private_key: SecretKey,
+        auth: [u8; 16],
+    }
+
+    impl Browser {
+        fn new() -> Self {
+            Self {
+                private_key: SecretKey::random(&mut rand::rngs::OsRng),
+                auth: random_bytes::<16>(),
+            }
+        }
+
+        fn registration(&self, endpoint: &str) -> RegistrationRow {
+            let subscription = WebPushSubscription {
+                endpoint: endpoint.to_string(),
+                keys: WebPushKeys {
+                    p256dh: URL_SAFE_NO_PAD.encode(
+                        self.private_key
+                            .public_key()
+                            .to_encoded_point(false)
+                            .as_bytes(),
+                    ),
+                    auth: URL_SAFE_NO_PAD.encode(self.auth),
+                },
+            };
+            RegistrationRow {
+                id: sqlx::types::Uuid::new_v4(),
+                did: "did:plc:alice".into(),
+                device_token: subscription.to_token(),
+                platform: PLATFORM_WEB.into(),
+                app_id: "blue.catbird.web".into(),
+                service_did: None,
+                age_restricted: false,
+                is_active: true,
+                apns_environment: None,
+                locale: None,
+                preference_overrides: None,
+                preview_key: None,
+            }
+        }
+    }
+
+    fn delivery() -> WebPushDelivery {
+        let key = SecretKey::random(&mut rand::rngs::OsRng);
+        WebPushDelivery::new(&WebPushConfig {
+            vapid_private_key: Some(URL_SAFE_NO_PAD.encode(key.to_bytes())),
+            vapid_subject: Some("mailto:ops@catbird.blue".into()),
+            ttl_seconds: 60,
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    fn notification() -> PushNotification {
+        PushNotification {
+            title: "Alice".into(),
+            body: "liked your post".into(),
+            localization: None,
+            user_did: "did:plc:alice".into(),
+            custom_data: HashMap::from([("type".to_string(), "like".to_string())]),
+            mutable_content: false,
+            rich: None,
+            thread_id: Some("thread".into()),
+            collapse_id: None,
+            silent: false,
+            content_available: false,
+            badge: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_an_encrypted_vapid_signed_message() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/push/abc"))
+            .and(header("content-encoding", "aes128gcm"))
+            .and(header("ttl", "60"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let browser = Browser::new();
+        let delivery = delivery();
+        delivery
+            .send(
+                &browser.registration(&format!("{}/push/abc", server.uri())),
+                &notification(),
+            )
+            .await
+            .expect("delivered");
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let payload: Value =
+            serde_json::from_slice(&decrypt(&browser.private_key, &browser.auth, &request.body))
+                .unwrap();
+        assert_eq!(payload["title"], "Alice");
+        assert_eq!(payload["data"]["type"], "like");
+        assert_eq!(payload["tag"], "thread");
+
+        let authorization = request
+            .headers
+            .get("authorization")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let (token, key) = authorization
+            .strip_prefix("vapid t=")
+            .and_then(|rest| rest.split_once(", k="))
+            .unwrap();
+        assert_eq!(key, delivery.public_key());
+
+        // The JWT is ES256 over the push service's origin.
+        use p256::ecdsa::{signature::Verifier, VerifyingKey};
+        let (signing_input, signature) = token.rsplit_once('.').unwrap();
+        let verifying_key = VerifyingKey::from_sec1_bytes(&b64(key)).unwrap();
+        let signature = Signature::from_slice(&b64(signature)).unwrap();
+        verifying_key
+            .verify(signing_input.as_bytes(), &signature)
+            .expect("valid VAPID signature");
+        let claims: Value =
+            serde_json::from_slice(&b64(signing_input.split('.').nth(1).unwrap())).unwrap();
+        assert_eq!(claims["aud"], server.uri());
+        assert_eq!(claims["sub"], "mailto:ops@catbird.blue");
+    }
+
+    #[tokio::test]
+    async fn gone_subscriptions_are_reported_as_gone() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/expired"))
+            .respond_with(ResponseTemplate::new(410))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/overloaded"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let browser = Browser::new();
+        let delivery = delivery();
+
+        let expired = browser.registration(&format!("{}/expired", server.uri()));
+        let err = PushTransport::send(&delivery, &expired, &notification())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            DeliveryError::InvalidToken {
+                reason: "webpush_gone",
+                ..
+            }
+        ));
+
+        let overloaded = browser.registration(&format!("{}/overloaded", server.uri()));
+        let err = PushTransport::send(&delivery, &overloaded, &notification())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DeliveryError::Other(_)));
+    }
+
+    #[test]
+    fn subscriptions_are_canonicalized_and_vetted() {
+        let browser = Browser::new();
+        let token = browser
+            .registration("https://fcm.googleapis.com/fcm/send/x")
+            .device_token;
+        let with_extras: Value = serde_json::from_str(&token)
+            .map(|mut v: Value| {
+                v["expirationTime"] = Value::Null;
+                v
+            })
+            .unwrap();
+
+        let parsed = WebPushSubscription::parse(&with_extras.to_string()).unwrap();
+        assert_eq!(parsed.to_token(), token);
+        assert!(parsed.validate_endpoint().is_ok());
+
+        let mut local = parsed.clone();
+        local.endpoint = "https://127.0.0.1/push".into();
+        assert!(local.validate_endpoint().is_err());
+        local.endpoint = "http://push.example.com/".into();
+        assert!(local.validate_endpoint().is_err());
+
+        assert!(WebPushSubscription::parse("not json").is_err());
+        let mut short_auth = parsed;
+        short_auth.keys.auth = "AAAA".into();
+        assert!(WebPushSubscription::parse(&short_auth.to_token()).is_err());
+    }
+
+    #[test]
+    fn oversized_payloads_drop_rich_content_before_custom_data() {
+        let mut rich_notification = notification();
+        rich_notification.rich = Some(RichContent {
+            parent_text: Some("x".repeat(MAX_PLAINTEXT_LEN)),
+            ..RichContent::default()
+        });
+
+        let payload: Value =
+            serde_json::from_slice(&build_payload(&rich_notification).unwrap()).unwrap();
+        assert!(payload.get("rich").is_none());
+        assert_eq!(payload["data"]["type"], "like");
+
+        rich_notification.rich = Some(RichContent {
+            avatar_url: Some("https://cdn.bsky.app/avatar".into()),
+            ..RichContent::default()
+        });
+        let payload: Value =
+            serde_json::from_slice(&build_payload(&rich_notification).unwrap()).unwrap();
+        assert_eq!(payload["rich"]["avatarUrl"], "https://cdn.bsky.app/avatar");
+    }
+}
@@ -11,10 +11,14 @@ use jacquard_oauth::session::{AuthRequestData, ClientSessionData, DpopClientData
 use jacquard_oauth::types::{OAuthTokenType, TokenSet};
 use redis::{AsyncCommands, Expiry};
 
-use super::redis_crypto::{decrypt_from_redis, encrypt_for_redis};
+use super::app_password::AppPasswordSessionData;
+use super::redis_crypto::{decrypt_from_redis, encrypt_for_redis, open, seal};
+use crate::error::OAuthFailure;
+use crate::models::{AuthFailureRecord, AuthFailureSource};
 
 const STATE_TTL_SECONDS: u64 = 600; // 10 minutes for OAuth state
 const SESSION_INDEX_TTL_SECONDS: u64 = 86400 * 30; // 30 days
+const AUTH_FAILURE_TTL_SECONDS: u64 = 86400 * 7; // 7 days
 
 fn redis_err(e: redis::RedisError) -> SessionStoreError {
     SessionStoreError::Other(e.into())
@@ -30,6 +34,12 @@ fn other_err(msg: &str) -> SessionStoreError {
 ///   `{prefix}session:{did}_{session_id}`   → encrypted ClientSessionData JSON
 ///   `{prefix}auth_req:{state}`             → encrypted AuthRequestData JSON
 ///   `{prefix}session_index:{session_id}`   → DID string (for session_id→DID lookup)
+///   `{prefix}app_password:{session_id}`    → sealed AppPasswordSessionData JSON
+///   `{prefix}app_password_lock:{session_id}` → refresh lock token (short TTL)
+///   `{prefix}refresh_schedule`             → ZSET session_id scored by access-token expiry
+///   `{prefix}refresh_activity`             → ZSET session_id scored by last use
+///   `{prefix}refresh_claim:{session_id}`   → background refresh claim (short TTL)
+///   `{prefix}auth_failure:{session_id}`    → last AuthFailureRecord JSON (7 days)
 #[derive(Clone)]
 pub struct RedisAuthStore {
     redis: redis::aio::ConnectionManager,
@@ -65,6 +75,30 @@ impl RedisAuthStore {
         format!("{}session_index:{}", self.key_prefix, session_id)
     }
 
+    fn app_password_key(&self, session_id: &str) -> String {
+        format!("{}app_password:{}", self.key_prefix, session_id)
+    }
+
+    fn app_password_lock_key(&self, session_id: &str) -> String {
+        format!("{}app_password_lock:{}", self.key_prefix, session_id)
+    }
+
+    fn refresh_schedule_key(&self) -> String {
+        format!("{}refresh_schedule", self.key_prefix)
+    }
+
+    fn refresh_activity_key(&self) -> String {
+        format!("{}refresh_activity", self.key_prefix)
+    }
+
+    fn refresh_claim_key(&self, session_id: &str) -> String {
+        format!("{}refresh_claim:{}", self.key_prefix, session_id)
+    }
+
+    fn auth_failure_key(&self, session_id: &str) -> String {
+        format!("{}auth_failure:{}", self.key_prefix, session_id)
+    }
+
     fn enc_key(&self) -> Option<&[u8; 32]> {
         self.encryption_key.as_ref()
     }
@@ -104,6 +138,276 @@ impl RedisAuthStore {
             .await
     }
 
+    /// Read an app-password session record.
+    ///
+    /// Unlike Jacquard sessions there is no plaintext fallback: the record
+    /// holds a long-lived refresh JWT, so it is only ever written sealed and a
+    /// value that fails to open is treated as corrupt. Slides the TTL like
+    /// `get_session` does.
+    pub async fn get_app_password_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<AppPasswordSessionData>, SessionStoreError> {
+        let key = self.app_password_key(session_id);
+        let mut conn = self.redis.clone();
+
+        let sealed: Option<String> = conn
+            .get_ex(&key, Expiry::EX(self.session_ttl as usize))
+            .await
+            .map_err(redis_err)?;
+        let Some(sealed) = sealed else {
+            return Ok(None);
+        };
+
+        let enc_key = self
+            .enc_key()
+            .ok_or_else(|| other_err("app-password sessions require SESSION_ENCRYPTION_KEY"))?;
+        let plaintext = open(enc_key, &sealed)
+            .map_err(|e| other_err(&format!("failed to open app-password session: {e}")))?;
+        let data = serde_json::from_slice(&plaintext).map_err(SessionStoreError::Serde)?;
+        Ok(Some(data))
+    }
+
+    /// Seal and store an app-password session record, and point the
+    /// session index at its DID so DID lookups work for both session kinds.
+    pub async fn put_app_password_session(
+        &self,
+        session_id: &str,
+        data: &AppPasswordSessionData,
+    ) -> Result<(), SessionStoreError> {
+        let enc_key = self
+            .enc_key()
+            .ok_or_else(|| other_err("app-password sessions require SESSION_ENCRYPTION_KEY"))?;
+        let json = serde_json::to_vec(data).map_err(SessionStoreError::Serde)?;
+        let sealed = seal(enc_key, &json)
+            .map_err(|e| other_err(&format!("failed to seal app-password session: {e}")))?;
+
+        let mut conn = self.redis.clone();
+        conn.set_ex::<_, _, ()>(&self.app_password_key(session_id), sealed, self.session_ttl)
+            .await
+            .map_err(redis_err)?;
+        self.write_session_index(session_id, &data.did)
+            .await
+            .map_err(redis_err)?;
+        self.schedule_refresh(session_id, data.access_expires_at.timestamp())
+            .await
+            .map_err(redis_err)?;
+        Ok(())
+    }
+
+    /// Remove an app-password session record and its index entry.
+    pub async fn delete_app_password_session(
+        &self,
+        session_id: &str,
+    ) -> Result<(), SessionStoreError> {
+        let mut conn = self.redis.clone();
+        conn.del::<_, ()>(&self.app_password_key(session_id))
+            .await
+            .map_err(redis_err)?;
+        conn.del::<_, ()>(&self.session_index_key(session_id))
+            .await
+            .map_err(redis_err)?;
+        self.unschedule_refresh(session_id)
+            .await
+            .map_err(redis_err)?;
+        Ok(())
+    }
+
+    /// Take the per-session refresh lock (`SET NX EX`). Returns `false` when
+    /// another request or replica already holds it.
+    pub async fn try_lock_app_password_refresh(
+        &self,
+        session_id: &str,
+        token: &str,
+        ttl_seconds: u64,
+    ) -> Result<bool, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(self.app_password_lock_key(session_id))
+            .arg(token)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_seconds)
+            .query_async(&mut conn)
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    /// Release the refresh lock, but only if `token` still owns it — a lock
+    /// that expired and was re-taken by someone else must not be deleted.
+    pub async fn unlock_app_password_refresh(
+        &self,
+        session_id: &str,
+        token: &str,
+    ) -> Result<(), redis::RedisError> {
+        let script = redis::Script::new(
+            r#"if redis.call("GET", KEYS[1]) == ARGV[1] then return redis.call("DEL", KEYS[1]) else return 0 end"#,
+        );
+        let mut conn = self.redis.clone();
+        script
+            .key(self.app_password_lock_key(session_id))
+            .arg(token)
+            .invoke_async::<_, i64>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Read the DID for a session without sliding the index TTL. For
+    /// background jobs, which must not keep idle sessions alive.
+    pub async fn peek_did_for_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<String>, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.get(self.session_index_key(session_id)).await
+    }
+
+    /// Record (or move) a session in the refresh schedule, scored by the
+    /// unix time its access token expires.
+    pub async fn schedule_refresh(
+        &self,
+        session_id: &str,
+        expires_at_unix: i64,
+    ) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.zadd::<_, _, _, ()>(self.refresh_schedule_key(), session_id, expires_at_unix)
+            .await
+    }
+
+    /// Drop a session from the refresh schedule and activity index.
+    pub async fn unschedule_refresh(&self, session_id: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        redis::pipe()
+            .zrem(self.refresh_schedule_key(), session_id)
+            .ignore()
+            .zrem(self.refresh_activity_key(), session_id)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+    }
+
+    /// Note that a session was just used by a request or background job.
+    /// Only sessions with recent activity are refreshed ahead of expiry.
+    pub async fn record_session_activity(&self, session_id: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.zadd::<_, _, _, ()>(
+            self.refresh_activity_key(),
+            session_id,
+            chrono::Utc::now().timestamp(),
+        )
+        .await
+    }
+
+    /// Forget activity older than `before_unix` (sessions that were resolved
+    /// once and never again, or whose record vanished without a delete).
+    pub async fn prune_session_activity(&self, before_unix: i64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.zrembyscore(self.refresh_activity_key(), "-inf", before_unix)
+            .await
+    }
+
+    /// Unix time a session was last used, if recorded.
+    pub async fn session_last_active(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<i64>, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        let score: Option<f64> = conn.zscore(self.refresh_activity_key(), session_id).await?;
+        Ok(score.map(|s| s as i64))
+    }
+
+    /// Sessions whose access token expires at or before `until_unix`, soonest
+    /// first, as `(session_id, expires_at_unix)`.
+    pub async fn sessions_due_for_refresh(
+        &self,
+        until_unix: i64,
+        limit: isize,
+    ) -> Result<Vec<(String, i64)>, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        let due: Vec<(String, f64)> = conn
+            .zrangebyscore_limit_withscores(
+                self.refresh_schedule_key(),
+                "-inf",
+                until_unix,
+                0,
+                limit,
+            )
+            .await?;
+        Ok(due
+            .into_iter()
+            .map(|(session_id, score)| (session_id, score as i64))
+            .collect())
+    }
+
+    /// Number of scheduled sessions expiring at or before `until_unix`.
+    pub async fn count_due_for_refresh(&self, until_unix: i64) -> Result<u64, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.zcount(self.refresh_schedule_key(), "-inf", until_unix)
+            .await
+    }
+
+    /// Claim a session for background refresh across replicas (`SET NX EX`).
+    /// A claim left in place after a failure doubles as the retry backoff.
+    pub async fn try_claim_refresh(
+        &self,
+        session_id: &str,
+        ttl_seconds: u64,
+    ) -> Result<bool, redis::RedisError> {
+        let mut conn = self.redis.clone();
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(self.refresh_claim_key(session_id))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_seconds)
+            .query_async(&mut conn)
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    pub async fn release_refresh_claim(&self, session_id: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.clone();
+        conn.del(self.refresh_claim_key(session_id)).await
+    }
+
+    /// Remember the latest OAuth / refresh failure for a session so
+    /// `/auth/session` and the admin port can explain a forced logout. Kept
+    /// past the session itself, since permanent failures delete it.
+    pub async fn record_auth_failure(
+        &self,
+        session_id: &str,
+        source: AuthFailureSource,
+        failure: &OAuthFailure,
+    ) -> Result<(), SessionStoreError> {
+        let record = AuthFailureRecord {
+            source,
+            failure: failure.clone(),
+            occurred_at: chrono::Utc::now(),
+        };
+        let json = serde_json::to_string(&record).map_err(SessionStoreError::Serde)?;
+        let mut conn = self.redis.clone();
+        conn.set_ex::<_, _, ()>(
+            self.auth_failure_key(session_id),
+            json,
+            AUTH_FAILURE_TTL_SECONDS,
+        )
+        .await
+        .map_err(redis_err)
+    }
+
+    pub async fn last_auth_failure(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<AuthFailureRecord>, SessionStoreError> {
+        let mut conn = self.redis.clone();
+        let json: Option<String> = conn
+            .get(self.auth_failure_key(session_id))
+            .await
+            .map_err(redis_err)?;
+        json.map(|j| serde_json::from_str(&j).map_err(SessionStoreError::Serde))
+            .transpose()
+    }
+
     /// Attempt to migrate a legacy (atrium) session to the new format.
     ///
     /// Legacy keys:
@@ -291,6 +595,19 @@ impl ClientAuthStore for RedisAuthStore {
         .await
         .map_err(redis_err)?;
 
+        // Keep the background refresher's schedule in step with every token
+        // write (login, lazy refresh, proactive refresh).
+        if let Some(expires_at) = session
+            .token_set
+            .expires_at
+            .as_ref()
+            .and_then(|dt| chrono::DateTime::parse_from_rfc3339(dt.as_str()).ok())
+        {
+            self.schedule_refresh(&session.session_id, expires_at.timestamp())
+                .await
+                .map_err(redis_err)?;
+        }
+
         Ok(())
     }
 
@@ -305,6 +622,9 @@ impl ClientAuthStore for RedisAuthStore {
 
         conn.del::<_, ()>(&key).await.map_err(redis_err)?;
         conn.del::<_, ()>(&index_key).await.map_err(redis_err)?;
+        self.unschedule_refresh(session_id)
+            .await
+            .map_err(redis_err)?;
 
         Ok(())
     }
@@ -110,9 +110,12 @@ impl ServiceAuthProvider {
     }
 
     async fn resolve_dpop_data(&self, session: &CatbirdSession) -> JacquardDpopData {
+        if session.auth_kind == crate::models::SessionAuthKind::AppPassword {
+            return JacquardDpopData::unbound();
+        }
         if let Some(jacquard_client) = &self.state.jacquard_client {
             if let Ok(did) = jacquard_common::types::did::Did::new(&session.did) {
-                if let Ok(session_data) = jacquard_client.registry.get(&did, &session.id.to_string(), true).await {
+                if let Ok(session_data) = jacquard_client.registry.get(&did, &session.id.to_string(), false).await {
                     return JacquardDpopData {
                         dpop_key: session_data.dpop_data.dpop_key.clone(),
                         dpop_host_nonce: session_data.dpop_data.dpop_host_nonce.to_string(),
@@ -122,13 +125,7 @@ impl ServiceAuthProvider {
         }
 
         // Ephemeral DPoP key fallback for testing or sessions without registry entries
-        let secret_key = p256::SecretKey::random(&mut rand::thread_rng());
-        let crypto_key = jose_jwk::crypto::Key::from(secret_key);
-        let dpop_key = jose_jwk::Key::from(&crypto_key);
-        JacquardDpopData {
-            dpop_key,
-            dpop_host_nonce: String::new(),
-        }
+        JacquardDpopData::unbound()
     }
 }
 
@@ -264,6 +261,7 @@ mod tests {
             access_token_expires_at: Utc::now() + chrono::Duration::hours(1),
             created_at: Utc::now(),
             last_used_at: Utc::now(),
+            auth_kind: crate::models::SessionAuthKind::OAuth,
         }
     }
 
@@ -0,0 +1,414 @@
+//! Background proactive token refresher
+//!
+//! Without this, access tokens are refreshed lazily: inside `auth_middleware`
+//! on the request path (Jacquard's 60s buffer, or `app_password`'s skew), or
+//! inside the push / chat-poll workers via `resolve_background_session`. A
+//! slow authorization server then shows up as user-facing latency or a
+//! stalled worker.
+//!
+//! Every token write goes through `RedisAuthStore`, which keeps a sorted set
+//! of session_id → access-token expiry. This loop scans that set every few
+//! seconds and refreshes anything expiring within `lead_seconds`, well ahead
+//! of the inline buffers, so the inline path almost always finds a fresh
+//! token.
+//!
+//! Coordination across replicas is a per-session `SET NX EX` claim: only one
+//! replica refreshes a given session per window. A claim is released on
+//! success and left to expire after a transient failure, which doubles as the
+//! retry backoff. Only sessions used within `idle_cutoff_seconds` (tracked by
+//! `record_session_activity`) are refreshed; idle ones drop out of the
+//! schedule and fall back to lazy refresh, so the refresher never keeps an
+//! abandoned session alive on its own.
+//!
+//! Inline OAuth refreshes (`oauth_session`) take the same claim. An OAuth
+//! refresh token is single-use, and jacquard deletes the session when a
+//! refresh is rejected, so a replica that refreshed alongside another would
+//! log the user out.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use jacquard_common::types::did::Did;
+use jacquard_oauth::session::ClientSessionData;
+
+use crate::config::{AppState, JacquardOAuthClient, TokenRefresherConfig};
+use crate::error::{AppError, OAuthFailure};
+use crate::metrics;
+use crate::middleware::record_auth_failure;
+use crate::models::AuthFailureSource;
+use crate::services::{app_password, RedisAuthStore};
+
+/// How many schedule entries to read per claimed slot, so a head of entries
+/// claimed by other replicas (or backing off) doesn't starve the rest.
+const SCAN_OVERFETCH: usize = 4;
+
+/// How close to expiry an inline lookup refreshes, matching jacquard's own
+/// buffer for `get(.., true)`.
+const INLINE_REFRESH_BUFFER_SECONDS: i64 = 60;
+/// How long an inline lookup waits on a refresh claimed elsewhere when its
+/// access token has already expired.
+const INLINE_WAIT_ATTEMPTS: u32 = 20;
+const INLINE_WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RefreshOutcome {
+    Refreshed,
+    AlreadyFresh,
+    Idle,
+    Missing,
+    PermanentFailure,
+    TransientFailure,
+}
+
+impl RefreshOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            RefreshOutcome::Refreshed => "refreshed",
+            RefreshOutcome::AlreadyFresh => "already_fresh",
+            RefreshOutcome::Idle => "idle",
+            RefreshOutcome::Missing => "missing",
+            RefreshOutcome::PermanentFailure => "permanent_failure",
+            RefreshOutcome::TransientFailure => "transient_failure",
+        }
+    }
+
+    /// Whether the session should leave the schedule. Successful refreshes
+    /// re-enter it through the store's token write.
+    fn unschedules(self) -> bool {
+        matches!(
+            self,
+            RefreshOutcome::Idle | RefreshOutcome::Missing | RefreshOutcome::PermanentFailure
+        )
+    }
+}
+
+/// Whether an access token expiring at `expires_at` is still good at `at`.
+/// Jacquard treats a token without an expiry as due, and so does this.
+fn is_fresh(expires_at: Option<i64>, at: i64) -> bool {
+    expires_at.is_some_and(|ts| ts > at)
+}
+
+fn access_expiry(session: &ClientSessionData<'_>) -> Option<i64> {
+    session
+        .token_set
+        .expires_at
+        .as_ref()
+        .and_then(|dt| chrono::DateTime::parse_from_rfc3339(dt.as_str()).ok())
+        .map(|dt| dt.timestamp())
+}
+
+/// Loads an OAuth session for a request or worker, refreshing it if due
+/// under the per-session claim the background loop takes. When another
+/// replica holds the claim, a still-valid token is used as is; an expired
+/// one waits for that refresh to land instead of spending the refresh token
+/// a second time.
+pub(crate) async fn oauth_session<'a>(
+    auth_store: &RedisAuthStore,
+    jacquard_client: &'a JacquardOAuthClient,
+    did: &Did<'_>,
+    session_id: &str,
+    claim_ttl_seconds: u64,
+    context: &str,
+) -> Result<ClientSessionData<'a>, AppError> {
+    let oauth_error = |err: jacquard_oauth::session::Error| {
+        AppError::OAuth(OAuthFailure::from_session_error(context, &err))
+    };
+
+    let current = jacquard_client
+        .registry
+        .get(did, session_id, false)
+        .await
+        .map_err(oauth_error)?;
+    let now = chrono::Utc::now().timestamp();
+    if is_fresh(access_expiry(&current), now + INLINE_REFRESH_BUFFER_SECONDS) {
+        return Ok(current);
+    }
+
+    for _ in 0..INLINE_WAIT_ATTEMPTS {
+        if auth_store
+            .try_claim_refresh(session_id, claim_ttl_seconds)
+            .await?
+        {
+            let result = jacquard_client
+                .registry
+                .refresh_if_expiring(did, session_id, INLINE_REFRESH_BUFFER_SECONDS)
+                .await;
+            if let Err(err) = auth_store.release_refresh_claim(session_id).await {
+                tracing::debug!(error = %err, "Failed to release refresh claim");
+            }
+            return result.map_err(oauth_error);
+        }
+
+        let current = jacquard_client
+            .registry
+            .get(did, session_id, false)
+            .await
+            .map_err(oauth_error)?;
+        if is_fresh(access_expiry(&current), chrono::Utc::now().timestamp()) {
+            return Ok(current);
+        }
+        tokio::time::sleep(INLINE_WAIT_INTERVAL).await;
+    }
+
+    Err(AppError::AuthTemporarilyUnavailable(
+        "Timed out waiting for a concurrent session refresh".into(),
+    ))
+}
+
+/// Whether a session last used at `last_active` is still worth keeping warm.
+fn is_recently_active(last_active: Option<i64>, now: i64, idle_cutoff_seconds: i64) -> bool {
+    last_active.is_some_and(|ts| now - ts <= idle_cutoff_seconds)
+}
+
+pub struct TokenRefresher {
+    state: Arc<AppState>,
+    config: TokenRefresherConfig,
+}
+
+impl TokenRefresher {
+    pub fn new(state: Arc<AppState>) -> Self {
+        let config = state.config.token_refresher.clone();
+        Self { state, config }
+    }
+
+    /// Spawn the refresh loop. No-op when the auth store is not configured.
+    pub fn spawn(self) {
+        if self.state.auth_store.is_none() {
+            tracing::warn!("Token refresher not started: auth store not configured");
+            return;
+        }
+        tokio::spawn(async move {
+            self.run_loop().await;
+        });
+    }
+
+    async fn run_loop(self) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            self.config.tick_seconds.max(1),
+        ));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        tracing::info!(
+            lead_seconds = self.config.lead_seconds,
+            max_concurrency = self.config.max_concurrency,
+            "Token refresher started"
+        );
+
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.tick().await {
+                tracing::warn!(error = %err, "Token refresher tick failed");
+            }
+        }
+    }
+
+    async fn tick(&self) -> Result<(), AppError> {
+        let Some(auth_store) = self.state.auth_store.as_deref() else {
+            return Ok(());
+        };
+        let now = chrono::Utc::now().timestamp();
+        let horizon = now + self.config.lead_seconds;
+
+        if let Ok(due) = auth_store.count_due_for_refresh(horizon).await {
+            metrics::set_token_refresh_due(due as f64);
+        }
+        if let Err(err) = auth_store
+            .prune_session_activity(now - self.config.idle_cutoff_seconds)
+            .await
+        {
+            tracing::debug!(error = %err, "Failed to prune session activity");
+        }
+
+        let candidates = auth_store
+            .sessions_due_for_refresh(horizon, (self.config.batch_size * SCAN_OVERFETCH) as isize)
+            .await?;
+
+        let mut claimed = Vec::with_capacity(self.config.batch_size);
+        for (session_id, _expires_at) in candidates {
+            if claimed.len() >= self.config.batch_size {
+                break;
+            }
+            if auth_store
+                .try_claim_refresh(&session_id, self.config.claim_ttl_seconds)
+                .await?
+            {
+                claimed.push(session_id);
+            }
+        }
+
+        if claimed.is_empty() {
+            return Ok(());
+        }
+        tracing::debug!(count = claimed.len(), "Token refresher claimed sessions");
+
+        futures_util::stream::iter(claimed)
+            .for_each_concurrent(
+                self.config.max_concurrency.max(1),
+                |session_id| async move {
+                    let (kind, outcome) = self.refresh_one(auth_store, &session_id, now).await;
+                    metrics::record_background_token_refresh(kind, outcome.as_str());
+
+                    if outcome.unschedules() {
+                        if let Err(err) = auth_store.unschedule_refresh(&session_id).await {
+                            tracing::debug!(error = %err, "Failed to unschedule session refresh");
+                        }
+                    }
+                    if outcome != RefreshOutcome::TransientFailure {
+                        if let Err(err) = auth_store.release_refresh_claim(&session_id).await {
+                            tracing::debug!(error = %err, "Failed to release refresh claim");
+                        }
+                    }
+                },
+            )
+            .await;
+
+        Ok(())
+    }
+
+    /// Refresh one session. Returns the session kind label and the outcome.
+    async fn refresh_one(
+        &self,
+        auth_store: &RedisAuthStore,
+        session_id: &str,
+        now: i64,
+    ) -> (&'static str, RefreshOutcome) {
+        let last_active = match auth_store.session_last_active(session_id).await {
+            Ok(ts) => ts,
+            Err(err) => {
+                tracing::debug!(error = %err, "Token refresher activity lookup failed");
+                return ("unknown", RefreshOutcome::TransientFailure);
+            }
+        };
+        if !is_recently_active(last_active, now, self.config.idle_cutoff_seconds) {
+            return ("unknown", RefreshOutcome::Idle);
+        }
+
+        let lead = self.config.lead_seconds;
+
+        // App-password sessions are held by Nest; try them first.
+        match app_password::refresh_if_expiring(&self.state, auth_store, session_id, lead).await {
+            Ok(Some(data)) => {
+                let outcome = if data.needs_refresh(lead) {
+                    RefreshOutcome::TransientFailure
+                } else {
+                    RefreshOutcome::Refreshed
+                };
+                return ("app_password", outcome);
+            }
+            Ok(None) => {}
+            Err(AppError::InvalidSession) => {
+                return ("app_password", RefreshOutcome::Missing);
+            }
+            Err(err) => {
+                tracing::info!(error = %err, "Background app-password refresh failed");
+                record_auth_failure(auth_store, session_id, AuthFailureSource::Refresher, &err)
+                    .await;
+                let outcome = match err {
+                    AppError::OAuth(failure) if failure.permanent => {
+                        RefreshOutcome::PermanentFailure
+                    }
+                    _ => RefreshOutcome::TransientFailure,
+                };
+                return ("app_password", outcome);
+            }
+        }
+
+        let Some(jacquard_client) = self.state.jacquard_client.as_ref() else {
+            return ("oauth", RefreshOutcome::TransientFailure);
+        };
+        let did_str = match auth_store.peek_did_for_session(session_id).await {
+            Ok(Some(did)) => did,
+            Ok(None) => return ("oauth", RefreshOutcome::Missing),
+            Err(err) => {
+                tracing::debug!(error = %err, "Token refresher session index lookup failed");
+                return ("oauth", RefreshOutcome::TransientFailure);
+            }
+        };
+        let Ok(did) = jacquard_common::types::did::Did::new(&did_str) else {
+            return ("oauth", RefreshOutcome::Missing);
+        };
+
+        match jacquard_client
+            .registry
+            .refresh_if_expiring(&did, session_id, lead)
+            .await
+        {
+            Ok(session) => {
+                let expires_at = session
+                    .token_set
+                    .expires_at
+                    .as_ref()
+                    .and_then(|dt| chrono::DateTime::parse_from_rfc3339(dt.as_str()).ok())
+                    .map(|dt| dt.timestamp());
+                match expires_at {
+                    Some(ts) if ts > now + lead => ("oauth", RefreshOutcome::Refreshed),
+                    // No expiry recorded: nothing to schedule against.
+                    None => ("oauth", RefreshOutcome::AlreadyFresh),
+                    Some(_) => ("oauth", RefreshOutcome::TransientFailure),
+                }
+            }
+            Err(err) => {
+                let failure =
+                    OAuthFailure::from_session_error("Background OAuth refresh failed", &err);
+                tracing::info!(
+                    did = %did_str,
+                    kind = failure.kind.as_str(),
+                    permanent = failure.permanent,
+                    error = %err,
+                    "Background OAuth refresh failed"
+                );
+                let outcome = if failure.permanent {
+                    RefreshOutcome::PermanentFailure
+                } else {
+                    RefreshOutcome::TransientFailure
+                };
+                record_auth_failure(
+                    auth_store,
+                    session_id,
+                    AuthFailureSource::Refresher,
+                    &AppError::OAuth(failure),
+                )
+                .await;
+                ("oauth", outcome)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_recent_sessions_are_kept_warm() {
+        let now = 1_000_000;
+        assert!(is_recently_active(Some(now - 60), now, 3600));
+        assert!(!is_recently_active(Some(now - 7200), now, 3600));
+        assert!(!is_recently_active(None, now, 3600));
+    }
+
+    #[test]
+    fn tokens_without_expiry_are_due() {
+        let now = 1_000_000;
+        assert!(is_fresh(
+            Some(now + 120),
+            now + INLINE_REFRESH_BUFFER_SECONDS
+        ));
+        assert!(!is_fresh(
+            Some(now + 30),
+            now + INLINE_REFRESH_BUFFER_SECONDS
+        ));
+        assert!(is_fresh(Some(now + 30), now));
+        assert!(!is_fresh(None, now));
+    }
+
+    #[test]
+    fn transient_failures_keep_schedule_and_claim() {
+        assert!(!RefreshOutcome::TransientFailure.unschedules());
+        assert!(!RefreshOutcome::Refreshed.unschedules());
+        assert!(RefreshOutcome::Idle.unschedules());
+        assert!(RefreshOutcome::PermanentFailure.unschedules());
+        assert!(RefreshOutcome::Missing.unschedules());
+    }
+}
@@ -363,6 +363,24 @@ where
         &self,
         did: &Did<'_>,
         session_id: &str,
+    ) -> Result<ClientSessionData<'_>, Error> {
+        // Check if token is still valid with a 60-second buffer before expiry.
+        // This triggers proactive refresh before the token actually expires,
+        // avoiding the race condition where a token expires mid-request.
+        const EXPIRY_BUFFER_SECS: i64 = 60;
+        self.refresh_if_expiring(did, session_id, EXPIRY_BUFFER_SECS)
+            .await
+    }
+
+    /// Refresh the session if its access token expires within `buffer_secs`,
+    /// otherwise return it unchanged. Shares the per-session lock with `get`,
+    /// so a background refresher and a request never refresh concurrently
+    /// within one process.
+    pub async fn refresh_if_expiring(
+        &self,
+        did: &Did<'_>,
+        session_id: &str,
+        buffer_secs: i64,
     ) -> Result<ClientSessionData<'_>, Error> {
         let key = format_smolstr!("{}_{}", did, session_id);
         let lock = self
@@ -378,14 +396,10 @@ where
             .await?
             .ok_or(Error::SessionNotFound)?;
 
-        // Check if token is still valid with a 60-second buffer before expiry.
-        // This triggers proactive refresh before the token actually expires,
-        // avoiding the race condition where a token expires mid-request.
-        const EXPIRY_BUFFER_SECS: i64 = 60;
         if let Some(expires_at) = &session.token_set.expires_at {
             let now_with_buffer = Datetime::now()
                 .as_ref()
-                .checked_add_signed(TimeDelta::seconds(EXPIRY_BUFFER_SECS))
+                .checked_add_signed(TimeDelta::seconds(buffer_secs))
                 .map(Datetime::new)
                 .unwrap_or_else(Datetime::now);
             if expires_at > &now_with_buffer {